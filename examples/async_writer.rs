@@ -0,0 +1,42 @@
+//! Logging from async code through `TokioConnect`, which writes on tokio's blocking pool.
+//!
+//! cargo run --example async_writer --features tokio
+
+use rusqlite::Connection;
+use tracing_subscriber_sqlite::{prepare_database, LogHandle, SubscriberBuilder, TokioConnect};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> rusqlite::Result<()> {
+    let path = std::env::temp_dir().join("tracing-sqlite-async-writer.db");
+    let _ = std::fs::remove_file(&path);
+
+    let conn = Connection::open(&path)?;
+    prepare_database(&conn)?;
+    let sink = TokioConnect::new(conn);
+    // the writer task ends once the subscriber and every clone of the sink are dropped, or
+    // when the runtime shuts down
+    let subscriber = tracing::subscriber::set_default(SubscriberBuilder::new().build(sink.clone()));
+
+    let tasks: Vec<_> = (0..4)
+        .map(|task| {
+            tokio::spawn(async move {
+                for i in 0..25 {
+                    tracing::info!(task, i, "working");
+                    tokio::task::yield_now().await;
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    // entries are written in the background, wait for them before reading or shutting down
+    sink.flushed().await;
+    drop((subscriber, sink));
+
+    let entries = LogHandle::new(Connection::open(&path)?).read_logs()?;
+    assert_eq!(entries.len(), 100);
+    println!("{} entries in {}", entries.len(), path.display());
+    Ok(())
+}
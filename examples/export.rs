@@ -0,0 +1,37 @@
+//! Exporting entries as JSON lines and CSV, and importing JSON lines into another database.
+//!
+//! cargo run --example export
+
+use tracing_subscriber_sqlite::{ExportProfile, LogHandle, LogQuery, SubscriberBuilder};
+
+fn main() -> rusqlite::Result<()> {
+    let handle = LogHandle::shared_memory("export-example")?;
+    tracing::subscriber::with_default(SubscriberBuilder::new().build(handle.clone()), || {
+        tracing::info!(user_id = 42, "signed in");
+        tracing::warn!(user_id = 42, attempts = 3, "wrong password, retrying");
+        tracing::error!("payment failed");
+    });
+
+    let mut jsonl = Vec::new();
+    let exported = handle.export_jsonl(&mut jsonl, &LogQuery::new())?;
+    assert_eq!(exported, 3);
+    print!("{}", String::from_utf8_lossy(&jsonl));
+
+    // the same entries with the field names of Elasticsearch's common schema
+    let mut ecs = Vec::new();
+    handle.export_jsonl_as(&mut ecs, &LogQuery::new(), ExportProfile::Ecs)?;
+    print!("{}", String::from_utf8_lossy(&ecs));
+
+    let mut csv = Vec::new();
+    handle.export_csv(&mut csv, &LogQuery::new().max_level(tracing::Level::WARN))?;
+    let csv = String::from_utf8(csv).unwrap();
+    assert_eq!(csv.lines().count(), 3, "a header and two rows");
+    print!("{csv}");
+
+    let copy = LogHandle::shared_memory("export-example-copy")?;
+    let report = copy.import_jsonl(jsonl.as_slice())?;
+    assert_eq!(report.imported, 3);
+    assert!(report.errors.is_empty());
+    assert_eq!(copy.read_logs()?[2].message, "payment failed");
+    Ok(())
+}
@@ -0,0 +1,53 @@
+//! Several processes writing to one database file, e.g. workers of one service.
+//!
+//! cargo run --example multi_process
+
+use std::{
+    process::Command,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rusqlite::Connection;
+use tracing_subscriber_sqlite::{LogHandle, LogQuery, SubscriberBuilder};
+
+const WORKERS: usize = 3;
+const EVENTS: usize = 50;
+
+fn main() -> rusqlite::Result<()> {
+    let path = std::env::temp_dir().join("tracing-sqlite-multi-process.db");
+
+    if let Some(worker) = std::env::args().nth(1) {
+        // WAL lets readers in while one process writes, the timeout makes writers wait their turn
+        let subscriber = SubscriberBuilder::new()
+            .with_pragma("journal_mode", "WAL")
+            .with_write_timeout(Duration::from_secs(10))
+            .build_prepared(Arc::new(Mutex::new(Connection::open(&path)?)))?;
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..EVENTS {
+                tracing::info!(worker = worker.as_str(), i, "working");
+            }
+        });
+        return Ok(());
+    }
+
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+    let exe = std::env::current_exe().expect("the path of this example");
+    let workers: Vec<_> = (0..WORKERS)
+        .map(|worker| Command::new(&exe).arg(worker.to_string()).spawn().unwrap())
+        .collect();
+    for mut worker in workers {
+        assert!(worker.wait().unwrap().success());
+    }
+
+    let handle = LogHandle::new(Connection::open(&path)?);
+    assert_eq!(handle.read_logs()?.len(), WORKERS * EVENTS);
+    for worker in 0..WORKERS {
+        let query = LogQuery::new().field("worker", worker.to_string().as_str());
+        assert_eq!(handle.query(&query)?.len(), EVENTS);
+    }
+    println!("{} entries from {WORKERS} processes", WORKERS * EVENTS);
+    Ok(())
+}
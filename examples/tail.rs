@@ -0,0 +1,44 @@
+//! Following new entries while another thread writes them, like `tail -f`.
+//!
+//! cargo run --example tail
+
+use std::{thread, time::Duration};
+
+use tracing_subscriber_sqlite::{LogHandle, LogQuery, SubscriberBuilder, TailOptions};
+
+fn main() -> rusqlite::Result<()> {
+    let handle = LogHandle::shared_memory("tail-example")?;
+    let subscriber = SubscriberBuilder::new().build(handle.clone());
+
+    // only warnings and errors, delivered at least every 50ms
+    let mut tail = handle.tail(
+        LogQuery::new().max_level(tracing::Level::WARN),
+        TailOptions {
+            max_batch: 10,
+            max_latency: Duration::from_millis(50),
+        },
+    )?;
+
+    let writer = thread::spawn(move || {
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..30 {
+                match i % 3 {
+                    0 => tracing::warn!(i, "disk almost full"),
+                    _ => tracing::info!(i, "request served"),
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+        })
+    });
+
+    let mut seen = 0;
+    while seen < 10 {
+        for entry in tail.next_batch()? {
+            println!("{} {} {}", entry.time, entry.level, entry.message);
+            assert_eq!(entry.level, tracing::Level::WARN);
+            seen += 1;
+        }
+    }
+    writer.join().unwrap();
+    Ok(())
+}
@@ -1,9 +1,20 @@
 CREATE TABLE IF NOT EXISTS logs_v0 (
-    time TEXT NOT NULL,
+    time NOT NULL,
     level TEXT NOT NULL,
     module TEXT,
     file TEXT,
     line INTEGER,
     message TEXT NOT NULL,
-    structured TEXT NOT NULL
+    structured TEXT NOT NULL,
+    category TEXT,
+    utc_offset INTEGER,
+    repeat_count INTEGER NOT NULL DEFAULT 1,
+    origin TEXT,
+    expires_at TEXT,
+    module_id INTEGER,
+    file_id INTEGER,
+    elapsed_us INTEGER,
+    event_id TEXT,
+    trace_id TEXT,
+    span_id TEXT
 );
\ No newline at end of file
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use time::OffsetDateTime;
+use tracing::Level;
+
+use crate::{timestamp::TIME_TEXT, LogHandle, LogQuery};
+
+/// Number of events per level, see `LogHandle::count_by_level`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LevelCounts {
+    pub error: u64,
+    pub warn: u64,
+    pub info: u64,
+    pub debug: u64,
+    pub trace: u64,
+}
+
+impl LevelCounts {
+    pub fn get(&self, level: Level) -> u64 {
+        match level {
+            Level::ERROR => self.error,
+            Level::WARN => self.warn,
+            Level::INFO => self.info,
+            Level::DEBUG => self.debug,
+            Level::TRACE => self.trace,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.error + self.warn + self.info + self.debug + self.trace
+    }
+
+    fn add(&mut self, level: &str, count: u64) {
+        let counter = match level.parse() {
+            Ok(Level::ERROR) => &mut self.error,
+            Ok(Level::WARN) => &mut self.warn,
+            Ok(Level::INFO) => &mut self.info,
+            Ok(Level::DEBUG) => &mut self.debug,
+            Ok(Level::TRACE) => &mut self.trace,
+            Err(_) => return,
+        };
+        *counter += count;
+    }
+}
+
+/// The events of one time bucket, see `LogHandle::histogram`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistogramBucket {
+    /// Start of the bucket, in UTC.
+    pub start: OffsetDateTime,
+    pub counts: LevelCounts,
+}
+
+impl LogHandle {
+    /// Number of events matching `query`, identical events coalesced into one row count with
+    /// their repeats. `select`, `limit` and `offset` are ignored.
+    pub fn count(&self, query: &LogQuery) -> rusqlite::Result<u64> {
+        Ok(self.count_by_level(query)?.total())
+    }
+
+    /// Number of events matching `query` per level, counted like `count`.
+    pub fn count_by_level(&self, query: &LogQuery) -> rusqlite::Result<LevelCounts> {
+        let query = self.scoped(query);
+        let (where_clause, params) = query.where_clause();
+        let sql = format!(
+            "SELECT level, sum(repeat_count) FROM {}{where_clause} GROUP BY level",
+            query.table_name()
+        );
+        self.with_reader(|conn| {
+            let mut counts = LevelCounts::default();
+            let mut stmt = conn.prepare(&sql)?;
+            let mut rows = stmt.query(rusqlite::params_from_iter(params))?;
+            while let Some(row) = rows.next()? {
+                counts.add(&row.get::<_, String>(0)?, row.get(1)?);
+            }
+            Ok(counts)
+        })
+    }
+
+    /// Number of events matching `query` per level in buckets of `bucket` (whole seconds,
+    /// at least one) since the Unix epoch, counted like `count`. Buckets without events are
+    /// left out, oldest first.
+    pub fn histogram(
+        &self,
+        query: &LogQuery,
+        bucket: Duration,
+    ) -> rusqlite::Result<Vec<HistogramBucket>> {
+        let seconds = bucket.as_secs().max(1) as i64;
+        let query = self.scoped(query);
+        let (where_clause, params) = query.where_clause();
+        let sql = format!(
+            "SELECT CAST(strftime('%s', {TIME_TEXT}) AS INTEGER) / {seconds} * {seconds} AS start,
+                    level, sum(repeat_count)
+             FROM {}{where_clause} GROUP BY start, level ORDER BY start",
+            query.table_name()
+        );
+        self.with_reader(|conn| {
+            let mut buckets: Vec<HistogramBucket> = Vec::new();
+            let mut stmt = conn.prepare(&sql)?;
+            let mut rows = stmt.query(rusqlite::params_from_iter(params))?;
+            while let Some(row) = rows.next()? {
+                let start = OffsetDateTime::from_unix_timestamp(row.get(0)?).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        0,
+                        rusqlite::types::Type::Integer,
+                        Box::new(e),
+                    )
+                })?;
+                match buckets.last_mut() {
+                    Some(last) if last.start == start => {}
+                    _ => buckets.push(HistogramBucket {
+                        start,
+                        counts: LevelCounts::default(),
+                    }),
+                }
+                let last = buckets.last_mut().unwrap();
+                last.counts.add(&row.get::<_, String>(1)?, row.get(2)?);
+            }
+            Ok(buckets)
+        })
+    }
+}
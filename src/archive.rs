@@ -0,0 +1,115 @@
+use std::path::Path;
+
+use rusqlite::{types::Value, Connection};
+use time::OffsetDateTime;
+
+use crate::{
+    db::{interned, prepare_attached},
+    timestamp::{time_condition, time_params},
+    Column, LogHandle, Progress, LOGS_TABLE,
+};
+
+/// Rows `LogHandle::archive_to_with_progress` copies between two reports.
+const ARCHIVE_CHUNK: u64 = 10_000;
+
+impl LogHandle {
+    /// Moves the `logs_v0` rows older than `before` into the log database at `path`, e.g.
+    /// `archive.sqlite` next to this one, keeping this database small while preserving history.
+    /// Returns the number of rows moved.
+    ///
+    /// The archive is created (or migrated) if needed and can be read with its own
+    /// `LogHandle`. Rows are copied and deleted in one transaction on the connection the handle
+    /// writes through. The newest row always stays, so rowids are never reused.
+    pub fn archive_to(
+        &self,
+        path: impl AsRef<Path>,
+        before: OffsetDateTime,
+    ) -> rusqlite::Result<u64> {
+        self.archive_to_with_progress(path, before, |_| {})
+    }
+
+    /// Same as `archive_to`, reporting the copied rows to `progress`. Rows are copied in chunks
+    /// of 10 000 within the one transaction.
+    pub fn archive_to_with_progress(
+        &self,
+        path: impl AsRef<Path>,
+        before: OffsetDateTime,
+        progress: impl Fn(Progress),
+    ) -> rusqlite::Result<u64> {
+        let path = path.as_ref();
+        let path = path
+            .to_str()
+            .ok_or_else(|| rusqlite::Error::InvalidPath(path.to_owned()))?;
+
+        let conn = self.writer();
+        conn.execute("ATTACH DATABASE ?1 AS archive", [path])?;
+        let result = archive_attached(&conn, before, progress);
+
+        let detached = conn.execute_batch("DETACH DATABASE archive");
+        let moved = result?;
+        detached?;
+        Ok(moved)
+    }
+}
+
+/// Moves the rows older than `before` to the database attached as `archive`.
+fn archive_attached(
+    conn: &Connection,
+    before: OffsetDateTime,
+    progress: impl Fn(Progress),
+) -> rusqlite::Result<u64> {
+    prepare_attached(conn, "archive")?;
+
+    let columns = Column::ALL.map(|c| c.name()).join(", ");
+    // the archive gets the text of interned values, it has strings of its own
+    let values = Column::ALL
+        .map(|c| match c {
+            Column::Module | Column::File => interned(c.name(), "main"),
+            _ => c.name().to_owned(),
+        })
+        .join(", ");
+    let filter = format!(
+        "WHERE {} AND rowid < (SELECT max(rowid) FROM main.{LOGS_TABLE})",
+        time_condition("<")
+    );
+    let before = time_params(before);
+
+    let tx = conn.unchecked_transaction()?;
+    let total: u64 = tx.query_row(
+        &format!("SELECT count(*) FROM main.{LOGS_TABLE} {filter}"),
+        rusqlite::params_from_iter(&before),
+        |row| row.get(0),
+    )?;
+    progress(Progress::new(0, Some(total)));
+
+    let mut moved = 0;
+    let mut last = i64::MIN;
+    loop {
+        let params = || before.iter().cloned().chain([Value::Integer(last)]);
+        let end: Option<i64> = tx.query_row(
+            &format!(
+                "SELECT max(rowid) FROM (SELECT rowid FROM main.{LOGS_TABLE} {filter}
+                 AND rowid > ? ORDER BY rowid LIMIT {ARCHIVE_CHUNK})"
+            ),
+            rusqlite::params_from_iter(params()),
+            |row| row.get(0),
+        )?;
+        let Some(end) = end else { break };
+        moved += tx.execute(
+            &format!(
+                "INSERT INTO archive.{LOGS_TABLE} ({columns})
+                 SELECT {values} FROM main.{LOGS_TABLE} {filter} AND rowid > ? AND rowid <= ?
+                 ORDER BY rowid"
+            ),
+            rusqlite::params_from_iter(params().chain([Value::Integer(end)])),
+        )? as u64;
+        last = end;
+        progress(Progress::new(moved, Some(total)));
+    }
+    tx.execute(
+        &format!("DELETE FROM main.{LOGS_TABLE} {filter}"),
+        rusqlite::params_from_iter(&before),
+    )?;
+    tx.commit()?;
+    Ok(moved)
+}
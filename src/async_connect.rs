@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use rusqlite::Connection;
+
+use crate::{
+    db::insert_entry,
+    prepare_database,
+    queue::{Queue, Receiver},
+    Connect, LogEntry, LOGS_TABLE,
+};
+
+/// A `Connect` handing entries to a task that writes them on tokio's blocking pool, so logging
+/// from async code never blocks a runtime worker on SQLite. Enabled by the `tokio` feature.
+///
+/// Entries that queue up while the task writes are committed together in one transaction.
+/// Write errors of the task are returned by the next `log` call and reach the layer's
+/// error handler from there. `flush` doesn't wait for the task, use [`TokioConnect::flushed`]
+/// before the runtime shuts down, or `flush_and_wait` from synchronous code.
+///
+/// The task is an async one that moves each batch to the blocking pool, so it doesn't keep
+/// the runtime from shutting down while a global subscriber still holds the sink. Entries
+/// still queued then are lost. At most 10 000 entries wait for the task, further ones are
+/// dropped and counted in `Stats::dropped`, see [`TokioConnect::with_capacity`].
+#[derive(Debug, Clone)]
+pub struct TokioConnect {
+    queue: Queue,
+}
+
+impl TokioConnect {
+    /// Starts the writer task on the current runtime, panics outside of one.
+    /// The database must be prepared already.
+    pub fn new(conn: Connection) -> Self {
+        let (queue, receiver) = Queue::new();
+        tokio::spawn(run(conn, receiver));
+        Self { queue }
+    }
+
+    /// Let up to `entries` wait for the writer task before new ones are dropped.
+    pub fn with_capacity(self, entries: usize) -> Self {
+        self.queue.set_capacity(entries);
+        self
+    }
+
+    /// Completes once every entry logged before the call has been written.
+    pub async fn flushed(&self) {
+        self.queue.flushed().await
+    }
+}
+
+impl Connect for TokioConnect {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self.queue.log(entry)
+    }
+
+    /// Blocks the calling thread, async code should await `flushed` instead.
+    fn flush_and_wait(&self, timeout: Duration) -> rusqlite::Result<()> {
+        self.queue.wait_flushed(timeout)
+    }
+
+    /// Queues `entry` like `log`; the writer task prepares the database before each batch
+    /// until one is written.
+    fn log_or_prepare(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self.queue.log_or_prepare(entry)
+    }
+
+    fn take_dropped(&self) -> u64 {
+        self.queue.take_dropped()
+    }
+}
+
+async fn run(mut conn: Connection, mut receiver: Receiver) {
+    while let Some(batch) = receiver.next().await {
+        let written = tokio::task::spawn_blocking(move || {
+            let result = if batch.prepare {
+                prepare_database(&conn)
+            } else {
+                Ok(())
+            };
+            let result = result.and_then(|()| write(&mut conn, &batch.entries));
+            (conn, batch, result)
+        })
+        .await;
+        // the runtime is shutting down
+        let Ok((returned, batch, result)) = written else {
+            return;
+        };
+        conn = returned;
+        receiver.done(batch, result);
+    }
+}
+
+fn write(conn: &mut Connection, entries: &[LogEntry]) -> rusqlite::Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let tx = conn.transaction()?;
+    for entry in entries {
+        insert_entry(&tx, LOGS_TABLE, &entry.to_borrowed())?;
+    }
+    tx.commit()
+}
@@ -0,0 +1,48 @@
+use std::{path::Path, thread, time::Duration};
+
+use rusqlite::{
+    backup::{Backup, StepResult},
+    Connection,
+};
+
+use crate::{LogHandle, Progress};
+
+/// Pages copied per step of `LogHandle::backup_to`.
+const PAGES_PER_STEP: i32 = 256;
+
+impl LogHandle {
+    /// Copies the whole database to a new database at `path` (replacing it), while the
+    /// application keeps logging.
+    ///
+    /// The copy is a consistent snapshot: SQLite restarts it if another connection writes in
+    /// between two steps. It reads through the handle's read connection, so writers only wait
+    /// for it on databases that can't be opened twice. The copy uses the handle's encryption key.
+    pub fn backup_to(&self, path: impl AsRef<Path>) -> rusqlite::Result<()> {
+        self.backup_to_with_progress(path, |_| {})
+    }
+
+    /// Same as `backup_to`, reporting the copied pages to `progress`.
+    pub fn backup_to_with_progress(
+        &self,
+        path: impl AsRef<Path>,
+        progress: impl Fn(Progress),
+    ) -> rusqlite::Result<()> {
+        let mut target = Connection::open(path)?;
+        self.apply_encryption_key(&target)?;
+
+        self.with_reader(|conn| {
+            let backup = Backup::new(conn, &mut target)?;
+            loop {
+                let result = backup.step(PAGES_PER_STEP)?;
+                let step = backup.progress();
+                let total = step.pagecount as u64;
+                progress(Progress::new(total - step.remaining as u64, Some(total)));
+                match result {
+                    StepResult::Done => return Ok(()),
+                    StepResult::More => {}
+                    _ => thread::sleep(Duration::from_millis(10)),
+                }
+            }
+        })
+    }
+}
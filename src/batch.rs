@@ -0,0 +1,217 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use rusqlite::Connection;
+
+use crate::{
+    db::{insert_entry, is_missing_table, warn_missing_table},
+    prepare_database, Connect, Fallback, LogEntry, LOGS_TABLE,
+};
+
+/// How many batches of entries a failing database keeps pending before the oldest are dropped.
+const RETAINED_BATCHES: usize = 16;
+
+/// A `Connect` that collects entries and writes them in one transaction, which is much faster
+/// than committing every entry on its own.
+///
+/// A batch is written when it reaches `batch_size` entries, when an entry arrives and the
+/// oldest pending one is older than `max_delay`, on `flush` and when the last clone is dropped.
+/// The layer flushes after every entry at its flush level (`ERROR` by default), so a crash
+/// right after logging an error doesn't lose it.
+///
+/// If a batch can't be written its entries go to the sink of [`BatchConnect::with_fallback`].
+/// Without one they stay pending and are written with the next batch, once `max_delay` has
+/// passed or on `flush`, and the layer's fallback doesn't get them; beyond 16 batches the
+/// oldest are dropped and counted in the layer's `Stats::dropped`.
+#[derive(Debug, Clone)]
+pub struct BatchConnect {
+    inner: Arc<BatchInner>,
+}
+
+#[derive(Debug)]
+struct BatchInner {
+    conn: Arc<Mutex<Connection>>,
+    batch_size: usize,
+    max_delay: Duration,
+    fallback: Option<Fallback>,
+    pending: Mutex<Pending>,
+    /// Entries dropped beyond `RETAINED_BATCHES`, see `Connect::take_dropped`.
+    dropped: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Pending {
+    entries: VecDeque<LogEntry>,
+    oldest: Option<Instant>,
+    /// Whether the last write failed, so a full batch doesn't retry before `max_delay`.
+    failed: bool,
+}
+
+impl BatchConnect {
+    pub fn new(conn: Arc<Mutex<Connection>>, batch_size: usize, max_delay: Duration) -> Self {
+        Self {
+            inner: Arc::new(BatchInner {
+                conn,
+                batch_size: batch_size.max(1),
+                max_delay,
+                fallback: None,
+                pending: Mutex::default(),
+                dropped: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Write the entries of a batch that could not be written to `sink` instead, e.g. a
+    /// [`JsonlConnect`](crate::JsonlConnect). The write only fails if `sink` fails too.
+    ///
+    /// Panics if the `BatchConnect` was cloned already.
+    pub fn with_fallback(mut self, sink: impl Connect + Send + Sync + 'static) -> Self {
+        Arc::get_mut(&mut self.inner)
+            .expect("with_fallback called on a cloned BatchConnect")
+            .fallback = Some(Fallback(Box::new(sink)));
+        self
+    }
+
+    /// Same as `new`, preparing the database first.
+    pub fn prepared(
+        conn: Arc<Mutex<Connection>>,
+        batch_size: usize,
+        max_delay: Duration,
+    ) -> rusqlite::Result<Self> {
+        prepare_database(&conn.lock().unwrap())?;
+        Ok(Self::new(conn, batch_size, max_delay))
+    }
+
+    /// Number of entries waiting to be written.
+    pub fn pending(&self) -> usize {
+        self.inner.pending.lock().unwrap().entries.len()
+    }
+}
+
+impl Pending {
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.oldest = None;
+        self.failed = false;
+    }
+}
+
+impl BatchInner {
+    /// Writes all pending entries. If the transaction fails they go to the fallback, or stay
+    /// pending for the next write.
+    fn write(&self, pending: &mut Pending) -> rusqlite::Result<()> {
+        if pending.entries.is_empty() {
+            pending.oldest = None;
+            return Ok(());
+        }
+
+        let result = self.commit(&pending.entries);
+        let result = match (result, &self.fallback) {
+            (Ok(()), _) => Ok(()),
+            (Err(e), Some(fallback)) => {
+                // every entry gets its chance, even after one failed
+                let mut rescued = true;
+                for entry in &pending.entries {
+                    rescued &= fallback.0.log(entry.to_borrowed()).is_ok();
+                }
+                if rescued {
+                    fallback.0.flush()
+                } else {
+                    Err(e)
+                }
+            }
+            (Err(e), None) => {
+                let retained = self.batch_size.saturating_mul(RETAINED_BATCHES);
+                let dropped = pending.entries.len().saturating_sub(retained);
+                pending.entries.drain(..dropped);
+                self.dropped.fetch_add(dropped as u64, Ordering::Relaxed);
+                pending.oldest = Some(Instant::now());
+                pending.failed = true;
+                return Err(e);
+            }
+        };
+        pending.clear();
+        result
+    }
+
+    fn commit(&self, entries: &VecDeque<LogEntry>) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for entry in entries {
+            insert_entry(&tx, LOGS_TABLE, &entry.to_borrowed())?;
+        }
+        tx.commit()
+    }
+}
+
+impl Connect for BatchConnect {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        let mut pending = self.inner.pending.lock().unwrap();
+        pending.entries.push_back(entry.into_owned());
+        if pending.entries.len() > self.inner.batch_size.saturating_mul(RETAINED_BATCHES) {
+            pending.entries.pop_front();
+            self.inner.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        let oldest = *pending.oldest.get_or_insert_with(Instant::now);
+
+        if (!pending.failed && pending.entries.len() >= self.inner.batch_size)
+            || oldest.elapsed() >= self.inner.max_delay
+        {
+            self.inner.write(&mut pending)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn flush(&self) -> rusqlite::Result<()> {
+        let mut pending = self.inner.pending.lock().unwrap();
+        self.inner.write(&mut pending)
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        prepare_database(&self.inner.conn.lock().unwrap())
+    }
+
+    /// Writes the pending entries with `entry` right away, so a missing table shows up now
+    /// rather than with a later batch.
+    fn log_or_prepare(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        let mut pending = self.inner.pending.lock().unwrap();
+        pending.entries.push_back(entry.into_owned());
+        pending.oldest.get_or_insert_with(Instant::now);
+        match self.inner.commit(&pending.entries) {
+            Ok(()) => {
+                pending.clear();
+                Ok(())
+            }
+            Err(e) if is_missing_table(&e) => {
+                warn_missing_table();
+                self.create_tables()?;
+                self.inner.write(&mut pending)
+            }
+            // the usual handling of failed batches
+            Err(_) => self.inner.write(&mut pending),
+        }
+    }
+
+    fn take_dropped(&self) -> u64 {
+        self.inner.dropped.swap(0, Ordering::Relaxed)
+    }
+
+    fn keeps_failed_entries(&self) -> bool {
+        self.inner.fallback.is_none()
+    }
+}
+
+impl Drop for BatchInner {
+    fn drop(&mut self) {
+        let mut pending = std::mem::take(self.pending.get_mut().unwrap());
+        // there is nobody left to report the error to
+        let _ = self.write(&mut pending);
+    }
+}
@@ -0,0 +1,284 @@
+//! Reading and maintaining a log database from the command line, see `tracing-sqlite --help`.
+//!
+//! cargo install tracing-subscriber-sqlite --features cli
+
+#[cfg(all(feature = "tui", unix))]
+mod view;
+
+use std::{
+    io::{self, Write},
+    process::ExitCode,
+};
+
+use rusqlite::{Connection, OpenFlags};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing_subscriber_sqlite::{
+    parse_time, ExportProfile, FieldMatch, LogEntry, LogHandle, LogQuery, Order, TailOptions,
+    Timezone,
+};
+
+const USAGE: &str = "\
+Usage: tracing-sqlite <DATABASE> <COMMAND> [OPTIONS]
+
+Commands:
+  tail     Print the newest entries, then the new ones as they are written
+  search   Print the entries matching the filters
+  export   Write the entries matching the filters to stdout
+  prune    Delete old or expired entries
+  stats    Show the size and contents of the database
+  view     Browse and follow the entries interactively (tui feature, Unix only)
+
+Filters (tail, search, export, prune, view):
+  --level <LEVEL>       Entries at LEVEL or more severe, e.g. warn
+  --module <PREFIX>     Entries whose module path starts with PREFIX
+  --since <TIME>        Entries at or after TIME, RFC 3339 or an age like 30m, 12h or 7d
+  --until <TIME>        Entries before TIME
+  --contains <TEXT>     Entries whose message contains TEXT
+  --field <NAME=VALUE>  Entries with this structured field, can be repeated
+  --table <TABLE>       Read another table with the logs_v0 schema
+
+Options:
+  -n <COUNT>            tail: entries printed before following [default: 10]
+  --limit <COUNT>       search: most entries printed [default: 100]
+  --newest              search: newest entries first
+  --local               tail, search, view: times in the local timezone instead of UTC
+  --format <FORMAT>     export: jsonl, csv, ecs or otel [default: jsonl]
+  --before <TIME>       prune: delete the entries before TIME
+  --expired             prune: delete the entries whose log_ttl_secs passed, without filters
+";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "-h" || a == "--help") {
+        print!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("tracing-sqlite: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<(), String> {
+    let [database, command, rest @ ..] = args.as_slice() else {
+        return Err("expected a database and a command, see --help".to_owned());
+    };
+    let options = Options::parse(rest)?;
+
+    // without `SQLITE_OPEN_CREATE`, a mistyped path fails instead of creating a database
+    let conn = Connection::open_with_flags(
+        database,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_URI,
+    )
+    .map_err(|e| format!("{database}: {e}"))?;
+    let handle = LogHandle::new(conn);
+
+    match command.as_str() {
+        "tail" => tail(&handle, &options),
+        "search" => search(&handle, &options),
+        "export" => export(&handle, &options),
+        "prune" => prune(&handle, &options),
+        "stats" => stats(&handle),
+        #[cfg(all(feature = "tui", unix))]
+        "view" => view::view(&handle, &options.query),
+        #[cfg(not(all(feature = "tui", unix)))]
+        "view" => Err("view needs the tui feature, on Unix for now".to_owned()),
+        other => Err(format!("unknown command {other:?}, see --help")),
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    query: LogQuery,
+    count: Option<u64>,
+    limit: Option<u64>,
+    newest: bool,
+    local: bool,
+    format: Option<String>,
+    before: Option<OffsetDateTime>,
+    expired: bool,
+    /// Whether a filter was given.
+    filtered: bool,
+}
+
+/// The options that narrow `Options::query`.
+const FILTERS: &[&str] = &[
+    "--level",
+    "--module",
+    "--since",
+    "--until",
+    "--contains",
+    "--field",
+    "--table",
+];
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .map(String::as_str)
+                    .ok_or_else(|| format!("{arg} needs a value"))
+            };
+            let query = std::mem::take(&mut options.query);
+            options.filtered |= FILTERS.contains(&arg.as_str());
+            options.query = match arg.as_str() {
+                "--level" => {
+                    let level = value()?;
+                    query.max_level(
+                        level
+                            .parse()
+                            .map_err(|_| format!("unknown level {level:?}"))?,
+                    )
+                }
+                "--module" => query.module(value()?),
+                "--since" => query.since(parse_time(value()?)?),
+                "--until" => query.until(parse_time(value()?)?),
+                "--contains" => query.message_contains(value()?),
+                "--field" => query.field_match(value()?.parse::<FieldMatch>()?),
+                // the query keeps the name for as long as the process runs anyway
+                "--table" => query.table(Box::leak(value()?.into())),
+                _ => {
+                    match arg.as_str() {
+                        "-n" => options.count = Some(parse_count(value()?)?),
+                        "--limit" => options.limit = Some(parse_count(value()?)?),
+                        "--newest" => options.newest = true,
+                        "--local" => options.local = true,
+                        "--format" => options.format = Some(value()?.to_owned()),
+                        "--before" => options.before = Some(parse_time(value()?)?),
+                        "--expired" => options.expired = true,
+                        _ => return Err(format!("unknown option {arg:?}, see --help")),
+                    }
+                    query
+                }
+            };
+        }
+        if options.local {
+            options.query = options.query.timezone(Timezone::local());
+        }
+        Ok(options)
+    }
+}
+
+fn parse_count(text: &str) -> Result<u64, String> {
+    text.parse().map_err(|_| format!("invalid count {text:?}"))
+}
+
+fn tail(handle: &LogHandle, options: &Options) -> Result<(), String> {
+    let count = options.count.unwrap_or(10);
+    // started first, so rows written while reading the newest ones are followed
+    let mut tail = handle
+        .tail(options.query.clone(), TailOptions::default())
+        .map_err(|e| e.to_string())?;
+    let backlog = options
+        .query
+        .clone()
+        .up_to(tail.position())
+        .order(Order::NewestFirst)
+        .limit(count);
+    let mut newest = handle.query(&backlog).map_err(|e| e.to_string())?;
+    newest.reverse();
+
+    print_entries(&newest)?;
+    loop {
+        print_entries(&tail.next_batch().map_err(|e| e.to_string())?)?;
+    }
+}
+
+fn search(handle: &LogHandle, options: &Options) -> Result<(), String> {
+    let order = match options.newest {
+        true => Order::NewestFirst,
+        false => Order::OldestFirst,
+    };
+    let query = options
+        .query
+        .clone()
+        .order(order)
+        .limit(options.limit.unwrap_or(100));
+    print_entries(&handle.query(&query).map_err(|e| e.to_string())?)
+}
+
+fn export(handle: &LogHandle, options: &Options) -> Result<(), String> {
+    let stdout = io::stdout().lock();
+    let profile = match options.format.as_deref().unwrap_or("jsonl") {
+        "csv" => {
+            return handle
+                .export_csv(stdout, &options.query)
+                .map(drop)
+                .map_err(|e| e.to_string())
+        }
+        "jsonl" => ExportProfile::Native,
+        "ecs" => ExportProfile::Ecs,
+        "otel" => ExportProfile::OpenTelemetry,
+        other => return Err(format!("unknown format {other:?}, see --help")),
+    };
+    handle
+        .export_jsonl_as(stdout, &options.query, profile)
+        .map(drop)
+        .map_err(|e| e.to_string())
+}
+
+fn prune(handle: &LogHandle, options: &Options) -> Result<(), String> {
+    if !options.expired && options.before.is_none() {
+        return Err("prune needs --before or --expired".to_owned());
+    }
+    if options.expired && options.filtered {
+        return Err("prune --expired deletes every expired entry, it takes no filters".to_owned());
+    }
+    let mut deleted = 0;
+    if options.expired {
+        deleted += handle.delete_expired().map_err(|e| e.to_string())?;
+    }
+    if let Some(before) = options.before {
+        let query = options.query.clone().until(before);
+        deleted += handle.delete_matching(&query).map_err(|e| e.to_string())?;
+    }
+    println!("deleted {deleted} entries");
+    Ok(())
+}
+
+fn stats(handle: &LogHandle) -> Result<(), String> {
+    let stats = handle.database_stats().map_err(|e| e.to_string())?;
+    let time = |time: Option<OffsetDateTime>| {
+        time.and_then(|t| t.format(&Rfc3339).ok())
+            .unwrap_or_else(|| "-".to_owned())
+    };
+    println!("rows      {}", stats.rows);
+    println!("earliest  {}", time(stats.earliest));
+    println!("latest    {}", time(stats.latest));
+    println!("file size {} bytes", stats.file_size);
+    if let Some(wal_size) = stats.wal_size {
+        println!("wal size  {wal_size} bytes");
+    }
+    println!("modules");
+    for (module, rows) in &stats.modules {
+        println!("  {rows:>10}  {}", module.as_deref().unwrap_or("-"));
+    }
+    Ok(())
+}
+
+/// One line per entry, `time level module: message name=value ...`.
+fn print_entries(entries: &[LogEntry]) -> Result<(), String> {
+    let mut stdout = io::stdout().lock();
+    for entry in entries {
+        let mut fields: Vec<_> = entry.structured.fields().iter().collect();
+        fields.sort();
+        let mut line = format!(
+            "{} {:5} {}: {}",
+            entry.time.format(&Rfc3339).unwrap_or_default(),
+            entry.level,
+            entry.module.as_deref().unwrap_or("-"),
+            entry.message,
+        );
+        for (name, value) in fields {
+            line += &format!(" {name}={value}");
+        }
+        writeln!(stdout, "{line}").map_err(|e| e.to_string())?;
+    }
+    stdout.flush().map_err(|e| e.to_string())
+}
@@ -0,0 +1,333 @@
+//! The `view` command: the entries in a list that follows new ones, with filters and the
+//! fields of the selected entry, drawn with ANSI escapes. The terminal itself is set up and
+//! read in [`terminal`].
+
+mod terminal;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+};
+
+use time::format_description::well_known::Rfc3339;
+use tracing::Level;
+use tracing_subscriber_sqlite::{LogEntry, LogHandle, LogQuery, Order, Tail, TailOptions};
+
+use terminal::{read_keys, terminal_size, Key, RawTerminal};
+
+/// Entries kept in the list, the oldest are dropped once there are more.
+const MAX_ENTRIES: usize = 10_000;
+
+const HELP: &str =
+    "q quit  ↑↓ PgUp PgDn select  End follow  1-5 level  0 all levels  t target  / search  c clear";
+
+pub(crate) fn view(handle: &LogHandle, base: &LogQuery) -> Result<(), String> {
+    let mut viewer = Viewer::new(handle, base.clone())?;
+    let _terminal = RawTerminal::enter().map_err(|e| format!("can't set up the terminal: {e}"))?;
+    viewer.run()
+}
+
+#[derive(Clone, Copy)]
+enum PromptKind {
+    Target,
+    Search,
+}
+
+struct Viewer<'a> {
+    handle: &'a LogHandle,
+    /// The filters from the command line, the viewer's own apply on top.
+    base: LogQuery,
+    level: Option<Level>,
+    target: String,
+    search: String,
+    entries: VecDeque<LogEntry>,
+    selected: usize,
+    top: usize,
+    /// Whether the newest entry stays selected as new ones arrive.
+    follow: bool,
+    tail: Tail,
+    prompt: Option<(PromptKind, String)>,
+    /// Rows of the list when it was drawn last, for paging.
+    list_height: usize,
+}
+
+impl<'a> Viewer<'a> {
+    fn new(handle: &'a LogHandle, base: LogQuery) -> Result<Self, String> {
+        let tail = handle
+            .tail(base.clone(), TailOptions::default())
+            .map_err(|e| e.to_string())?;
+        let mut viewer = Self {
+            handle,
+            base,
+            level: None,
+            target: String::new(),
+            search: String::new(),
+            entries: VecDeque::new(),
+            selected: 0,
+            top: 0,
+            follow: true,
+            tail,
+            prompt: None,
+            list_height: 10,
+        };
+        viewer.reload()?;
+        Ok(viewer)
+    }
+
+    fn query(&self) -> LogQuery {
+        let mut query = self.base.clone();
+        if let Some(level) = self.level {
+            query = query.max_level(level);
+        }
+        if !self.target.is_empty() {
+            query = query.module(self.target.clone());
+        }
+        if !self.search.is_empty() {
+            query = query.message_contains(self.search.clone());
+        }
+        query
+    }
+
+    /// Reads the newest matching entries again, after a filter changed.
+    fn reload(&mut self) -> Result<(), String> {
+        let query = self.query();
+        let options = TailOptions {
+            max_batch: 1000,
+            ..TailOptions::default()
+        };
+        self.tail = self
+            .handle
+            .tail(query.clone(), options)
+            .map_err(|e| e.to_string())?;
+        let newest = self
+            .handle
+            .query(&query.order(Order::NewestFirst).limit(MAX_ENTRIES as u64))
+            .map_err(|e| e.to_string())?;
+        self.entries = newest.into_iter().rev().collect();
+        self.selected = match self.follow {
+            true => self.entries.len().saturating_sub(1),
+            false => self.selected.min(self.entries.len().saturating_sub(1)),
+        };
+        Ok(())
+    }
+
+    fn run(&mut self) -> Result<(), String> {
+        loop {
+            let new = self.tail.try_next_batch().map_err(|e| e.to_string())?;
+            self.entries.extend(new);
+            let dropped = self.entries.len().saturating_sub(MAX_ENTRIES);
+            self.entries.drain(..dropped);
+            self.selected = self.selected.saturating_sub(dropped);
+            if self.follow {
+                self.selected = self.entries.len().saturating_sub(1);
+            }
+
+            self.draw().map_err(|e| e.to_string())?;
+            for key in read_keys().map_err(|e| e.to_string())? {
+                if !self.handle_key(key)? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Returns whether to go on.
+    fn handle_key(&mut self, key: Key) -> Result<bool, String> {
+        if let Some((kind, mut text)) = self.prompt.take() {
+            match key {
+                Key::Interrupt => return Ok(false),
+                Key::Escape => {}
+                Key::Enter => {
+                    match kind {
+                        PromptKind::Target => self.target = text,
+                        PromptKind::Search => self.search = text,
+                    }
+                    self.reload()?;
+                }
+                Key::Backspace => {
+                    text.pop();
+                    self.prompt = Some((kind, text));
+                }
+                Key::Char(c) => {
+                    text.push(c);
+                    self.prompt = Some((kind, text));
+                }
+                _ => self.prompt = Some((kind, text)),
+            }
+            return Ok(true);
+        }
+
+        let last = self.entries.len().saturating_sub(1);
+        let page = self.list_height.max(1);
+        match key {
+            Key::Char('q') | Key::Interrupt => return Ok(false),
+            Key::Up | Key::Char('k') => self.select(self.selected.saturating_sub(1)),
+            Key::Down | Key::Char('j') => self.select(self.selected + 1),
+            Key::PageUp => self.select(self.selected.saturating_sub(page)),
+            Key::PageDown => self.select(self.selected + page),
+            Key::Home | Key::Char('g') => self.select(0),
+            Key::End | Key::Char('G') => self.select(last),
+            Key::Char('f') => self.follow = !self.follow,
+            Key::Char(c @ '0'..='5') => {
+                self.level = match c {
+                    '1' => Some(Level::ERROR),
+                    '2' => Some(Level::WARN),
+                    '3' => Some(Level::INFO),
+                    '4' => Some(Level::DEBUG),
+                    '5' => Some(Level::TRACE),
+                    _ => None,
+                };
+                self.reload()?;
+            }
+            Key::Char('t') => self.prompt = Some((PromptKind::Target, self.target.clone())),
+            Key::Char('/') => self.prompt = Some((PromptKind::Search, self.search.clone())),
+            Key::Char('c') => {
+                self.level = None;
+                self.target.clear();
+                self.search.clear();
+                self.reload()?;
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn select(&mut self, index: usize) {
+        let last = self.entries.len().saturating_sub(1);
+        self.selected = index.min(last);
+        self.follow = self.selected == last;
+    }
+
+    fn draw(&mut self) -> io::Result<()> {
+        let (width, height) = terminal_size();
+        let mut frame = String::new();
+        for (row, line) in self.lines(width, height).iter().enumerate() {
+            // move to the row, clear it, draw it
+            frame += &format!("\x1b[{};1H\x1b[2K{line}", row + 1);
+        }
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(frame.as_bytes())?;
+        stdout.flush()
+    }
+
+    /// The lines of a screen of `width` by `height`, scrolling the list to the selected entry.
+    fn lines(&mut self, width: usize, height: usize) -> Vec<String> {
+        let details_height = (height / 3).clamp(2, 12);
+        // the title, separator and footer lines
+        self.list_height = height.saturating_sub(details_height + 3).max(1);
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + self.list_height {
+            self.top = self.selected + 1 - self.list_height;
+        }
+
+        let mut lines = vec![inverse(&fit(&self.title(), width), width)];
+        for row in 0..self.list_height {
+            let line = match self.entries.get(self.top + row) {
+                Some(entry) if self.top + row == self.selected => {
+                    inverse(&fit(&list_line(entry), width), width)
+                }
+                Some(entry) => colored(entry.level, &fit(&list_line(entry), width)),
+                None => String::new(),
+            };
+            lines.push(line);
+        }
+        lines.push(fit(&"─".repeat(width), width));
+        let details = self
+            .entries
+            .get(self.selected)
+            .map(details)
+            .unwrap_or_default();
+        for row in 0..details_height {
+            lines.push(details.get(row).map_or_else(String::new, |l| fit(l, width)));
+        }
+        lines.push(match &self.prompt {
+            Some((PromptKind::Target, text)) => fit(&format!("target: {text}▏"), width),
+            Some((PromptKind::Search, text)) => fit(&format!("search: {text}▏"), width),
+            None => fit(HELP, width),
+        });
+        lines
+    }
+
+    fn title(&self) -> String {
+        let mut title = format!(" tracing-sqlite  {} entries", self.entries.len());
+        if let Some(level) = self.level {
+            title += &format!("  level: {level} and above");
+        }
+        if !self.target.is_empty() {
+            title += &format!("  target: {}", self.target);
+        }
+        if !self.search.is_empty() {
+            title += &format!("  search: {}", self.search);
+        }
+        if self.follow {
+            title += "  following";
+        }
+        title
+    }
+}
+
+/// `time level module: message` of an entry in the list.
+fn list_line(entry: &LogEntry) -> String {
+    let time = entry.time;
+    format!(
+        "{:02}:{:02}:{:02}.{:03} {:5} {}: {}",
+        time.hour(),
+        time.minute(),
+        time.second(),
+        time.millisecond(),
+        entry.level,
+        entry.module.as_deref().unwrap_or("-"),
+        entry.message,
+    )
+}
+
+/// The lines showing all of an entry.
+fn details(entry: &LogEntry) -> Vec<String> {
+    let mut place = entry.file.clone().unwrap_or_default();
+    if let Some(line) = entry.line {
+        place += &format!(":{line}");
+    }
+    let mut lines = vec![format!(
+        "{}  {}  {}  {place}",
+        entry.time.format(&Rfc3339).unwrap_or_default(),
+        entry.level,
+        entry.module.as_deref().unwrap_or("-"),
+    )];
+    lines.extend(entry.message.lines().map(str::to_owned));
+    let mut fields: Vec<_> = entry.structured.fields().iter().collect();
+    fields.sort();
+    lines.extend(
+        fields
+            .into_iter()
+            .map(|(name, value)| format!("  {name} = {value}")),
+    );
+    lines
+}
+
+/// `text` on one line of `width` columns, control characters replaced by spaces.
+fn fit(text: &str, width: usize) -> String {
+    text.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .take(width)
+        .collect()
+}
+
+/// `text` in reverse video, padded to the whole line.
+fn inverse(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(text.chars().count());
+    format!("\x1b[7m{text}{}\x1b[0m", " ".repeat(padding))
+}
+
+fn colored(level: Level, text: &str) -> String {
+    let color = match level {
+        Level::ERROR => "31",
+        Level::WARN => "33",
+        Level::INFO => "32",
+        Level::DEBUG => "34",
+        Level::TRACE => "2",
+    };
+    format!("\x1b[{color}m{text}\x1b[0m")
+}
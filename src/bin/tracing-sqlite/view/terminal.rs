@@ -0,0 +1,116 @@
+//! Raw input, the window size and the keys pressed, on a Unix terminal. Everything the viewer
+//! needs from the platform is in here, a backend for other terminals only has to provide it.
+
+use std::io::{self, Write};
+
+use rustix::{
+    event::{poll, PollFd, PollFlags},
+    io::Errno,
+    termios::{self, OptionalActions, Termios},
+};
+
+/// How long to wait for a key before polling the database again, in milliseconds.
+const POLL_MS: i32 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Escape,
+    Backspace,
+    Interrupt,
+    Char(char),
+}
+
+/// Columns and rows, 80 by 24 if stdout is not a terminal.
+pub(super) fn terminal_size() -> (usize, usize) {
+    match termios::tcgetwinsize(io::stdout()) {
+        Ok(size) if size.ws_col > 0 && size.ws_row > 0 => (size.ws_col.into(), size.ws_row.into()),
+        _ => (80, 24),
+    }
+}
+
+/// The keys pressed, waiting up to `POLL_MS` for the first.
+pub(super) fn read_keys() -> io::Result<Vec<Key>> {
+    let stdin = io::stdin();
+    let mut fds = [PollFd::new(&stdin, PollFlags::IN)];
+    match poll(&mut fds, POLL_MS) {
+        Ok(0) | Err(Errno::INTR) => return Ok(Vec::new()),
+        Ok(_) => {}
+        Err(e) => return Err(e.into()),
+    }
+    let mut buf = [0; 256];
+    let read = rustix::io::read(&stdin, &mut buf)?;
+    Ok(parse_keys(&buf[..read]))
+}
+
+pub(super) fn parse_keys(mut bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    while !bytes.is_empty() {
+        let (key, len) = match bytes {
+            [0x1b, b'[', b'A', ..] => (Some(Key::Up), 3),
+            [0x1b, b'[', b'B', ..] => (Some(Key::Down), 3),
+            [0x1b, b'[', b'H', ..] | [0x1b, b'O', b'H', ..] => (Some(Key::Home), 3),
+            [0x1b, b'[', b'F', ..] | [0x1b, b'O', b'F', ..] => (Some(Key::End), 3),
+            [0x1b, b'[', b'1', b'~', ..] => (Some(Key::Home), 4),
+            [0x1b, b'[', b'4', b'~', ..] => (Some(Key::End), 4),
+            [0x1b, b'[', b'5', b'~', ..] => (Some(Key::PageUp), 4),
+            [0x1b, b'[', b'6', b'~', ..] => (Some(Key::PageDown), 4),
+            // other sequences, e.g. the arrows left and right
+            [0x1b, b'[', rest @ ..] => {
+                let end = rest.iter().position(|b| (0x40..=0x7e).contains(b));
+                (None, 2 + end.map_or(rest.len(), |end| end + 1))
+            }
+            [0x1b, ..] => (Some(Key::Escape), 1),
+            [b'\r' | b'\n', ..] => (Some(Key::Enter), 1),
+            [0x7f | 0x08, ..] => (Some(Key::Backspace), 1),
+            [0x03, ..] => (Some(Key::Interrupt), 1),
+            _ => {
+                let len = (1..=bytes.len().min(4))
+                    .find(|&len| std::str::from_utf8(&bytes[..len]).is_ok())
+                    .unwrap_or(1);
+                let c = std::str::from_utf8(&bytes[..len])
+                    .ok()
+                    .and_then(|s| s.chars().next())
+                    .filter(|c| !c.is_control());
+                (c.map(Key::Char), len)
+            }
+        };
+        keys.extend(key);
+        bytes = &bytes[len..];
+    }
+    keys
+}
+
+/// Raw input on the alternate screen until dropped, so the shell's screen comes back.
+pub(super) struct RawTerminal {
+    original: Termios,
+}
+
+impl RawTerminal {
+    pub(super) fn enter() -> io::Result<Self> {
+        let original = termios::tcgetattr(io::stdin())?;
+        let mut raw = original.clone();
+        raw.make_raw();
+        termios::tcsetattr(io::stdin(), OptionalActions::Now, &raw)?;
+        // the alternate screen, without a cursor
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(b"\x1b[?1049h\x1b[?25l")?;
+        stdout.flush()?;
+        Ok(Self { original })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout().lock();
+        let _ = stdout.write_all(b"\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+        let _ = termios::tcsetattr(io::stdin(), OptionalActions::Now, &self.original);
+    }
+}
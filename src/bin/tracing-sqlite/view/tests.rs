@@ -0,0 +1,125 @@
+//! Key handling and the lines drawn by the viewer, without a terminal.
+
+use tracing_subscriber_sqlite::{LogHandle, LogQuery, SubscriberBuilder};
+
+use super::{
+    fit, inverse,
+    terminal::{parse_keys, Key},
+    Viewer, HELP,
+};
+
+/// A database named `name` with an info, a warning and an error entry.
+fn handle(name: &str) -> LogHandle {
+    let handle = LogHandle::shared_memory(name).unwrap();
+    let subscriber = SubscriberBuilder::new().build(handle.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(user = "alice", "first");
+        tracing::warn!("second");
+        tracing::error!("third");
+    });
+    handle
+}
+
+fn messages(viewer: &Viewer<'_>) -> Vec<String> {
+    viewer
+        .entries
+        .iter()
+        .map(|entry| entry.message.clone())
+        .collect()
+}
+
+fn press(viewer: &mut Viewer<'_>, keys: &[Key]) -> bool {
+    keys.iter().all(|&key| viewer.handle_key(key).unwrap())
+}
+
+#[test]
+fn escape_sequences_and_characters_are_parsed() {
+    let keys = parse_keys(b"\x1b[A\x1b[6~q\x03\x1b[C\xc3\xa4\r\x7f\x1b");
+    assert_eq!(
+        keys,
+        [
+            Key::Up,
+            Key::PageDown,
+            Key::Char('q'),
+            Key::Interrupt,
+            Key::Char('ä'),
+            Key::Enter,
+            Key::Backspace,
+            Key::Escape,
+        ]
+    );
+}
+
+#[test]
+fn keys_move_the_selection_and_follow_the_newest() {
+    let handle = handle("keys_move_the_selection_and_follow_the_newest");
+    let mut viewer = Viewer::new(&handle, LogQuery::new()).unwrap();
+    assert_eq!((viewer.selected, viewer.follow), (2, true));
+
+    assert!(press(&mut viewer, &[Key::Up]));
+    assert_eq!((viewer.selected, viewer.follow), (1, false));
+    assert!(press(&mut viewer, &[Key::Home, Key::Up]));
+    assert_eq!(viewer.selected, 0);
+    assert!(press(&mut viewer, &[Key::End]));
+    assert_eq!((viewer.selected, viewer.follow), (2, true));
+
+    assert!(!press(&mut viewer, &[Key::Char('q')]));
+}
+
+#[test]
+fn filters_reload_the_entries() {
+    let handle = handle("filters_reload_the_entries");
+    let mut viewer = Viewer::new(&handle, LogQuery::new()).unwrap();
+
+    assert!(press(&mut viewer, &[Key::Char('2')]));
+    assert_eq!(messages(&viewer), ["second", "third"]);
+
+    // typing goes to the prompt, escape drops it
+    let search = [
+        Key::Char('/'),
+        Key::Char('t'),
+        Key::Char('h'),
+        Key::Char('x'),
+    ];
+    assert!(press(&mut viewer, &search));
+    assert!(press(&mut viewer, &[Key::Backspace, Key::Escape]));
+    assert_eq!(messages(&viewer), ["second", "third"]);
+    assert!(press(&mut viewer, &search[..3]));
+    assert!(press(&mut viewer, &[Key::Enter]));
+    assert_eq!(messages(&viewer), ["third"]);
+    assert!(viewer.title().contains("search: th"));
+
+    assert!(press(&mut viewer, &[Key::Char('c')]));
+    assert_eq!(messages(&viewer), ["first", "second", "third"]);
+}
+
+#[test]
+fn lines_show_the_list_and_the_selected_entry() {
+    let handle = handle("lines_show_the_list_and_the_selected_entry");
+    let mut viewer = Viewer::new(&handle, LogQuery::new()).unwrap();
+    assert!(press(&mut viewer, &[Key::Home]));
+
+    let lines = viewer.lines(60, 12);
+    assert_eq!(lines.len(), 12);
+    assert!(lines[0].starts_with("\x1b[7m tracing-sqlite  3 entries"));
+    assert!(lines[1].starts_with("\x1b[7m") && lines[1].contains("first"));
+    assert!(lines[2].starts_with("\x1b[33m") && lines[2].contains("second"));
+    assert_eq!(lines[11], fit(HELP, 60));
+    assert_eq!(lines[11], fit(HELP, 60));
+
+    // the list scrolls to keep the selection visible
+    assert!(press(&mut viewer, &[Key::Char('t')]));
+    let lines = viewer.lines(60, 7);
+    assert_eq!(viewer.top, 0);
+    assert_eq!(lines[6], "target: ▏");
+    assert!(press(&mut viewer, &[Key::Escape, Key::End]));
+    viewer.lines(60, 7);
+    assert_eq!(viewer.top, 1);
+}
+
+#[test]
+fn lines_are_cut_to_the_width() {
+    assert_eq!(fit("a\tb\ncdef", 4), "a b ");
+    assert_eq!(fit("äöü", 2), "äö");
+    assert_eq!(inverse("ab", 4), "\x1b[7mab  \x1b[0m");
+}
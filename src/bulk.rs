@@ -0,0 +1,158 @@
+use std::sync::{Arc, Mutex};
+
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{db::is_missing_table, LogHandle, LOGS_TABLE};
+
+/// Definitions of the `logs_v0` indexes dropped by `LogHandle::suspend_indexes`, until they
+/// are recreated.
+pub const SUSPENDED_INDEXES_TABLE: &str = "suspended_indexes_v0";
+
+const SUSPENDED_INDEXES_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS suspended_indexes_v0 (
+    name TEXT PRIMARY KEY,
+    sql TEXT NOT NULL
+)";
+
+impl LogHandle {
+    /// Drops the secondary indexes of `logs_v0`, so bulk writes don't have to update them.
+    /// Queries that used them scan the table until `restore_indexes`. Returns the number of
+    /// indexes dropped.
+    ///
+    /// Their definitions are kept in [`SUSPENDED_INDEXES_TABLE`] in the same transaction, so
+    /// `prepare_database` recreates them if the process stops before they are restored.
+    pub fn suspend_indexes(&self) -> rusqlite::Result<u64> {
+        suspend_indexes(&self.writer())
+    }
+
+    /// Recreates the indexes dropped by `suspend_indexes`, returns how many.
+    pub fn restore_indexes(&self) -> rusqlite::Result<u64> {
+        restore_indexes(&self.writer())
+    }
+
+    /// Runs `f` with the indexes suspended, e.g. around `import_jsonl` or `merge_from` of many
+    /// rows. They are restored also if `f` fails.
+    pub fn with_indexes_suspended<T>(
+        &self,
+        f: impl FnOnce() -> rusqlite::Result<T>,
+    ) -> rusqlite::Result<T> {
+        self.suspend_indexes()?;
+        let result = f();
+        let restored = self.restore_indexes();
+        let value = result?;
+        restored?;
+        Ok(value)
+    }
+}
+
+pub(crate) fn suspend_indexes(conn: &Connection) -> rusqlite::Result<u64> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(SUSPENDED_INDEXES_SCHEMA)?;
+    // indexes SQLite creates for constraints have no `sql` and can't be dropped
+    let indexes = tx
+        .prepare(
+            "SELECT name, sql FROM sqlite_schema
+             WHERE type = 'index' AND tbl_name = ?1 AND sql IS NOT NULL",
+        )?
+        .query_map([LOGS_TABLE], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (name, sql) in &indexes {
+        tx.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {SUSPENDED_INDEXES_TABLE} (name, sql) VALUES (?1, ?2)"
+            ),
+            (name, sql),
+        )?;
+        tx.execute_batch(&format!("DROP INDEX \"{}\"", name.replace('"', "\"\"")))?;
+    }
+    tx.commit()?;
+    Ok(indexes.len() as u64)
+}
+
+pub(crate) fn restore_indexes(conn: &Connection) -> rusqlite::Result<u64> {
+    let tx = conn.unchecked_transaction()?;
+    let indexes = match tx
+        .prepare(&format!("SELECT name, sql FROM {SUSPENDED_INDEXES_TABLE}"))
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()
+        }) {
+        Err(e) if is_missing_table(&e) => return Ok(0),
+        indexes => indexes?,
+    };
+
+    let mut restored = 0;
+    for (name, sql) in &indexes {
+        // `prepare_database` may have created the built-in ones again
+        let exists = tx
+            .query_row(
+                "SELECT 1 FROM sqlite_schema WHERE type = 'index' AND name = ?1",
+                [name],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if !exists {
+            tx.execute_batch(sql)?;
+            restored += 1;
+        }
+    }
+    tx.execute_batch(&format!("DELETE FROM {SUSPENDED_INDEXES_TABLE}"))?;
+    tx.commit()?;
+    Ok(restored)
+}
+
+/// Suspends the indexes while more than `rows_per_check` rows are written between two checks,
+/// see `SubscriberBuilder::with_burst_index_suspension`. Restores them when dropped.
+pub(crate) struct BurstDetector {
+    conn: Arc<Mutex<Connection>>,
+    rows_per_check: i64,
+    last_rowid: Option<i64>,
+    suspended: bool,
+}
+
+impl BurstDetector {
+    pub(crate) fn new(conn: Arc<Mutex<Connection>>, rows_per_check: i64) -> Self {
+        Self {
+            conn,
+            rows_per_check,
+            last_rowid: None,
+            suspended: false,
+        }
+    }
+
+    /// `conn` is the locked connection the detector was created with.
+    pub(crate) fn check(&mut self, conn: &Connection) -> rusqlite::Result<()> {
+        let rowid: i64 = conn.query_row(
+            &format!("SELECT coalesce(max(rowid), 0) FROM {LOGS_TABLE}"),
+            [],
+            |row| row.get(0),
+        )?;
+        let burst = self
+            .last_rowid
+            .is_some_and(|last| rowid - last > self.rows_per_check);
+        self.last_rowid = Some(rowid);
+
+        if burst && !self.suspended {
+            suspend_indexes(conn)?;
+            self.suspended = true;
+        } else if !burst && self.suspended {
+            restore_indexes(conn)?;
+            self.suspended = false;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for BurstDetector {
+    fn drop(&mut self) {
+        if self.suspended {
+            // otherwise the next `prepare_database` restores them
+            let _ = restore_indexes(&self.conn.lock().unwrap());
+        }
+    }
+}
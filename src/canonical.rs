@@ -0,0 +1,123 @@
+use std::{collections::HashMap, sync::Mutex, time::Instant};
+
+use time::OffsetDateTime;
+use tracing::{span, Level, Metadata};
+
+use crate::{LogEntry, Origin, TimePrecision};
+
+/// What `SubscriberBuilder::with_canonical_lines` collects.
+#[derive(Debug, Clone, Default)]
+pub struct CanonicalLineOptions {
+    /// The fields of the root span, its child spans and their events to collect, all if empty.
+    /// Later values replace earlier ones.
+    pub fields: Vec<&'static str>,
+    /// Only write the canonical line, not the events in the span.
+    pub replace_events: bool,
+}
+
+/// The canonical lines of the open root spans.
+#[derive(Debug)]
+pub(crate) struct CanonicalLines {
+    pub(crate) options: CanonicalLineOptions,
+    lines: Mutex<HashMap<span::Id, Line>>,
+}
+
+#[derive(Debug)]
+struct Line {
+    metadata: &'static Metadata<'static>,
+    started: Instant,
+    fields: HashMap<&'static str, String>,
+    events: u64,
+    /// Most severe level of the events.
+    level: Level,
+}
+
+impl CanonicalLines {
+    pub(crate) fn new(options: CanonicalLineOptions) -> Self {
+        Self {
+            options,
+            lines: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn start(
+        &self,
+        root: &span::Id,
+        metadata: &'static Metadata<'static>,
+        fields: &HashMap<&'static str, String>,
+    ) {
+        let mut line = Line {
+            metadata,
+            started: Instant::now(),
+            fields: HashMap::new(),
+            events: 0,
+            level: Level::INFO,
+        };
+        self.collect(&mut line, fields);
+        self.lines.lock().unwrap().insert(root.clone(), line);
+    }
+
+    /// Adds the fields of a span in the trace of `root`.
+    pub(crate) fn add_fields(&self, root: &span::Id, fields: &HashMap<&'static str, String>) {
+        if let Some(line) = self.lines.lock().unwrap().get_mut(root) {
+            self.collect(line, fields);
+        }
+    }
+
+    pub(crate) fn add_event(
+        &self,
+        root: &span::Id,
+        level: Level,
+        fields: &HashMap<&'static str, String>,
+    ) {
+        if let Some(line) = self.lines.lock().unwrap().get_mut(root) {
+            self.collect(line, fields);
+            line.events += 1;
+            line.level = line.level.min(level);
+        }
+    }
+
+    /// The line of the closed root span, named after it, at the most severe level of its
+    /// events and at least `INFO`.
+    pub(crate) fn finish(
+        &self,
+        root: &span::Id,
+        now: OffsetDateTime,
+    ) -> Option<LogEntry<&'static str>> {
+        let line = self.lines.lock().unwrap().remove(root)?;
+        let mut fields = line.fields;
+        fields.insert("canonical.events", line.events.to_string());
+        fields.insert(
+            "canonical.duration_us",
+            line.started.elapsed().as_micros().to_string(),
+        );
+
+        Some(LogEntry {
+            time: now,
+            time_precision: TimePrecision::Text,
+            level: line.level,
+            module: line.metadata.module_path(),
+            file: line.metadata.file(),
+            line: line.metadata.line(),
+            message: line.metadata.name().to_owned(),
+            structured: fields.into(),
+            category: None,
+            utc_offset: None,
+            repeat_count: 1,
+            origin: Some(Origin::Tracing),
+            expires_at: None,
+            elapsed: None,
+            event_id: None,
+            trace_context: None,
+        })
+    }
+
+    fn collect(&self, line: &mut Line, fields: &HashMap<&'static str, String>) {
+        let selected = fields.iter().filter(|(name, _)| {
+            self.options.fields.is_empty() || self.options.fields.contains(name)
+        });
+        for (name, value) in selected {
+            line.fields.insert(name, value.clone());
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use rusqlite::Connection;
+
+use crate::LogHandle;
+
+/// How much work a WAL checkpoint does, see `PRAGMA wal_checkpoint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CheckpointMode {
+    /// Copy as many frames as possible without waiting for readers or writers.
+    Passive,
+    /// Wait for writers, then copy every frame.
+    Full,
+    /// `Full`, then wait for readers so the next writer starts the WAL file from the beginning.
+    Restart,
+    /// `Restart`, then truncate the WAL file to zero bytes.
+    Truncate,
+}
+
+impl CheckpointMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckpointMode::Passive => "PASSIVE",
+            CheckpointMode::Full => "FULL",
+            CheckpointMode::Restart => "RESTART",
+            CheckpointMode::Truncate => "TRUNCATE",
+        }
+    }
+}
+
+/// What a checkpoint did, the columns of `PRAGMA wal_checkpoint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// A reader or writer kept a `Full`, `Restart` or `Truncate` checkpoint from finishing.
+    pub busy: bool,
+    /// Frames in the WAL file, `-1` if the database is not in WAL mode.
+    pub wal_frames: i64,
+    /// Frames copied into the database, `-1` if the database is not in WAL mode.
+    pub checkpointed_frames: i64,
+}
+
+impl LogHandle {
+    /// Copies the WAL file into the database through the connection the handle writes with.
+    /// Readers that stay in a transaction, e.g. long tail sessions, keep it from finishing.
+    pub fn checkpoint(&self, mode: CheckpointMode) -> rusqlite::Result<Checkpoint> {
+        checkpoint(&self.writer(), mode)
+    }
+}
+
+pub(crate) fn checkpoint(conn: &Connection, mode: CheckpointMode) -> rusqlite::Result<Checkpoint> {
+    conn.query_row(
+        &format!("PRAGMA wal_checkpoint({})", mode.as_str()),
+        [],
+        |row| {
+            Ok(Checkpoint {
+                busy: row.get(0)?,
+                wal_frames: row.get(1)?,
+                checkpointed_frames: row.get(2)?,
+            })
+        },
+    )
+}
@@ -0,0 +1,51 @@
+use time::OffsetDateTime;
+
+/// The source of the timestamps the layer records, see `SubscriberBuilder::with_clock`.
+///
+/// Closures returning an `OffsetDateTime` implement it, e.g. for reproducible timestamps in
+/// tests:
+///
+/// ```
+/// # use tracing_subscriber_sqlite::SubscriberBuilder;
+/// use time::{Duration, OffsetDateTime};
+///
+/// let start = OffsetDateTime::UNIX_EPOCH + Duration::days(19_723);
+/// let builder = SubscriberBuilder::new().with_clock(move || start);
+/// ```
+pub trait Clock: Send + Sync {
+    /// The time of an event recorded now, in UTC.
+    fn now(&self) -> OffsetDateTime;
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> OffsetDateTime + Send + Sync,
+{
+    fn now(&self) -> OffsetDateTime {
+        self()
+    }
+}
+
+/// The system clock, `OffsetDateTime::now_utc`. The default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+pub(crate) struct ClockSource(pub(crate) Box<dyn Clock>);
+
+impl Default for ClockSource {
+    fn default() -> Self {
+        Self(Box::new(SystemClock))
+    }
+}
+
+impl std::fmt::Debug for ClockSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Clock")
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tracing::Level;
+
+use crate::LogEntry;
+
+/// Collapses identical consecutive entries (same level, module and message) within a window.
+///
+/// The first entry of a run is written right away, the repeats are counted and written as a
+/// single row with `repeat_count` once the run ends, so nothing is delayed unless it repeats.
+#[derive(Debug)]
+pub(crate) struct Coalescer {
+    window: Duration,
+    run: Mutex<Option<Run>>,
+}
+
+#[derive(Debug)]
+struct Run {
+    started: Instant,
+    level: Level,
+    module: Option<String>,
+    message: String,
+    /// The latest repeat, with `repeat_count` set to the number of repeats.
+    repeated: Option<LogEntry>,
+}
+
+impl Run {
+    fn matches(&self, entry: &LogEntry<&str>) -> bool {
+        self.level == entry.level
+            && self.module.as_deref() == entry.module
+            && self.message == entry.message
+    }
+}
+
+impl Coalescer {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            run: Mutex::new(None),
+        }
+    }
+
+    /// Passes the repeats counted so far to `write`, a later repeat starts counting again.
+    pub(crate) fn flush(&self, mut write: impl FnMut(LogEntry<&str>)) {
+        let mut run = self.run.lock().unwrap();
+        if let Some(repeated) = run.as_mut().and_then(|run| run.repeated.take()) {
+            write(repeated.to_borrowed());
+        }
+    }
+
+    /// Passes `entry` to `write` unless it repeats the current run, writing the finished run first.
+    pub(crate) fn coalesce(&self, entry: LogEntry<&str>, mut write: impl FnMut(LogEntry<&str>)) {
+        let mut run = self.run.lock().unwrap();
+
+        if let Some(current) = run.as_mut() {
+            if current.matches(&entry) && current.started.elapsed() < self.window {
+                let repeat_count = current.repeated.as_ref().map_or(0, |e| e.repeat_count) + 1;
+                current.repeated = Some(LogEntry {
+                    repeat_count,
+                    ..entry.into_owned()
+                });
+                return;
+            }
+        }
+
+        if let Some(repeated) = run.take().and_then(|run| run.repeated) {
+            write(repeated.to_borrowed());
+        }
+
+        *run = Some(Run {
+            started: Instant::now(),
+            level: entry.level,
+            module: entry.module.map(str::to_owned),
+            message: entry.message.clone(),
+            repeated: None,
+        });
+        write(entry);
+    }
+}
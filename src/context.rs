@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use crate::redact::{FieldLists, Redactor};
+
+/// Adds application context to every recorded event, e.g. the current user id or feature
+/// flags from task-locals, see `SubscriberBuilder::with_context_provider`.
+///
+/// Closures taking `&mut ContextFields` implement it:
+///
+/// ```
+/// # use tracing_subscriber_sqlite::{ContextFields, SubscriberBuilder};
+/// let builder = SubscriberBuilder::new().with_context_provider(|fields: &mut ContextFields| {
+///     fields.insert("app.version", env!("CARGO_PKG_VERSION"));
+/// });
+/// ```
+pub trait ContextProvider: Send + Sync {
+    /// Called on the thread that records the event, before field filters run.
+    fn provide(&self, fields: &mut ContextFields<'_>);
+}
+
+impl<F> ContextProvider for F
+where
+    F: Fn(&mut ContextFields<'_>) + Send + Sync,
+{
+    fn provide(&self, fields: &mut ContextFields<'_>) {
+        self(fields)
+    }
+}
+
+/// The structured fields of an event, passed to a [`ContextProvider`].
+pub struct ContextFields<'a> {
+    pub(crate) kvs: &'a mut HashMap<&'static str, String>,
+    pub(crate) redactor: &'a Redactor,
+    pub(crate) field_lists: &'a FieldLists,
+    pub(crate) max_field_len: Option<usize>,
+}
+
+impl ContextFields<'_> {
+    /// Adds a field unless the event recorded one with the same name. Like the event's own
+    /// fields, the `Debug` representation of `value` is stored, and redaction, field lists and
+    /// the field length limit apply.
+    pub fn insert(&mut self, name: &'static str, value: impl std::fmt::Debug) {
+        if self.kvs.contains_key(name) || !self.field_lists.keeps(name) {
+            return;
+        }
+
+        let mut value = self.redactor.redact(name, format!("{value:?}"));
+        if let Some(max) = self.max_field_len {
+            crate::truncate(&mut value, max);
+        }
+        self.kvs.insert(name, value);
+    }
+
+    /// The value of a field the event recorded (or a provider added), as stored.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.kvs.get(name).map(String::as_str)
+    }
+}
+
+impl std::fmt::Debug for ContextFields<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.kvs.iter()).finish()
+    }
+}
+
+pub(crate) struct Provider(pub(crate) Box<dyn ContextProvider>);
+
+impl std::fmt::Debug for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ContextProvider")
+    }
+}
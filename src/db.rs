@@ -1,90 +1,1260 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    borrow::Cow,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
-use rusqlite::Connection;
-use time::OffsetDateTime;
+use rusqlite::{
+    types::{Type, Value},
+    Connection, InterruptHandle, OpenFlags,
+};
+use time::{OffsetDateTime, UtcOffset};
 use tracing::Level;
 
+use crate::{
+    format,
+    store::TailSource,
+    timestamp::{StoredTime, NANOS_FROM},
+    Column, LogQuery, LogStore, Order, StoreStats, Structured, Tail, TailOptions, TimePrecision,
+    Timezone, TraceContext, Ulid, RUNS_TABLE,
+};
+
 pub const SQL_SCHEMA: &str = include_str!("../schema/log.sql");
 
+/// Columns added to `logs_v0` after its first release, as `(name, definition)`.
+///
+/// `prepare_database` adds the missing ones to databases created by older versions.
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("category", "TEXT"),
+    ("utc_offset", "INTEGER"),
+    ("repeat_count", "INTEGER NOT NULL DEFAULT 1"),
+    ("origin", "TEXT"),
+    ("expires_at", "TEXT"),
+    ("module_id", "INTEGER"),
+    ("file_id", "INTEGER"),
+    ("elapsed_us", "INTEGER"),
+    ("event_id", "TEXT"),
+    ("trace_id", "TEXT"),
+    ("span_id", "TEXT"),
+];
+
+/// Interned `module` and `file` values, see [`interned`].
+pub const STRINGS_TABLE: &str = "strings_v0";
+
+/// The table `Connect for Connection` writes to.
+pub const LOGS_TABLE: &str = "logs_v0";
+
+/// Conventional table for severe entries when routing them separately, see [`Route`].
+pub const ERRORS_TABLE: &str = "errors_v0";
+
 pub fn prepare_database(conn: &Connection) -> rusqlite::Result<()> {
-    conn.execute(SQL_SCHEMA, ()).map(|_| {})
+    prepare_database_with_progress(conn, |_| {})
 }
 
-#[derive(Debug, Clone)]
-// Here we are using Mutex instead of RwLock because Connection did not implement Sync
-pub struct LogHandle(pub(crate) Arc<Mutex<Connection>>);
+/// Whether `e` says that a table doesn't exist, e.g. because `prepare_database` wasn't called.
+pub(crate) fn is_missing_table(e: &rusqlite::Error) -> bool {
+    matches!(e, rusqlite::Error::SqliteFailure(_, Some(msg)) if msg.starts_with("no such table"))
+}
+
+/// Tells the user that a write is preparing the database, see `Connect::log_or_prepare`.
+pub(crate) fn warn_missing_table() {
+    eprintln!(
+        "tracing-subscriber-sqlite: the log table is missing, preparing the database. \
+         Call `prepare_database` or use `build_prepared` to avoid this."
+    );
+}
+
+/// Same as `prepare_database`, reporting each migration step to `progress`.
+pub fn prepare_database_with_progress(
+    conn: &Connection,
+    progress: impl Fn(Progress),
+) -> rusqlite::Result<()> {
+    conn.execute_batch(SQL_SCHEMA)?;
+    migrate(conn, "main", LOGS_TABLE, progress)?;
+    crate::field_index::migrate_field_indexes(conn)?;
+    // indexes suspended by a process that stopped during a bulk write
+    crate::bulk::restore_indexes(conn).map(drop)
+}
+
+/// Creates (or migrates) `table` with the same schema as `logs_v0`, e.g. [`ERRORS_TABLE`].
+pub fn prepare_table(conn: &Connection, table: &str) -> rusqlite::Result<()> {
+    conn.execute_batch(&SQL_SCHEMA.replace(LOGS_TABLE, table))?;
+    migrate(conn, "main", table, |_| {})
+}
+
+/// Creates (or migrates) `logs_v0` in the attached database `schema`.
+pub(crate) fn prepare_attached(conn: &Connection, schema: &str) -> rusqlite::Result<()> {
+    conn.execute_batch(&SQL_SCHEMA.replace(LOGS_TABLE, &format!("{schema}.{LOGS_TABLE}")))?;
+    migrate(conn, schema, LOGS_TABLE, |_| {})
+}
+
+fn migrate(
+    conn: &Connection,
+    schema: &str,
+    table: &str,
+    progress: impl Fn(Progress),
+) -> rusqlite::Result<()> {
+    let existing = conn
+        .prepare("SELECT name FROM pragma_table_info(?1, ?2)")?
+        .query_map([table, schema], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let missing: Vec<_> = ADDED_COLUMNS
+        .iter()
+        .filter(|(name, _)| !existing.iter().any(|c| c == name))
+        .collect();
+    let total = missing.len() as u64;
+
+    progress(Progress::new(0, Some(total)));
+    for (done, (name, definition)) in missing.into_iter().enumerate() {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {schema}.{table} ADD COLUMN {name} {definition}"
+        ))?;
+        progress(Progress::new(done as u64 + 1, Some(total)));
+    }
+
+    conn.execute_batch(&format!(
+        "CREATE INDEX IF NOT EXISTS {schema}.{table}_expires_at ON {table} (expires_at) WHERE expires_at IS NOT NULL;
+         CREATE INDEX IF NOT EXISTS {schema}.{table}_event_id ON {table} (event_id) WHERE event_id IS NOT NULL;
+         CREATE INDEX IF NOT EXISTS {schema}.{table}_trace_id ON {table} (trace_id) WHERE trace_id IS NOT NULL;
+         CREATE TABLE IF NOT EXISTS {schema}.{STRINGS_TABLE} (id INTEGER PRIMARY KEY, value TEXT NOT NULL UNIQUE);"
+    ))
+}
+
+/// The SQL expression for the text of `column` (`module` or `file`) of a row in `schema`.
+///
+/// The rusqlite sinks store these values once in [`STRINGS_TABLE`] and reference them by id
+/// in `<column>_id`, leaving `<column>` `NULL`. Other sinks and older versions store the text.
+pub(crate) fn interned(column: &str, schema: &str) -> String {
+    format!(
+        "coalesce({column}, (SELECT value FROM {schema}.{STRINGS_TABLE} WHERE id = {column}_id))"
+    )
+}
+
+/// Rows between two progress reports of row by row operations, e.g. `export_jsonl_with_progress`.
+pub(crate) const PROGRESS_ROWS: u64 = 1000;
+
+/// Progress of a long running operation, passed to `*_with_progress` callbacks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Units of work (rows or steps) finished so far.
+    pub done: u64,
+    /// Total units of work, if known up front.
+    pub total: Option<u64>,
+}
+
+impl Progress {
+    pub fn new(done: u64, total: Option<u64>) -> Self {
+        Self { done, total }
+    }
+
+    /// Finished fraction in `0.0..=1.0`, if the total is known.
+    pub fn fraction(&self) -> Option<f64> {
+        self.total.map(|total| match total {
+            0 => 1.0,
+            total => self.done as f64 / total as f64,
+        })
+    }
+}
+
+/// Coarse classification of warnings and errors, stored in the `category` column.
+///
+/// Set it on an event with the `error.category` field (`"user"`, `"system"` or `"external"`),
+/// or derive it from targets and fields with the builder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    /// Caused by invalid input or misuse, usually not actionable by operators.
+    User,
+    /// A bug or failure inside the application itself.
+    System,
+    /// A failing dependency such as a remote service or the filesystem.
+    External,
+}
 
+impl ErrorCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCategory::User => "user",
+            ErrorCategory::System => "system",
+            ErrorCategory::External => "external",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for ErrorCategory {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "user" => Ok(ErrorCategory::User),
+            "system" => Ok(ErrorCategory::System),
+            "external" => Ok(ErrorCategory::External),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Reads entries from a log database and writes to it as a `Connect`.
+///
+/// Clones read through their own connection to the same file, opened on first use, so a clone
+/// moved to another thread doesn't wait for the writer or for other handles. Writes always go
+/// through the connection the handle was created with. Private in-memory databases can't be
+/// opened twice, their clones keep sharing that connection; use [`LogHandle::shared_memory`]
+/// for an in-memory database with independent readers.
 #[derive(Debug)]
+pub struct LogHandle {
+    // Here we are using Mutex instead of RwLock because Connection did not implement Sync
+    conn: Arc<Mutex<Connection>>,
+    /// Path or URI clones open their read connection with.
+    source: Option<Arc<str>>,
+    reader: Option<Mutex<Option<Connection>>>,
+    /// Highest `logs_v0` rowid queries see, see `LogHandle::as_of`.
+    as_of: Option<i64>,
+    #[cfg(feature = "sqlcipher")]
+    encryption_key: Option<Arc<str>>,
+}
+
+#[derive(Debug, Clone)]
 pub struct LogEntry<S = String> {
     pub time: OffsetDateTime,
+    /// How `time` is (or was) stored, see `SubscriberBuilder::with_time_precision`.
+    pub time_precision: TimePrecision,
     pub level: Level,
     pub module: Option<S>,
     pub file: Option<S>,
     pub line: Option<u32>,
     pub message: String,
-    pub structured: HashMap<S, String>,
+    pub structured: Structured<S>,
+    pub category: Option<ErrorCategory>,
+    /// UTC offset of the writer when the event happened, stored in minutes. `time` is stored in
+    /// UTC, and read in UTC unless the query sets a `LogQuery::timezone`.
+    pub utc_offset: Option<UtcOffset>,
+    /// How many identical consecutive events this row stands for, see `SubscriberBuilder::with_coalescing`.
+    pub repeat_count: u64,
+    /// Where the entry came from, `None` for rows written before this was recorded.
+    pub origin: Option<Origin>,
+    /// When `LogHandle::delete_expired` may delete the entry, set with the `log_ttl_secs` field.
+    pub expires_at: Option<OffsetDateTime>,
+    /// Time since the subscriber was created, from a monotonic clock, stored in microseconds.
+    /// Orders the events of one process correctly when the system clock jumps.
+    pub elapsed: Option<Duration>,
+    /// Generated by the layer for each entry, `None` for rows written before ids were recorded.
+    pub event_id: Option<Ulid>,
+    /// The OpenTelemetry trace and span the event was recorded in, see
+    /// `SubscriberBuilder::with_trace_context`.
+    pub trace_context: Option<TraceContext>,
+}
+
+/// Reads a row selected with `LogQuery::select_list`, tolerating `NULL` for unselected columns.
+pub(crate) fn entry_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<LogEntry> {
+    Ok(LogEntry {
+        time: row.get::<_, StoredTime>(0)?.0,
+        time_precision: TimePrecision::of(row.get_ref(0)?),
+        level: {
+            let level: String = row.get(1)?;
+            level.parse().unwrap()
+        },
+        module: row.get(2)?,
+        file: row.get(3)?,
+        line: row.get(4)?,
+        message: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+        structured: match row.get(6)? {
+            Value::Text(raw) => Structured::from_stored_json(raw)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(6, Type::Text, e.into()))?,
+            Value::Blob(bytes) => format::decode(&bytes)
+                .map(Structured::from_fields)
+                .ok_or_else(|| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        6,
+                        Type::Blob,
+                        "the fields are neither MessagePack nor CBOR".into(),
+                    )
+                })?,
+            _ => Structured::default(),
+        },
+        category: row
+            .get::<_, Option<String>>(7)?
+            .and_then(|c| c.parse().ok()),
+        utc_offset: row
+            .get::<_, Option<i32>>(8)?
+            .and_then(|minutes| UtcOffset::from_whole_seconds(minutes * 60).ok()),
+        repeat_count: row.get::<_, Option<u64>>(9)?.unwrap_or(1),
+        origin: row
+            .get::<_, Option<String>>(10)?
+            .and_then(|o| o.parse().ok()),
+        expires_at: row.get(11)?,
+        elapsed: row.get::<_, Option<u64>>(12)?.map(Duration::from_micros),
+        event_id: row
+            .get::<_, Option<String>>(13)?
+            .and_then(|id| id.parse().ok()),
+        trace_context: TraceContext::from_hex(
+            row.get::<_, Option<String>>(14)?.as_deref(),
+            row.get::<_, Option<String>>(15)?.as_deref(),
+        ),
+    })
+}
+
+/// Where an entry came from, stored in the `origin` column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Origin {
+    /// A `tracing` event, including the rows the layer writes itself.
+    Tracing,
+    /// A `log` record bridged by `tracing-log`.
+    Log,
+    /// Imported from another database or file.
+    Import,
+}
+
+impl Origin {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Origin::Tracing => "tracing",
+            Origin::Log => "log",
+            Origin::Import => "import",
+        }
+    }
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Origin {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "tracing" => Ok(Origin::Tracing),
+            "log" => Ok(Origin::Log),
+            "import" => Ok(Origin::Import),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Cancels reads started with a clone of this token, e.g. when a UI changes its filters.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl<S> LogEntry<S> {
+    /// `time` in the writer's own UTC offset, if it was recorded.
+    pub fn local_time(&self) -> OffsetDateTime {
+        match self.utc_offset {
+            Some(offset) => self.time.to_offset(offset),
+            None => self.time,
+        }
+    }
+
+    /// `time` in `timezone`, e.g. `Timezone::local()` to show it to the user of this system.
+    pub fn time_in(&self, timezone: Timezone) -> OffsetDateTime {
+        self.time.to_offset(timezone.offset(self.utc_offset))
+    }
+}
+
+impl LogEntry<&str> {
+    pub fn into_owned(self) -> LogEntry {
+        LogEntry {
+            time: self.time,
+            time_precision: self.time_precision,
+            level: self.level,
+            module: self.module.map(str::to_owned),
+            file: self.file.map(str::to_owned),
+            line: self.line,
+            message: self.message,
+            structured: self.structured.into_owned(),
+            category: self.category,
+            utc_offset: self.utc_offset,
+            repeat_count: self.repeat_count,
+            origin: self.origin,
+            expires_at: self.expires_at,
+            elapsed: self.elapsed,
+            event_id: self.event_id,
+            trace_context: self.trace_context,
+        }
+    }
+}
+
+impl LogEntry {
+    pub fn to_borrowed(&self) -> LogEntry<&str> {
+        LogEntry {
+            time: self.time,
+            time_precision: self.time_precision,
+            level: self.level,
+            module: self.module.as_deref(),
+            file: self.file.as_deref(),
+            line: self.line,
+            message: self.message.clone(),
+            structured: self.structured.to_borrowed(),
+            category: self.category,
+            utc_offset: self.utc_offset,
+            repeat_count: self.repeat_count,
+            origin: self.origin,
+            expires_at: self.expires_at,
+            elapsed: self.elapsed,
+            event_id: self.event_id,
+            trace_context: self.trace_context,
+        }
+    }
+}
+
+fn query_entries(conn: &Connection, query: &LogQuery) -> rusqlite::Result<Vec<LogEntry>> {
+    let (sql, params) = query.to_sql();
+    let mut stmt = conn.prepare(&sql)?;
+    let log_iter = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+        entry_from_row(row).map(|entry| query.present(entry))
+    })?;
+
+    log_iter.collect()
+}
+
+impl Clone for LogHandle {
+    fn clone(&self) -> Self {
+        Self {
+            conn: self.conn.clone(),
+            source: self.source.clone(),
+            reader: self.source.is_some().then(Mutex::default),
+            as_of: self.as_of,
+            #[cfg(feature = "sqlcipher")]
+            encryption_key: self.encryption_key.clone(),
+        }
+    }
 }
 
 impl LogHandle {
     pub fn new(connection: Connection) -> Self {
-        Self(Arc::new(Mutex::new(connection)))
+        let source = connection
+            .path()
+            .filter(|path| !path.is_empty())
+            .map(Arc::from);
+
+        Self {
+            conn: Arc::new(Mutex::new(connection)),
+            source,
+            reader: None,
+            as_of: None,
+            #[cfg(feature = "sqlcipher")]
+            encryption_key: None,
+        }
     }
 
-    pub fn read_logs(&self) -> rusqlite::Result<Vec<LogEntry>> {
-        let conn = self.0.lock().unwrap();
-
-        let mut stmt = conn.prepare("SELECT * FROM logs_v0")?;
-        let log_iter = stmt.query_map([], |row| {
-            Ok(LogEntry {
-                time: row.get(0)?,
-                level: {
-                    let level: String = row.get(1)?;
-                    level.parse().unwrap()
-                },
-                module: row.get(2)?,
-                file: row.get(3)?,
-                line: row.get(4)?,
-                message: row.get(5)?,
-                structured: {
-                    let structured: String = row.get(6)?;
-                    serde_json::from_str(&structured).unwrap()
-                },
-            })
+    /// Opens the in-memory database `name` in shared-cache mode (`file:name?mode=memory&cache=shared`)
+    /// and prepares it.
+    ///
+    /// Clones read through their own connections to it, so tests and short-lived tools get
+    /// a writer and concurrent readers like with a file, without touching the disk.
+    /// The database is deleted when the last connection to it is closed. Readers use
+    /// `read_uncommitted`, otherwise they would fail with `SQLITE_LOCKED` while the writer
+    /// holds the table lock.
+    pub fn shared_memory(name: &str) -> rusqlite::Result<Self> {
+        let uri = format!("file:{name}?mode=memory&cache=shared");
+        let connection = Connection::open_with_flags(
+            &uri,
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+                | OpenFlags::SQLITE_OPEN_CREATE
+                | OpenFlags::SQLITE_OPEN_URI
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        prepare_database(&connection)?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(connection)),
+            source: Some(uri.into()),
+            reader: None,
+            as_of: None,
+            #[cfg(feature = "sqlcipher")]
+            encryption_key: None,
+        })
+    }
+
+    /// The SQLCipher key clones open their read connections with. The connection the handle
+    /// was created with must have the key set already. Enabled by the `sqlcipher` feature.
+    #[cfg(feature = "sqlcipher")]
+    pub fn with_encryption_key(self, key: &str) -> Self {
+        Self {
+            encryption_key: Some(key.into()),
+            ..self
+        }
+    }
+
+    /// A handle whose queries only see the `logs_v0` rows that existed when the run `run_id`
+    /// ended, using the `last_log_rowid` recorded by [`RunRecorder`](crate::RunRecorder).
+    /// Other tables and writes through the handle are not affected.
+    ///
+    /// Fails with `QueryReturnedNoRows` if the run doesn't exist or hasn't recorded its end,
+    /// e.g. because it is still going or was killed.
+    pub fn as_of(&self, run_id: i64) -> rusqlite::Result<Self> {
+        let rowid = self.with_reader(|conn| {
+            conn.query_row(
+                &format!("SELECT last_log_rowid FROM {RUNS_TABLE} WHERE rowid = ?1"),
+                [run_id],
+                |row| row.get::<_, Option<i64>>(0),
+            )
         })?;
 
-        log_iter.collect()
+        Ok(Self {
+            as_of: Some(rowid.ok_or(rusqlite::Error::QueryReturnedNoRows)?),
+            ..self.clone()
+        })
+    }
+
+    /// `query` limited to the rows this handle sees.
+    pub(crate) fn scoped<'a>(&self, query: &'a LogQuery) -> Cow<'a, LogQuery> {
+        match self.as_of {
+            Some(rowid) if query.table_name() == LOGS_TABLE => {
+                let rowid = query
+                    .max_rowid_bound()
+                    .map_or(rowid, |bound| bound.min(rowid));
+                Cow::Owned(query.clone().max_rowid(rowid))
+            }
+            _ => Cow::Borrowed(query),
+        }
+    }
+
+    /// The connection this handle writes through.
+    pub(crate) fn writer(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap()
+    }
+
+    /// The file of the database, `None` for in-memory databases.
+    pub(crate) fn file_path(&self) -> Option<&std::path::Path> {
+        self.source
+            .as_deref()
+            .filter(|source| !source.is_empty() && !source.starts_with("file:"))
+            .map(std::path::Path::new)
+    }
+
+    /// Sets the key of `with_encryption_key` on a connection opened by the handle.
+    pub(crate) fn apply_encryption_key(&self, _conn: &Connection) -> rusqlite::Result<()> {
+        #[cfg(feature = "sqlcipher")]
+        if let Some(key) = &self.encryption_key {
+            _conn.pragma_update(None, "key", key)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `f` with the connection this handle reads through.
+    pub(crate) fn with_reader<T>(
+        &self,
+        f: impl FnOnce(&Connection) -> rusqlite::Result<T>,
+    ) -> rusqlite::Result<T> {
+        let Some(reader) = &self.reader else {
+            return f(&self.conn.lock().unwrap());
+        };
+
+        let mut reader = reader.lock().unwrap();
+        let conn = match &mut *reader {
+            Some(conn) => conn,
+            empty => {
+                let source = self.source.as_deref().unwrap_or_default();
+                let conn = Connection::open_with_flags(
+                    source,
+                    OpenFlags::SQLITE_OPEN_READ_ONLY
+                        | OpenFlags::SQLITE_OPEN_URI
+                        | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                )?;
+                self.apply_encryption_key(&conn)?;
+                if source.starts_with("file:") && source.contains("cache=shared") {
+                    conn.pragma_update(None, "read_uncommitted", true)?;
+                }
+                empty.insert(conn)
+            }
+        };
+        f(conn)
+    }
+
+    pub fn read_logs(&self) -> rusqlite::Result<Vec<LogEntry>> {
+        self.query(&LogQuery::new())
+    }
+
+    /// The `n` newest entries, oldest first. Only reads those rows.
+    pub fn latest(&self, n: u64) -> rusqlite::Result<Vec<LogEntry>> {
+        let mut entries = self.query(&LogQuery::new().order(Order::NewestFirst).limit(n))?;
+        entries.reverse();
+        Ok(entries)
+    }
+
+    /// Same as `read_logs`, but gives up with `SQLITE_INTERRUPT` as soon as `token` is cancelled.
+    pub fn read_logs_cancellable(
+        &self,
+        token: &CancellationToken,
+    ) -> rusqlite::Result<Vec<LogEntry>> {
+        self.query_cancellable(&LogQuery::new(), token)
+    }
+
+    /// Entries matching `query`, oldest first unless it orders them otherwise.
+    pub fn query(&self, query: &LogQuery) -> rusqlite::Result<Vec<LogEntry>> {
+        self.with_reader(|conn| query_entries(conn, &self.scoped(query)))
+    }
+
+    /// Calls `f` with each entry matching `query`, oldest first, without collecting them.
+    /// Returns the number of entries.
+    pub(crate) fn for_each_entry(
+        &self,
+        query: &LogQuery,
+        mut f: impl FnMut(LogEntry) -> rusqlite::Result<()>,
+    ) -> rusqlite::Result<u64> {
+        let (sql, params) = self.scoped(query).to_sql();
+        self.with_reader(|conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let mut rows = stmt.query(rusqlite::params_from_iter(params))?;
+            let mut count = 0;
+            while let Some(row) = rows.next()? {
+                f(query.present(entry_from_row(row)?))?;
+                count += 1;
+            }
+            Ok(count)
+        })
+    }
+
+    /// Number of rows `for_each_entry` visits for `query`.
+    pub(crate) fn count_rows(&self, query: &LogQuery) -> rusqlite::Result<u64> {
+        let (sql, params) = self.scoped(query).to_sql();
+        self.with_reader(|conn| {
+            conn.query_row(
+                &format!("SELECT count(*) FROM ({sql})"),
+                rusqlite::params_from_iter(params),
+                |row| row.get(0),
+            )
+        })
+    }
+
+    /// Same as `query`, but gives up with `SQLITE_INTERRUPT` as soon as `token` is cancelled.
+    pub fn query_cancellable(
+        &self,
+        query: &LogQuery,
+        token: &CancellationToken,
+    ) -> rusqlite::Result<Vec<LogEntry>> {
+        if token.is_cancelled() {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_INTERRUPT),
+                None,
+            ));
+        }
+
+        self.with_reader(|conn| {
+            let token = token.clone();
+            conn.progress_handler(100, Some(move || token.is_cancelled()));
+            let result = query_entries(conn, &self.scoped(query));
+            conn.progress_handler(100, None::<fn() -> bool>);
+
+            result
+        })
+    }
+
+    /// Follow entries matching `query` that are written from now on, delivered in batches.
+    /// `limit` and `offset` of the query are ignored.
+    pub fn tail(&self, query: LogQuery, options: TailOptions) -> rusqlite::Result<Tail> {
+        Tail::new(self.clone(), query, options)
+    }
+
+    /// A handle to abort the query currently running on this handle from another thread.
+    ///
+    /// Opens the read connection of a clone if needed, the error is returned if that fails.
+    pub fn interrupt_handle(&self) -> rusqlite::Result<InterruptHandle> {
+        self.with_reader(|conn| Ok(conn.get_interrupt_handle()))
+    }
+}
+
+impl TailSource for LogHandle {
+    fn last_id(&self, query: &LogQuery) -> rusqlite::Result<i64> {
+        let table = query.table_name();
+        self.with_reader(|conn| {
+            conn.query_row(
+                &format!("SELECT coalesce(max(rowid), 0) FROM {table}"),
+                [],
+                |row| row.get(0),
+            )
+        })
+        .map(|rowid: i64| match self.as_of {
+            Some(as_of) if table == LOGS_TABLE => rowid.min(as_of),
+            _ => rowid,
+        })
+    }
+
+    fn entries_after(
+        &self,
+        rowid: i64,
+        query: &LogQuery,
+        limit: u64,
+    ) -> rusqlite::Result<(i64, Vec<LogEntry>)> {
+        let query = self.scoped(query);
+        let table = query.table_name();
+        let (where_clause, mut params) = query.where_clause();
+        let sql = format!(
+            "SELECT {}, rowid FROM {table} WHERE rowid > ? AND rowid <= ?{} ORDER BY rowid \
+             LIMIT {limit}",
+            query.select_list(),
+            where_clause.replacen(" WHERE ", " AND ", 1),
+        );
+
+        self.with_reader(|conn| {
+            // the newest rowid and the entries come from the same snapshot
+            let tx = conn.unchecked_transaction()?;
+            let newest: i64 = tx.query_row(
+                &format!("SELECT coalesce(max(rowid), 0) FROM {table}"),
+                [],
+                |row| row.get(0),
+            )?;
+            let newest = match self.as_of {
+                Some(as_of) if table == LOGS_TABLE => newest.min(as_of),
+                _ => newest,
+            };
+            params.insert(0, rowid.into());
+            params.insert(1, newest.into());
+
+            let mut stmt = tx.prepare(&sql)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+                Ok((
+                    row.get::<_, i64>(Column::ALL.len())?,
+                    query.present(entry_from_row(row)?),
+                ))
+            })?;
+            let rows = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+
+            // a full batch may be followed by more matches, otherwise everything up to the
+            // newest row was looked at
+            let scanned = match rows.last() {
+                Some((last, _)) if rows.len() as u64 == limit => *last,
+                _ => rowid.max(newest),
+            };
+            Ok((scanned, rows.into_iter().map(|(_, entry)| entry).collect()))
+        })
+    }
+}
+
+impl LogStore for LogHandle {
+    fn query(&self, query: &LogQuery) -> rusqlite::Result<Vec<LogEntry>> {
+        LogHandle::query(self, query)
+    }
+
+    fn tail(&self, query: LogQuery, options: TailOptions) -> rusqlite::Result<Tail> {
+        LogHandle::tail(self, query, options)
+    }
+
+    fn stats(&self) -> rusqlite::Result<StoreStats> {
+        // text and the integer precisions only compare among themselves
+        self.with_reader(|conn| {
+            let mut stats = StoreStats::default();
+            let mut stmt = conn.prepare(&format!(
+                "SELECT count(*), min(time), max(time) FROM {LOGS_TABLE} WHERE rowid <= ?1
+                 GROUP BY typeof(time), abs(time) >= {NANOS_FROM}"
+            ))?;
+            let mut rows = stmt.query([self.as_of.unwrap_or(i64::MAX)])?;
+            while let Some(row) = rows.next()? {
+                let earliest = row.get::<_, StoredTime>(1)?.0;
+                let latest = row.get::<_, StoredTime>(2)?.0;
+                stats.rows += row.get::<_, u64>(0)?;
+                stats.earliest = Some(stats.earliest.map_or(earliest, |t| t.min(earliest)));
+                stats.latest = Some(stats.latest.map_or(latest, |t| t.max(latest)));
+            }
+            Ok(stats)
+        })
     }
 }
 
+/// A sink for log entries.
+///
+/// Errors are handed to the layer's error handler, see `SubscriberBuilder::with_error_handler`.
 pub trait Connect {
-    fn log(&self, entry: LogEntry<&str>);
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()>;
+
+    /// Write out entries a batching sink is holding back. Called by the layer after entries
+    /// at the flush level, see `SubscriberBuilder::with_flush_level`.
+    fn flush(&self) -> rusqlite::Result<()> {
+        Ok(())
+    }
+
+    /// `flush`, then block until every entry logged before the call is in the database, for
+    /// sinks that write on another thread or task. Fails if that takes longer than `timeout`.
+    fn flush_and_wait(&self, timeout: Duration) -> rusqlite::Result<()> {
+        let _ = timeout;
+        self.flush()
+    }
+
+    /// Create the tables this sink writes to, see `log_or_prepare`.
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        Ok(())
+    }
+
+    /// The entries this sink dropped since the last call although `log` accepted them, e.g.
+    /// because its queue was full. The layer counts them in `Stats::dropped` and drop markers.
+    fn take_dropped(&self) -> u64 {
+        0
+    }
+
+    /// Whether entries whose `log` failed are kept and written later, e.g. by a
+    /// [`BatchConnect`](crate::BatchConnect) without a fallback. The layer doesn't pass them to
+    /// the sink of `SubscriberBuilder::with_fallback` then, they would be written twice.
+    fn keeps_failed_entries(&self) -> bool {
+        false
+    }
+
+    /// `log`, but if it fails because the tables are missing, create them and try again.
+    /// Used by the layer until a write succeeds, see `SubscriberBuilder::with_auto_prepare`.
+    ///
+    /// Sinks that write to several others override this, so only the ones that failed are retried.
+    /// Sinks that hold entries back override it too, so the layer doesn't stop preparing before
+    /// an entry reached the database.
+    fn log_or_prepare(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        match self.log(entry.clone()) {
+            Err(e) if is_missing_table(&e) => {
+                warn_missing_table();
+                self.create_tables()?;
+                self.log(entry)
+            }
+            result => result,
+        }
+    }
 }
 
 impl Connect for Connection {
-    fn log(&self, entry: LogEntry<&str>) {
-        self.execute("INSERT INTO logs_v0 (time, level, module, file, line, message, structured) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)", 
-        (entry.time, entry.level.as_str(), entry.module, entry.file, entry.line, entry.message, serde_json::to_string(&entry.structured).unwrap())).unwrap();
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        insert_entry(self, LOGS_TABLE, &entry)?;
+        Ok(())
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        prepare_database(self)
+    }
+}
+
+/// Inserts `entry` into `table`, returning its rowid.
+pub(crate) fn insert_entry(
+    conn: &Connection,
+    table: &str,
+    entry: &LogEntry<&str>,
+) -> rusqlite::Result<i64> {
+    // the strings and the row are written together, also outside of a transaction
+    conn.prepare_cached("SAVEPOINT insert_entry")?.execute([])?;
+    match insert_row(conn, table, entry) {
+        Ok(rowid) => {
+            conn.prepare_cached("RELEASE insert_entry")?.execute([])?;
+            Ok(rowid)
+        }
+        Err(e) => {
+            // the savepoint is gone if the error rolled back the whole transaction
+            let _ = conn.execute_batch("ROLLBACK TO insert_entry; RELEASE insert_entry");
+            Err(e)
+        }
+    }
+}
+
+fn insert_row(conn: &Connection, table: &str, entry: &LogEntry<&str>) -> rusqlite::Result<i64> {
+    // `module` and `file` repeat a lot, they are stored once and referenced by id
+    conn.prepare_cached(&format!(
+        "INSERT OR IGNORE INTO {STRINGS_TABLE} (value)
+         SELECT value FROM (SELECT ?1 AS value UNION ALL SELECT ?2) WHERE value IS NOT NULL"
+    ))?
+    .execute((entry.module, entry.file))?;
+    let structured = if supports_jsonb() && entry.structured.prefers_jsonb() {
+        // MessagePack and CBOR are written as they are
+        "CASE WHEN typeof(?7) = 'text' THEN jsonb(?7) ELSE ?7 END"
+    } else {
+        "?7"
+    };
+    conn.prepare_cached(&format!(
+        "INSERT INTO {table} (time, level, module_id, file_id, line, message, structured,
+             category, utc_offset, repeat_count, origin, expires_at, elapsed_us, event_id,
+             trace_id, span_id)
+         VALUES (?1, ?2, (SELECT id FROM {STRINGS_TABLE} WHERE value = ?3),
+             (SELECT id FROM {STRINGS_TABLE} WHERE value = ?4), ?5, ?6, {structured}, ?8, ?9,
+             ?10, ?11, ?12, ?13, ?14, ?15, ?16)"
+    ))?
+    .execute((
+        entry.time_precision.to_sql(entry.time),
+        entry.level.as_str(),
+        entry.module,
+        entry.file,
+        entry.line,
+        &entry.message,
+        entry.structured.to_sql(),
+        entry.category.map(|c| c.as_str()),
+        entry.utc_offset.map(|o| o.whole_minutes()),
+        entry.repeat_count,
+        entry.origin.map(|o| o.as_str()),
+        entry.expires_at,
+        entry.elapsed.map(|e| e.as_micros() as i64),
+        entry.event_id.map(|id| id.to_string()),
+        entry.trace_context.map(|c| c.trace_id_hex()),
+        entry.trace_context.map(|c| c.span_id_hex()),
+    ))?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Whether the linked SQLite stores JSONB (3.45+), which `structured` is then written as.
+/// It is smaller and faster to query than JSON text, rows of older versions stay text.
+pub(crate) fn supports_jsonb() -> bool {
+    rusqlite::version_number() >= 3_045_000
+}
+
+/// Called by [`TableConnect`] with the connection, the rowid of the new row and the entry,
+/// see `TableConnect::with_insert_hook`.
+pub type InsertHook =
+    dyn Fn(&Connection, i64, &LogEntry<&str>) -> rusqlite::Result<()> + Send + Sync;
+
+/// A `Connect` writing to another table with the `logs_v0` schema, created with [`prepare_table`].
+#[derive(Clone)]
+pub struct TableConnect {
+    conn: Arc<Mutex<Connection>>,
+    table: &'static str,
+    hook: Option<Arc<InsertHook>>,
+}
+
+impl TableConnect {
+    pub fn new(conn: Arc<Mutex<Connection>>, table: &'static str) -> Self {
+        Self {
+            conn,
+            table,
+            hook: None,
+        }
+    }
+
+    /// Run `hook` after every insert, in the same transaction, e.g. to maintain a full text
+    /// index or rollups in side tables. If the hook fails the entry is not written either.
+    pub fn with_insert_hook(
+        self,
+        hook: impl Fn(&Connection, i64, &LogEntry<&str>) -> rusqlite::Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            hook: Some(Arc::new(hook)),
+            ..self
+        }
+    }
+
+    /// Same as `new`, creating the table if needed.
+    pub fn prepared(conn: Arc<Mutex<Connection>>, table: &'static str) -> rusqlite::Result<Self> {
+        prepare_table(&conn.lock().unwrap(), table)?;
+        Ok(Self::new(conn, table))
+    }
+}
+
+impl Connect for TableConnect {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let Some(hook) = &self.hook else {
+            insert_entry(&conn, self.table, &entry)?;
+            return Ok(());
+        };
+
+        let tx = conn.unchecked_transaction()?;
+        let rowid = insert_entry(&tx, self.table, &entry)?;
+        hook(&tx, rowid, &entry)?;
+        tx.commit()
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        prepare_table(&self.conn.lock().unwrap(), self.table)
+    }
+}
+
+impl std::fmt::Debug for TableConnect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TableConnect")
+            .field("table", &self.table)
+            .field("hook", &self.hook.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A `Connect` sending entries at `level` or more severe to `severe` and the rest to `rest`,
+/// e.g. errors and warnings to [`ERRORS_TABLE`] or to a second database file,
+/// so they can be kept longer than debug output.
+#[derive(Debug, Clone)]
+pub struct Route<A, B> {
+    pub level: Level,
+    pub severe: A,
+    pub rest: B,
+}
+
+impl<A, B> Route<A, B> {
+    pub fn new(level: Level, severe: A, rest: B) -> Self {
+        Self {
+            level,
+            severe,
+            rest,
+        }
+    }
+}
+
+impl<A: Connect, B: Connect> Connect for Route<A, B> {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        if entry.level <= self.level {
+            self.severe.log(entry)
+        } else {
+            self.rest.log(entry)
+        }
+    }
+
+    fn flush(&self) -> rusqlite::Result<()> {
+        let severe = self.severe.flush();
+        let rest = self.rest.flush();
+        severe.and(rest)
+    }
+
+    fn flush_and_wait(&self, timeout: Duration) -> rusqlite::Result<()> {
+        let deadline = Instant::now() + timeout;
+        let severe = self.severe.flush_and_wait(timeout);
+        let rest = self
+            .rest
+            .flush_and_wait(deadline.saturating_duration_since(Instant::now()));
+        severe.and(rest)
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        let severe = self.severe.create_tables();
+        let rest = self.rest.create_tables();
+        severe.and(rest)
+    }
+
+    fn log_or_prepare(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        if entry.level <= self.level {
+            self.severe.log_or_prepare(entry)
+        } else {
+            self.rest.log_or_prepare(entry)
+        }
+    }
+
+    fn take_dropped(&self) -> u64 {
+        self.severe.take_dropped() + self.rest.take_dropped()
+    }
+
+    fn keeps_failed_entries(&self) -> bool {
+        self.severe.keeps_failed_entries() && self.rest.keeps_failed_entries()
     }
 }
 
 impl Connect for Mutex<Connection> {
-    fn log(&self, entry: LogEntry<&str>) {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
         let conn = self.lock().unwrap();
-        conn.log(entry);
+        conn.log(entry)
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        self.lock().unwrap().create_tables()
     }
 }
 
 impl Connect for Arc<Mutex<Connection>> {
-    fn log(&self, entry: LogEntry<&str>) {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
         self.as_ref().log(entry)
     }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        self.as_ref().create_tables()
+    }
 }
 
 impl Connect for LogHandle {
-    fn log(&self, entry: LogEntry<&str>) {
-        self.0.log(entry)
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self.conn.log(entry)
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        self.conn.create_tables()
+    }
+}
+
+/// A `Connect` that writes every entry to both `A` and `B`.
+///
+/// Tuples `(A, B)` behave the same way, `Tee` is only a named alternative.
+/// `B` is written even if `A` fails, the first error is returned.
+#[derive(Debug, Clone)]
+pub struct Tee<A, B>(pub A, pub B);
+
+impl<A: Connect, B: Connect> Tee<A, B> {
+    // on borrowed halves, so `(A, B)` can share them without being moved into a `Tee`
+    fn log_both(a: &A, b: &B, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        let first = a.log(entry.clone());
+        let second = b.log(entry);
+        first.and(second)
+    }
+
+    fn flush_both(a: &A, b: &B) -> rusqlite::Result<()> {
+        let first = a.flush();
+        let second = b.flush();
+        first.and(second)
+    }
+
+    fn flush_and_wait_both(a: &A, b: &B, timeout: Duration) -> rusqlite::Result<()> {
+        let deadline = Instant::now() + timeout;
+        let first = a.flush_and_wait(timeout);
+        let second = b.flush_and_wait(deadline.saturating_duration_since(Instant::now()));
+        first.and(second)
+    }
+
+    fn create_tables_both(a: &A, b: &B) -> rusqlite::Result<()> {
+        let first = a.create_tables();
+        let second = b.create_tables();
+        first.and(second)
+    }
+
+    fn log_or_prepare_both(a: &A, b: &B, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        let first = a.log_or_prepare(entry.clone());
+        let second = b.log_or_prepare(entry);
+        first.and(second)
+    }
+}
+
+impl<A: Connect, B: Connect> Connect for Tee<A, B> {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        Self::log_both(&self.0, &self.1, entry)
+    }
+
+    fn flush(&self) -> rusqlite::Result<()> {
+        Self::flush_both(&self.0, &self.1)
+    }
+
+    fn flush_and_wait(&self, timeout: Duration) -> rusqlite::Result<()> {
+        Self::flush_and_wait_both(&self.0, &self.1, timeout)
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        Self::create_tables_both(&self.0, &self.1)
+    }
+
+    fn log_or_prepare(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        Self::log_or_prepare_both(&self.0, &self.1, entry)
+    }
+
+    fn take_dropped(&self) -> u64 {
+        self.0.take_dropped() + self.1.take_dropped()
+    }
+
+    fn keeps_failed_entries(&self) -> bool {
+        self.0.keeps_failed_entries() && self.1.keeps_failed_entries()
+    }
+}
+
+/// Same as [`Tee`].
+impl<A: Connect, B: Connect> Connect for (A, B) {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        Tee::log_both(&self.0, &self.1, entry)
+    }
+
+    fn flush(&self) -> rusqlite::Result<()> {
+        Tee::flush_both(&self.0, &self.1)
+    }
+
+    fn flush_and_wait(&self, timeout: Duration) -> rusqlite::Result<()> {
+        Tee::flush_and_wait_both(&self.0, &self.1, timeout)
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        Tee::create_tables_both(&self.0, &self.1)
+    }
+
+    fn log_or_prepare(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        Tee::log_or_prepare_both(&self.0, &self.1, entry)
+    }
+
+    fn take_dropped(&self) -> u64 {
+        self.0.take_dropped() + self.1.take_dropped()
+    }
+
+    fn keeps_failed_entries(&self) -> bool {
+        self.0.keeps_failed_entries() && self.1.keeps_failed_entries()
+    }
+}
+
+/// Closures can be used as quick custom sinks, e.g. to forward entries to a channel.
+///
+/// ```
+/// # use tracing_subscriber_sqlite::{LogEntry, SubscriberBuilder};
+/// let (sender, receiver) = std::sync::mpsc::channel();
+/// let subscriber = SubscriberBuilder::new().build(move |entry: LogEntry<&str>| {
+///     let _ = sender.send(entry.message);
+/// });
+///
+/// tracing::subscriber::with_default(subscriber, || tracing::info!("hello"));
+/// assert_eq!(receiver.recv().unwrap(), "hello");
+/// ```
+impl<F> Connect for F
+where
+    F: Fn(LogEntry<&str>) + Send + Sync,
+{
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self(entry);
+        Ok(())
+    }
+}
+
+/// A `Connect` that discards every entry, for when persistence is disabled by configuration.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullConnect;
+
+impl Connect for NullConnect {
+    fn log(&self, _entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        Ok(())
+    }
+}
+
+/// `None` discards entries like [`NullConnect`].
+impl<C: Connect> Connect for Option<C> {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        match self {
+            Some(conn) => conn.log(entry),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&self) -> rusqlite::Result<()> {
+        match self {
+            Some(conn) => conn.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn flush_and_wait(&self, timeout: Duration) -> rusqlite::Result<()> {
+        match self {
+            Some(conn) => conn.flush_and_wait(timeout),
+            None => Ok(()),
+        }
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        match self {
+            Some(conn) => conn.create_tables(),
+            None => Ok(()),
+        }
+    }
+
+    fn log_or_prepare(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        match self {
+            Some(conn) => conn.log_or_prepare(entry),
+            None => Ok(()),
+        }
+    }
+
+    fn take_dropped(&self) -> u64 {
+        self.as_ref().map_or(0, Connect::take_dropped)
+    }
+
+    fn keeps_failed_entries(&self) -> bool {
+        self.as_ref().is_some_and(Connect::keeps_failed_entries)
     }
 }
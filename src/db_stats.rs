@@ -0,0 +1,103 @@
+use time::OffsetDateTime;
+
+use crate::{
+    db::interned,
+    export::io_error,
+    timestamp::{StoredTime, NANOS_FROM},
+    LogHandle, LogQuery, LogStore, StoreStats,
+};
+
+/// Size and contents of a log database, see `LogHandle::database_stats`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DatabaseStats {
+    /// Rows in `logs_v0`.
+    pub rows: u64,
+    pub earliest: Option<OffsetDateTime>,
+    pub latest: Option<OffsetDateTime>,
+    /// Bytes of the database file, or of the pages of an in-memory database.
+    pub file_size: u64,
+    /// Bytes of the WAL file, `None` if there is none, e.g. not in WAL mode.
+    pub wal_size: Option<u64>,
+    /// Rows per module (`None` for entries without one), most rows first.
+    pub modules: Vec<(Option<String>, u64)>,
+}
+
+impl LogHandle {
+    /// Numbers to monitor how the database grows. Counts the rows of every module, which
+    /// reads the whole table.
+    pub fn database_stats(&self) -> rusqlite::Result<DatabaseStats> {
+        let totals = self.stats()?;
+        self.stats_with(&LogQuery::new(), totals)
+    }
+
+    /// Same as `database_stats`, counting only the rows matching `query`, e.g. the part of the
+    /// database a `LogServer` serves. The file sizes are those of the whole database.
+    pub fn database_stats_matching(&self, query: &LogQuery) -> rusqlite::Result<DatabaseStats> {
+        let (where_clause, params) = self.scoped(query).where_clause();
+        let totals = self.with_reader(|conn| {
+            // text and the integer precisions only compare among themselves
+            let mut totals = StoreStats::default();
+            let mut stmt = conn.prepare(&format!(
+                "SELECT count(*), min(time), max(time) FROM {}{where_clause}
+                 GROUP BY typeof(time), abs(time) >= {NANOS_FROM}",
+                query.table_name()
+            ))?;
+            let mut rows = stmt.query(rusqlite::params_from_iter(params))?;
+            while let Some(row) = rows.next()? {
+                let earliest = row.get::<_, StoredTime>(1)?.0;
+                let latest = row.get::<_, StoredTime>(2)?.0;
+                totals.rows += row.get::<_, u64>(0)?;
+                totals.earliest = Some(totals.earliest.map_or(earliest, |t| t.min(earliest)));
+                totals.latest = Some(totals.latest.map_or(latest, |t| t.max(latest)));
+            }
+            Ok(totals)
+        })?;
+        self.stats_with(query, totals)
+    }
+
+    fn stats_with(&self, query: &LogQuery, totals: StoreStats) -> rusqlite::Result<DatabaseStats> {
+        let (where_clause, params) = self.scoped(query).where_clause();
+        let (pages, modules) = self.with_reader(|conn| {
+            let pages: u64 = conn.query_row(
+                "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+                [],
+                |row| row.get(0),
+            )?;
+            let modules = conn
+                .prepare(&format!(
+                    "SELECT {} AS name, count(*) AS rows FROM {}{where_clause}
+                     GROUP BY name ORDER BY rows DESC, name",
+                    interned("module", "main"),
+                    query.table_name(),
+                ))?
+                .query_map(rusqlite::params_from_iter(params), |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok((pages, modules))
+        })?;
+
+        let size = |path: &std::path::Path| match std::fs::metadata(path) {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error(e)),
+        };
+        let (file_size, wal_size) = match self.file_path() {
+            Some(path) => {
+                let mut wal = path.as_os_str().to_owned();
+                wal.push("-wal");
+                (size(path)?.unwrap_or(pages), size(wal.as_ref())?)
+            }
+            None => (pages, None),
+        };
+
+        Ok(DatabaseStats {
+            rows: totals.rows,
+            earliest: totals.earliest,
+            latest: totals.latest,
+            file_size,
+            wal_size,
+            modules,
+        })
+    }
+}
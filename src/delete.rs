@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use rusqlite::{Connection, OptionalExtension};
+use time::OffsetDateTime;
+
+use crate::{db::LOGS_TABLE, LogHandle, LogQuery};
+
+impl LogHandle {
+    /// Deletes every entry of `logs_v0`, e.g. for a "clear logs" button. Returns the number of
+    /// entries deleted, see `delete_matching`.
+    pub fn clear(&self) -> rusqlite::Result<u64> {
+        self.delete_matching(&LogQuery::new())
+    }
+
+    /// Deletes the entries older than `time`, see `delete_matching`.
+    pub fn delete_before(&self, time: OffsetDateTime) -> rusqlite::Result<u64> {
+        self.delete_matching(&LogQuery::new().until(time))
+    }
+
+    /// Deletes the entries matching `query` (`select`, `limit` and `offset` are ignored), e.g.
+    /// those of a user with `LogQuery::field`. Returns the number of entries deleted.
+    ///
+    /// If the newest row is deleted, a row saying how many entries were deleted takes its
+    /// rowid, so rowids are never reused and tails and shipping watermarks stay valid.
+    pub fn delete_matching(&self, query: &LogQuery) -> rusqlite::Result<u64> {
+        delete_matching(&self.writer(), &self.scoped(query))
+    }
+}
+
+/// Deletes the entries older than `max_age` except the newest, for
+/// `SubscriberBuilder::with_retention`.
+pub(crate) fn delete_older_than(conn: &Connection, max_age: Duration) -> rusqlite::Result<u64> {
+    let newest: Option<i64> =
+        conn.query_row(&format!("SELECT max(rowid) FROM {LOGS_TABLE}"), [], |row| {
+            row.get(0)
+        })?;
+    let Some(newest) = newest else {
+        return Ok(0);
+    };
+    let query = LogQuery::new()
+        .until(OffsetDateTime::now_utc() - max_age)
+        .max_rowid(newest - 1);
+    delete_matching(conn, &query)
+}
+
+/// `LogHandle::delete_matching` on `conn`.
+pub(crate) fn delete_matching(conn: &Connection, query: &LogQuery) -> rusqlite::Result<u64> {
+    let table = query.table_name();
+    let (where_clause, params) = query.where_clause();
+
+    let tx = conn.unchecked_transaction()?;
+    let newest: Option<i64> =
+        tx.query_row(&format!("SELECT max(rowid) FROM {table}"), [], |row| {
+            row.get(0)
+        })?;
+    let deleted = tx.execute(
+        &format!("DELETE FROM {table}{where_clause}"),
+        rusqlite::params_from_iter(params),
+    )?;
+
+    if let Some(rowid) = newest {
+        let kept = tx
+            .query_row(
+                &format!("SELECT 1 FROM {table} WHERE rowid = ?1"),
+                [rowid],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if !kept {
+            tx.execute(
+                &format!(
+                    "INSERT INTO {table} (rowid, time, level, message, structured, origin)
+                     VALUES (?1, ?2, 'INFO', ?3, ?4, 'tracing')"
+                ),
+                (
+                    rowid,
+                    OffsetDateTime::now_utc(),
+                    format!("{deleted} entries deleted"),
+                    serde_json::json!({ "deleted": deleted.to_string() }).to_string(),
+                ),
+            )?;
+        }
+    }
+    tx.commit()?;
+    Ok(deleted as u64)
+}
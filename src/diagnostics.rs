@@ -0,0 +1,98 @@
+use std::{
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Internal events of the layer, enabled by the `self-diagnostics` feature.
+///
+/// They are delivered to the sink set with `SubscriberBuilder::with_diagnostics` (stderr by default),
+/// never through `tracing`, so the sink must not emit `tracing` events itself.
+#[derive(Debug)]
+pub enum Diagnostic<'a> {
+    /// An entry could not be written, it is passed to the error handler afterwards.
+    WriteFailed(&'a rusqlite::Error),
+    /// Totals since the layer was built, reported every [`DIAGNOSTICS_INTERVAL`] entries.
+    Counters { written: u64, failed: u64 },
+    /// The database was corrupt and moved aside, see `SubscriberBuilder::with_corruption_recovery`.
+    CorruptionRecovered {
+        problems: &'a [String],
+        moved_to: &'a Path,
+    },
+}
+
+/// Number of entries between two [`Diagnostic::Counters`] reports.
+pub const DIAGNOSTICS_INTERVAL: u64 = 10_000;
+
+impl std::fmt::Display for Diagnostic<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnostic::WriteFailed(e) => write!(f, "failed to write log entry: {e}"),
+            Diagnostic::Counters { written, failed } => {
+                write!(f, "{written} entries written, {failed} failed")
+            }
+            Diagnostic::CorruptionRecovered { problems, moved_to } => write!(
+                f,
+                "the log database is corrupt ({}), moved it to {} and created a new one",
+                problems.join("; "),
+                moved_to.display()
+            ),
+        }
+    }
+}
+
+pub(crate) struct Diagnostics {
+    sink: Box<dyn Fn(Diagnostic<'_>) + Send + Sync>,
+    written: AtomicU64,
+    failed: AtomicU64,
+    seen: AtomicU64,
+}
+
+impl Diagnostics {
+    pub(crate) fn new(sink: impl Fn(Diagnostic<'_>) + Send + Sync + 'static) -> Self {
+        Self {
+            sink: Box::new(sink),
+            written: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            seen: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn report(&self, diagnostic: Diagnostic<'_>) {
+        (self.sink)(diagnostic)
+    }
+
+    pub(crate) fn record(&self, result: &rusqlite::Result<()>) {
+        match result {
+            Ok(()) => {
+                self.written.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                (self.sink)(Diagnostic::WriteFailed(e));
+            }
+        }
+
+        let seen = self.seen.fetch_add(1, Ordering::Relaxed) + 1;
+        if seen.is_multiple_of(DIAGNOSTICS_INTERVAL) {
+            (self.sink)(Diagnostic::Counters {
+                written: self.written.load(Ordering::Relaxed),
+                failed: self.failed.load(Ordering::Relaxed),
+            });
+        }
+    }
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self::new(|diagnostic| eprintln!("tracing-subscriber-sqlite: {diagnostic}"))
+    }
+}
+
+impl std::fmt::Debug for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Diagnostics")
+            .field("written", &self.written)
+            .field("failed", &self.failed)
+            .finish_non_exhaustive()
+    }
+}
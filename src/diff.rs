@@ -0,0 +1,133 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+};
+
+use crate::LogEntry;
+
+/// Differences between two sets of entries, see [`diff`].
+#[derive(Debug, Clone, Default)]
+pub struct Diff {
+    /// Entries only in `after`.
+    pub added: Vec<LogEntry>,
+    /// Entries only in `before`.
+    pub removed: Vec<LogEntry>,
+    /// Entries in both, with a different level, fields, category, repeat count, origin or expiry.
+    pub changed: Vec<Change>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub before: LogEntry,
+    pub after: LogEntry,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compares the entries an application logged before and after a change, e.g. snapshots
+/// collected with [`MemoryConnect`](crate::MemoryConnect) in a test.
+///
+/// Entries are matched by module and message, in order when the same event occurs several times.
+/// Time, UTC offset and source location are ignored, since they differ from run to run
+/// or with unrelated edits.
+pub fn diff(before: &[LogEntry], after: &[LogEntry]) -> Diff {
+    let mut unmatched: HashMap<(Option<&str>, &str), VecDeque<&LogEntry>> = HashMap::new();
+    for entry in before {
+        unmatched.entry(key(entry)).or_default().push_back(entry);
+    }
+
+    let mut diff = Diff::default();
+    for entry in after {
+        match unmatched.get_mut(&key(entry)).and_then(VecDeque::pop_front) {
+            Some(old) if same_event(old, entry) => {}
+            Some(old) => diff.changed.push(Change {
+                before: old.clone(),
+                after: entry.clone(),
+            }),
+            None => diff.added.push(entry.clone()),
+        }
+    }
+
+    // keep the order of `before`
+    diff.removed = before
+        .iter()
+        .rev()
+        .filter(|entry| {
+            let queue = unmatched.get_mut(&key(entry)).unwrap();
+            queue.pop_back().is_some()
+        })
+        .cloned()
+        .collect();
+    diff.removed.reverse();
+
+    diff
+}
+
+fn key(entry: &LogEntry) -> (Option<&str>, &str) {
+    (entry.module.as_deref(), &entry.message)
+}
+
+fn same_event(a: &LogEntry, b: &LogEntry) -> bool {
+    a.level == b.level
+        && a.category == b.category
+        && a.repeat_count == b.repeat_count
+        && a.origin == b.origin
+        // the time to live, the expiry itself moves with the run
+        && ttl(a) == ttl(b)
+        && a.structured.fields() == b.structured.fields()
+}
+
+/// The time to live in milliseconds, `time` may be stored with less precision than `expires_at`.
+fn ttl(entry: &LogEntry) -> Option<i128> {
+    entry
+        .expires_at
+        .map(|expires_at| (expires_at - entry.time).whole_milliseconds())
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.removed {
+            writeln!(f, "- {}", Event(entry))?;
+        }
+        for entry in &self.added {
+            writeln!(f, "+ {}", Event(entry))?;
+        }
+        for change in &self.changed {
+            writeln!(f, "~ {}", Event(&change.before))?;
+            writeln!(f, "  {}", Event(&change.after))?;
+        }
+        Ok(())
+    }
+}
+
+struct Event<'a>(&'a LogEntry);
+
+impl fmt::Display for Event<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entry = self.0;
+        write!(
+            f,
+            "{} {}: {}",
+            entry.level,
+            entry.module.as_deref().unwrap_or("?"),
+            entry.message
+        )?;
+
+        let mut fields: Vec<_> = entry.structured.fields().iter().collect();
+        fields.sort();
+        for (key, value) in fields {
+            write!(f, " {key}={value}")?;
+        }
+        if let Some(category) = entry.category {
+            write!(f, " category={category}")?;
+        }
+        if entry.repeat_count > 1 {
+            write!(f, " (x{})", entry.repeat_count)?;
+        }
+        Ok(())
+    }
+}
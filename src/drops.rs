@@ -0,0 +1,65 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Counts events the layer dropped on purpose (e.g. by field filters), so they can be
+/// persisted as marker rows once per interval and analyses know the data is incomplete.
+#[derive(Debug)]
+pub(crate) struct DropMarkers {
+    interval: Duration,
+    state: Mutex<DropState>,
+}
+
+#[derive(Debug)]
+struct DropState {
+    since: Instant,
+    counts: HashMap<(Option<String>, &'static str), u64>,
+}
+
+/// Dropped events of one module for one reason.
+pub(crate) struct DropCount {
+    pub(crate) module: Option<String>,
+    pub(crate) reason: &'static str,
+    pub(crate) count: u64,
+}
+
+impl DropMarkers {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            state: Mutex::new(DropState {
+                since: Instant::now(),
+                counts: HashMap::new(),
+            }),
+        }
+    }
+
+    pub(crate) fn record(&self, module: Option<&str>, reason: &'static str, count: u64) {
+        let mut state = self.state.lock().unwrap();
+        *state
+            .counts
+            .entry((module.map(str::to_owned), reason))
+            .or_default() += count;
+    }
+
+    /// Takes the counts of the last interval once it has elapsed.
+    pub(crate) fn take_due(&self) -> Vec<DropCount> {
+        let mut state = self.state.lock().unwrap();
+        if state.counts.is_empty() || state.since.elapsed() < self.interval {
+            return Vec::new();
+        }
+
+        state.since = Instant::now();
+        state
+            .counts
+            .drain()
+            .map(|((module, reason), count)| DropCount {
+                module,
+                reason,
+                count,
+            })
+            .collect()
+    }
+}
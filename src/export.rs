@@ -0,0 +1,184 @@
+use std::io::{self, BufWriter, Write};
+
+use time::format_description::well_known::Rfc3339;
+
+use crate::{db::PROGRESS_ROWS, Column, ExportProfile, LogEntry, LogHandle, LogQuery, Progress};
+
+impl LogHandle {
+    /// Writes the entries matching `query` to `writer` as JSON lines, in the format of
+    /// [`JsonlConnect`](crate::JsonlConnect). Returns the number of entries written.
+    pub fn export_jsonl(&self, writer: impl Write, query: &LogQuery) -> rusqlite::Result<u64> {
+        self.export_jsonl_as(writer, query, ExportProfile::Native)
+    }
+
+    /// Same as `export_jsonl`, with the field names of `profile`, e.g. for Elasticsearch or an
+    /// OpenTelemetry collector.
+    pub fn export_jsonl_as(
+        &self,
+        writer: impl Write,
+        query: &LogQuery,
+        profile: ExportProfile,
+    ) -> rusqlite::Result<u64> {
+        self.write_jsonl(writer, query, profile, None)
+    }
+
+    /// Same as `export_jsonl`, reporting the written entries to `progress`.
+    pub fn export_jsonl_with_progress(
+        &self,
+        writer: impl Write,
+        query: &LogQuery,
+        progress: impl Fn(Progress),
+    ) -> rusqlite::Result<u64> {
+        self.write_jsonl(writer, query, ExportProfile::Native, Some(&progress))
+    }
+
+    fn write_jsonl(
+        &self,
+        writer: impl Write,
+        query: &LogQuery,
+        profile: ExportProfile,
+        progress: Option<&dyn Fn(Progress)>,
+    ) -> rusqlite::Result<u64> {
+        let mut writer = BufWriter::new(writer);
+        let mut report = self.reporter(query, progress)?;
+        let count = self.for_each_entry(query, |entry| {
+            serde_json::to_writer(&mut writer, &profile.json(&entry))
+                .map_err(io::Error::from)
+                .and_then(|()| writer.write_all(b"\n"))
+                .map_err(io_error)?;
+            report(false);
+            Ok(())
+        })?;
+        writer.flush().map_err(io_error)?;
+        report(true);
+        Ok(count)
+    }
+
+    /// Writes the entries matching `query` to `writer` as CSV with a header row, one column
+    /// per [`Column`] (structured fields as JSON, times as RFC 3339). Columns that are not
+    /// selected by the query are left empty. Returns the number of entries written.
+    pub fn export_csv(&self, writer: impl Write, query: &LogQuery) -> rusqlite::Result<u64> {
+        self.write_csv(writer, query, None)
+    }
+
+    /// Same as `export_csv`, reporting the written entries to `progress`.
+    pub fn export_csv_with_progress(
+        &self,
+        writer: impl Write,
+        query: &LogQuery,
+        progress: impl Fn(Progress),
+    ) -> rusqlite::Result<u64> {
+        self.write_csv(writer, query, Some(&progress))
+    }
+
+    fn write_csv(
+        &self,
+        writer: impl Write,
+        query: &LogQuery,
+        progress: Option<&dyn Fn(Progress)>,
+    ) -> rusqlite::Result<u64> {
+        let mut writer = BufWriter::new(writer);
+        let header = Column::ALL.map(|c| c.name().to_owned());
+        write_record(&mut writer, &header).map_err(io_error)?;
+
+        let mut report = self.reporter(query, progress)?;
+        let count = self.for_each_entry(query, |entry| {
+            write_record(&mut writer, &csv_record(&entry)).map_err(io_error)?;
+            report(false);
+            Ok(())
+        })?;
+        writer.flush().map_err(io_error)?;
+        report(true);
+        Ok(count)
+    }
+
+    /// Counts the entries of an export and reports them to `progress` every [`PROGRESS_ROWS`]
+    /// and when called with `true` at the end. Counting is skipped without `progress`.
+    fn reporter<'a>(
+        &self,
+        query: &LogQuery,
+        progress: Option<&'a dyn Fn(Progress)>,
+    ) -> rusqlite::Result<impl FnMut(bool) + 'a> {
+        let total = match progress {
+            Some(progress) => {
+                let total = self.count_rows(query)?;
+                progress(Progress::new(0, Some(total)));
+                Some(total)
+            }
+            None => None,
+        };
+        let mut done = 0;
+        Ok(move |finished: bool| {
+            let Some(progress) = progress else { return };
+            if !finished {
+                done += 1;
+            }
+            // the last report doesn't repeat the one at a multiple of `PROGRESS_ROWS`
+            if finished != (done % PROGRESS_ROWS == 0) {
+                progress(Progress::new(done, total));
+            }
+        })
+    }
+}
+
+fn csv_record(entry: &LogEntry) -> [String; Column::ALL.len()] {
+    Column::ALL.map(|column| match column {
+        Column::Time => entry.time.format(&Rfc3339).unwrap_or_default(),
+        Column::Level => entry.level.as_str().to_owned(),
+        Column::Module => entry.module.clone().unwrap_or_default(),
+        Column::File => entry.file.clone().unwrap_or_default(),
+        Column::Line => entry.line.map(|l| l.to_string()).unwrap_or_default(),
+        Column::Message => entry.message.clone(),
+        Column::Structured => entry.structured.to_json().into_owned(),
+        Column::Category => entry
+            .category
+            .map(|c| c.as_str().to_owned())
+            .unwrap_or_default(),
+        Column::UtcOffset => entry
+            .utc_offset
+            .map(|o| o.whole_minutes().to_string())
+            .unwrap_or_default(),
+        Column::RepeatCount => entry.repeat_count.to_string(),
+        Column::Origin => entry
+            .origin
+            .map(|o| o.as_str().to_owned())
+            .unwrap_or_default(),
+        Column::ExpiresAt => entry
+            .expires_at
+            .and_then(|t| t.format(&Rfc3339).ok())
+            .unwrap_or_default(),
+        Column::Elapsed => entry
+            .elapsed
+            .map(|e| e.as_micros().to_string())
+            .unwrap_or_default(),
+        Column::EventId => entry.event_id.map(|id| id.to_string()).unwrap_or_default(),
+        Column::TraceId => entry
+            .trace_context
+            .map(|c| c.trace_id_hex())
+            .unwrap_or_default(),
+        Column::SpanId => entry
+            .trace_context
+            .map(|c| c.span_id_hex())
+            .unwrap_or_default(),
+    })
+}
+
+/// One RFC 4180 record, fields with commas, quotes or line breaks are quoted.
+fn write_record(writer: &mut impl Write, fields: &[String]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            write!(writer, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            writer.write_all(field.as_bytes())?;
+        }
+    }
+    writer.write_all(b"\r\n")
+}
+
+pub(crate) fn io_error(e: io::Error) -> rusqlite::Error {
+    // `LogHandle` reports rusqlite errors, this is the variant that carries arbitrary ones
+    rusqlite::Error::ToSqlConversionFailure(Box::new(e))
+}
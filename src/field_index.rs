@@ -0,0 +1,65 @@
+use rusqlite::Connection;
+
+use crate::{query::field_expression, LogHandle, LOGS_TABLE};
+
+/// Indexes on structured fields are named with this prefix followed by the field name.
+const FIELD_INDEX_PREFIX: &str = "logs_v0_field_";
+
+impl LogHandle {
+    /// Indexes the structured field `name`, so `LogQuery::field` conditions on it don't scan the
+    /// whole table, e.g. for a request id that is searched often. Building the index reads every
+    /// row once, afterwards each write updates it.
+    pub fn index_field(&self, name: &str) -> rusqlite::Result<()> {
+        self.writer().execute_batch(&format!(
+            "CREATE INDEX IF NOT EXISTS {} ON {LOGS_TABLE} ({})",
+            index_name(name),
+            field_expression(name),
+        ))
+    }
+
+    /// Removes the index `index_field` created for `name`, if any.
+    pub fn drop_field_index(&self, name: &str) -> rusqlite::Result<()> {
+        self.writer()
+            .execute_batch(&format!("DROP INDEX IF EXISTS {}", index_name(name)))
+    }
+
+    /// The structured fields with an index, by name.
+    pub fn indexed_fields(&self) -> rusqlite::Result<Vec<String>> {
+        self.with_reader(|conn| {
+            conn.prepare(
+                "SELECT substr(name, length(?1) + 1) FROM sqlite_master
+                 WHERE type = 'index' AND tbl_name = ?2 AND substr(name, 1, length(?1)) = ?1
+                 ORDER BY name",
+            )?
+            .query_map([FIELD_INDEX_PREFIX, LOGS_TABLE], |row| row.get(0))?
+            .collect()
+        })
+    }
+}
+
+/// Recreates the field indexes of versions that extracted fields with `json_extract`, queries
+/// don't use them since fields are extracted with `->>`. Called by `prepare_database`.
+pub(crate) fn migrate_field_indexes(conn: &Connection) -> rusqlite::Result<()> {
+    let outdated = conn
+        .prepare(
+            "SELECT substr(name, length(?1) + 1) FROM sqlite_master
+             WHERE type = 'index' AND tbl_name = ?2 AND substr(name, 1, length(?1)) = ?1
+             AND sql LIKE '%json_extract(%'",
+        )?
+        .query_map([FIELD_INDEX_PREFIX, LOGS_TABLE], |row| {
+            row.get::<_, String>(0)
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for field in outdated {
+        conn.execute_batch(&format!(
+            "DROP INDEX {name}; CREATE INDEX {name} ON {LOGS_TABLE} ({})",
+            field_expression(&field),
+            name = index_name(&field),
+        ))?;
+    }
+    Ok(())
+}
+
+fn index_name(field: &str) -> String {
+    format!("\"{FIELD_INDEX_PREFIX}{}\"", field.replace('"', "\"\""))
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+/// How the `structured` column is encoded, see `SubscriberBuilder::with_structured_format`.
+///
+/// Entries are read back from any of them. MessagePack and CBOR are smaller than JSON text
+/// (about as small as JSONB) and can be decoded without SQLite, but SQLite can't look into
+/// them: `LogQuery::field`, field indexes and promoted fields ignore rows stored that way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum StructuredFormat {
+    /// JSON text, or JSONB if SQLite supports it.
+    #[default]
+    Json,
+    /// JSON text even if SQLite supports JSONB, e.g. for tools that read the column with an
+    /// older SQLite.
+    JsonText,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+/// `structured` if it is JSON text or JSONB, `NULL` for the binary formats.
+///
+/// Maps in the binary formats are written with headers whose first byte has a low nibble
+/// other than `C`, the one of a JSONB object.
+pub(crate) const STRUCTURED_JSON: &str = "CASE WHEN typeof(structured) != 'blob' \
+    OR hex(substr(structured, 1, 1)) LIKE '_C' THEN structured END";
+
+impl StructuredFormat {
+    /// `fields` in this format, `None` for JSON.
+    pub(crate) fn encode(self, fields: &HashMap<&str, String>) -> Option<Vec<u8>> {
+        let _ = fields;
+        match self {
+            StructuredFormat::Json | StructuredFormat::JsonText => None,
+            #[cfg(feature = "msgpack")]
+            StructuredFormat::MessagePack => Some(encode_map(fields, &MSGPACK)),
+            #[cfg(feature = "cbor")]
+            StructuredFormat::Cbor => Some(encode_map(fields, &CBOR)),
+        }
+    }
+}
+
+/// The headers of a string map in MessagePack or CBOR, by size.
+struct Headers {
+    map16: u8,
+    map32: u8,
+    /// The base of strings with their length in the first byte, and the largest such length.
+    short_str: (u8, usize),
+    str8: u8,
+    str16: u8,
+    str32: u8,
+}
+
+const MSGPACK: Headers = Headers {
+    map16: 0xde,
+    map32: 0xdf,
+    short_str: (0xa0, 31),
+    str8: 0xd9,
+    str16: 0xda,
+    str32: 0xdb,
+};
+
+const CBOR: Headers = Headers {
+    map16: 0xb9,
+    map32: 0xba,
+    short_str: (0x60, 23),
+    str8: 0x78,
+    str16: 0x79,
+    str32: 0x7a,
+};
+
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+fn encode_map(fields: &HashMap<&str, String>, headers: &Headers) -> Vec<u8> {
+    fn header(out: &mut Vec<u8>, len: usize, [h8, h16, h32]: [u8; 3]) {
+        if let Ok(len) = u8::try_from(len) {
+            out.extend([h8, len]);
+        } else if let Ok(len) = u16::try_from(len) {
+            out.push(h16);
+            out.extend(len.to_be_bytes());
+        } else {
+            out.push(h32);
+            out.extend((len as u32).to_be_bytes());
+        }
+    }
+    let string = |out: &mut Vec<u8>, s: &str| {
+        let (base, max) = headers.short_str;
+        if s.len() <= max {
+            out.push(base + s.len() as u8);
+        } else {
+            header(out, s.len(), [headers.str8, headers.str16, headers.str32]);
+        }
+        out.extend(s.as_bytes());
+    };
+
+    let mut out = Vec::new();
+    match u16::try_from(fields.len()) {
+        Ok(len) => {
+            out.push(headers.map16);
+            out.extend(len.to_be_bytes());
+        }
+        Err(_) => {
+            out.push(headers.map32);
+            out.extend((fields.len() as u32).to_be_bytes());
+        }
+    }
+    for (name, value) in fields {
+        string(&mut out, name);
+        string(&mut out, value);
+    }
+    out
+}
+
+/// Decodes a map written by `StructuredFormat::encode`, `None` if `bytes` is not one.
+pub(crate) fn decode(bytes: &[u8]) -> Option<HashMap<String, String>> {
+    let first = *bytes.first()?;
+    let headers = [&MSGPACK, &CBOR]
+        .into_iter()
+        .find(|h| first == h.map16 || first == h.map32)?;
+    let mut rest = bytes;
+    let mut take = |n: usize| {
+        let (head, tail) = rest.split_at_checked(n)?;
+        rest = tail;
+        Some(head)
+    };
+    let uint = |bytes: &[u8]| bytes.iter().fold(0usize, |n, &b| n << 8 | b as usize);
+
+    let len = if take(1)?[0] == headers.map16 {
+        uint(take(2)?)
+    } else {
+        uint(take(4)?)
+    };
+    let mut fields = HashMap::with_capacity(len.min(1024));
+    for _ in 0..len {
+        let mut string = || {
+            let (base, max) = headers.short_str;
+            let len = match take(1)?[0] {
+                h if (base..=base + max as u8).contains(&h) => (h - base) as usize,
+                h if h == headers.str8 => uint(take(1)?),
+                h if h == headers.str16 => uint(take(2)?),
+                h if h == headers.str32 => uint(take(4)?),
+                _ => return None,
+            };
+            String::from_utf8(take(len)?.to_vec()).ok()
+        };
+        let name = string()?;
+        let value = string()?;
+        fields.insert(name, value);
+    }
+    Some(fields)
+}
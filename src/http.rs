@@ -0,0 +1,239 @@
+use std::{convert::Infallible, net::TcpListener, sync::Arc};
+
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server,
+};
+use serde_json::{json, Value};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{parse_time, ExportProfile, FieldMatch, LogHandle, LogQuery, Order};
+
+/// A small JSON API over a `LogHandle`, so a service can expose its own recent logs for
+/// debugging without access to the database file.
+///
+/// - `GET /logs` answers `{"entries": [...]}`, newest first, each entry as in
+///   [`JsonlConnect`](crate::JsonlConnect) files. Parameters: `level` (e.g. `warn`, that level
+///   or more severe), `module` (a prefix), `since` and `until` (RFC 3339 or an age like `30s`,
+///   `15m`, `12h` or `7d`), `contains` (message text), `field` (`name=value`, may repeat),
+///   `trace_id` (hex), `order` (`newest` or `oldest`) and `limit` (100 by default).
+/// - `GET /stats` answers the [`DatabaseStats`](crate::DatabaseStats) of the served entries,
+///   taking the same parameters.
+///
+/// Parameters narrow the query of [`LogServer::with_query`]: the stricter level and the later
+/// `since` and earlier `until` win. A `module`, `contains` or `trace_id` outside of the
+/// served entries is answered with 400.
+///
+/// Errors are answered as `{"error": "..."}`. There is no authentication, bind it to a
+/// loopback or otherwise private address.
+#[derive(Debug, Clone)]
+pub struct LogServer {
+    handle: LogHandle,
+    query: LogQuery,
+    max_limit: u64,
+}
+
+impl LogServer {
+    pub fn new(handle: LogHandle) -> Self {
+        Self {
+            handle,
+            query: LogQuery::new(),
+            max_limit: 1000,
+        }
+    }
+
+    /// Filters all answered entries and stats, e.g. to serve only the modules of one service.
+    pub fn with_query(self, query: LogQuery) -> Self {
+        Self { query, ..self }
+    }
+
+    /// The most entries one request can ask for, 1000 by default.
+    pub fn with_max_limit(self, max_limit: u64) -> Self {
+        Self { max_limit, ..self }
+    }
+
+    /// Answers requests on `listener` until the future is dropped, e.g. a listener bound to
+    /// `127.0.0.1:9090`. Needs a tokio runtime with IO enabled; queries run on its blocking pool.
+    pub async fn serve(self, listener: TcpListener) -> rusqlite::Result<()> {
+        let server = Arc::new(self);
+        let make_service = make_service_fn(move |_| {
+            let server = server.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.answer(request).await) }
+                }))
+            }
+        });
+        Server::from_tcp(listener)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?
+            .serve(make_service)
+            .await
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+    }
+
+    /// The status code and body answering `method` on `target`, a path with an optional query
+    /// string, to mount the API in a server of another framework. Blocks for as long as the
+    /// query takes.
+    pub fn respond(&self, method: &str, target: &str) -> (u16, Value) {
+        let (path, params) = target.split_once('?').unwrap_or((target, ""));
+        let result = match (method, path) {
+            ("GET", "/logs") => self.logs(params),
+            ("GET", "/stats") => self.stats(params),
+            (_, "/logs" | "/stats") => Err((405, format!("{method} is not allowed"))),
+            _ => Err((404, format!("no such endpoint {path:?}"))),
+        };
+        result.unwrap_or_else(|(status, error)| (status, json!({ "error": error })))
+    }
+
+    async fn answer(self: Arc<Self>, request: Request<Body>) -> Response<Body> {
+        let method = request.method().clone();
+        let target = request.uri().path_and_query().map_or_else(
+            || request.uri().path().to_owned(),
+            |p| p.as_str().to_owned(),
+        );
+        let (status, body) =
+            tokio::task::spawn_blocking(move || self.respond(method.as_str(), &target))
+                .await
+                .unwrap_or_else(|e| (500, json!({ "error": e.to_string() })));
+        Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn logs(&self, params: &str) -> Result<(u16, Value), (u16, String)> {
+        let query = self.parse_query(params).map_err(|e| (400, e))?;
+        let entries = self
+            .handle
+            .query(&query)
+            .map_err(|e| (500, e.to_string()))?;
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|entry| ExportProfile::Native.json(entry))
+            .collect();
+        Ok((200, json!({ "entries": entries })))
+    }
+
+    fn parse_query(&self, params: &str) -> Result<LogQuery, String> {
+        let mut query = self.query.clone().order(Order::NewestFirst);
+        let mut limit = 100;
+        for param in params.split('&').filter(|p| !p.is_empty()) {
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            let value = percent_decode(value)?;
+            query = match name {
+                "level" => {
+                    let level = value
+                        .parse()
+                        .map_err(|_| format!("unknown level {value:?}"))?;
+                    let stricter = query
+                        .level_filter()
+                        .map_or(level, |served| served.min(level));
+                    query.max_level(stricter)
+                }
+                "module" => match query.module_filter() {
+                    Some(served) if !value.starts_with(served) => {
+                        return Err(format!("module {value:?} is outside of {served:?}"))
+                    }
+                    _ => query.module(value),
+                },
+                "since" => {
+                    let since = parse_time(&value)?;
+                    match query.time_filter().0 {
+                        Some(served) if served > since => query,
+                        _ => query.since(since),
+                    }
+                }
+                "until" => {
+                    let until = parse_time(&value)?;
+                    match query.time_filter().1 {
+                        Some(served) if served < until => query,
+                        _ => query.until(until),
+                    }
+                }
+                "contains" => match query.message_filter() {
+                    Some(served) if !value.contains(served) => {
+                        return Err(format!("contains {value:?} is outside of {served:?}"))
+                    }
+                    _ => query.message_contains(value),
+                },
+                "field" => query.field_match(value.parse::<FieldMatch>()?),
+                "trace_id" => {
+                    let trace_id = u128::from_str_radix(&value, 16)
+                        .map_err(|_| format!("invalid trace_id {value:?}"))?;
+                    match query.trace_id_filter() {
+                        Some(served) if served != trace_id => {
+                            return Err(format!("trace_id {value:?} is outside of {served:032x}"))
+                        }
+                        _ => query.trace_id(trace_id),
+                    }
+                }
+                "order" => match value.as_str() {
+                    "newest" => query.order(Order::NewestFirst),
+                    "oldest" => query.order(Order::OldestFirst),
+                    _ => {
+                        return Err(format!(
+                            "invalid order {value:?}, expected newest or oldest"
+                        ))
+                    }
+                },
+                "limit" => {
+                    limit = value
+                        .parse()
+                        .map_err(|_| format!("invalid limit {value:?}"))?;
+                    query
+                }
+                _ => return Err(format!("unknown parameter {name:?}")),
+            };
+        }
+        Ok(query.limit(limit.min(self.max_limit)))
+    }
+
+    fn stats(&self, params: &str) -> Result<(u16, Value), (u16, String)> {
+        let query = self.parse_query(params).map_err(|e| (400, e))?;
+        let stats = self
+            .handle
+            .database_stats_matching(&query)
+            .map_err(|e| (500, e.to_string()))?;
+        let time = |time: Option<OffsetDateTime>| time.and_then(|t| t.format(&Rfc3339).ok());
+        let modules: Vec<_> = stats
+            .modules
+            .iter()
+            .map(|(module, rows)| json!({ "module": module, "rows": rows }))
+            .collect();
+        Ok((
+            200,
+            json!({
+                "rows": stats.rows,
+                "earliest": time(stats.earliest),
+                "latest": time(stats.latest),
+                "file_size": stats.file_size,
+                "wal_size": stats.wal_size,
+                "modules": modules,
+            }),
+        ))
+    }
+}
+
+/// Decodes a query string value, `+` is a space.
+fn percent_decode(text: &str) -> Result<String, String> {
+    let invalid = || format!("invalid percent-encoding in {text:?}");
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = rest.get(..2).ok_or_else(invalid)?;
+                let hex = std::str::from_utf8(hex).map_err(|_| invalid())?;
+                bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+                rest = &rest[2..];
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
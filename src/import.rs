@@ -0,0 +1,83 @@
+use std::io::BufRead;
+
+use crate::{
+    db::{insert_entry, PROGRESS_ROWS},
+    export::io_error,
+    jsonl::entry_from_json,
+    LogHandle, Progress, LOGS_TABLE,
+};
+
+/// The result of [`LogHandle::import_jsonl`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Entries written to `logs_v0`.
+    pub imported: u64,
+    /// Lines that could not be parsed, they were skipped.
+    pub errors: Vec<ImportError>,
+}
+
+/// A line [`LogHandle::import_jsonl`] skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportError {
+    /// 1-based line number.
+    pub line: u64,
+    pub message: String,
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl LogHandle {
+    /// Writes the entries of JSON lines in the format of [`JsonlConnect`](crate::JsonlConnect)
+    /// or `export_jsonl` to `logs_v0`, e.g. to consolidate logs from several machines.
+    /// Imported entries get [`Origin::Import`](crate::Origin::Import).
+    ///
+    /// Lines that can't be parsed are skipped and reported, blank lines are ignored.
+    /// Everything is written in one transaction, nothing is imported if reading fails.
+    pub fn import_jsonl(&self, reader: impl BufRead) -> rusqlite::Result<ImportReport> {
+        self.import_jsonl_with_progress(reader, |_| {})
+    }
+
+    /// Same as `import_jsonl`, reporting the lines read to `progress`. The total is unknown.
+    pub fn import_jsonl_with_progress(
+        &self,
+        reader: impl BufRead,
+        progress: impl Fn(Progress),
+    ) -> rusqlite::Result<ImportReport> {
+        let conn = self.writer();
+        let tx = conn.unchecked_transaction()?;
+        let mut report = ImportReport::default();
+
+        let mut lines = 0;
+        progress(Progress::new(0, None));
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.map_err(io_error)?;
+            lines = i as u64 + 1;
+            if lines % PROGRESS_ROWS == 0 {
+                progress(Progress::new(lines, None));
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            match entry_from_json(&line) {
+                Ok(entry) => {
+                    insert_entry(&tx, LOGS_TABLE, &entry.to_borrowed())?;
+                    report.imported += 1;
+                }
+                Err(message) => report.errors.push(ImportError {
+                    line: i as u64 + 1,
+                    message,
+                }),
+            }
+        }
+
+        tx.commit()?;
+        if lines % PROGRESS_ROWS != 0 {
+            progress(Progress::new(lines, None));
+        }
+        Ok(report)
+    }
+}
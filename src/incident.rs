@@ -0,0 +1,169 @@
+use std::{ops::Range, time::Duration};
+
+use rusqlite::OptionalExtension;
+use time::OffsetDateTime;
+
+use crate::{
+    db::{interned, is_missing_table},
+    query::time_value,
+    timestamp::StoredTime,
+    LogHandle, LOGS_TABLE,
+};
+
+pub const INCIDENTS_TABLE: &str = "incidents_v0";
+
+const INCIDENTS_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS incidents_v0 (
+    fingerprint TEXT NOT NULL,
+    module TEXT,
+    started TEXT NOT NULL,
+    ended TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    sample_message TEXT NOT NULL,
+    first_rowid INTEGER NOT NULL,
+    last_rowid INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS incidents_v0_fingerprint ON incidents_v0 (fingerprint, ended)";
+
+/// A run of `ERROR` entries with the same fingerprint, see `LogHandle::rollup_incidents`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Incident {
+    /// Hash of the module and the message with numbers left out, as 16 hex digits.
+    pub fingerprint: String,
+    pub module: Option<String>,
+    pub started: OffsetDateTime,
+    pub ended: OffsetDateTime,
+    /// Number of errors, including repeats collapsed by coalescing.
+    pub count: u64,
+    /// The message of the first error.
+    pub sample_message: String,
+}
+
+impl LogHandle {
+    /// Adds the `ERROR` entries written since the last call to [`INCIDENTS_TABLE`], returning
+    /// how many were added.
+    ///
+    /// Errors with the same fingerprint belong to one incident as long as each follows the
+    /// previous one within `window`, a later one starts a new incident. The fingerprint ignores
+    /// numbers in the message, so `timeout after 31ms` and `timeout after 30ms` are the same
+    /// error. Call it regularly, e.g. before showing a status dashboard.
+    pub fn rollup_incidents(&self, window: Duration) -> rusqlite::Result<u64> {
+        let conn = self.writer();
+        conn.execute_batch(INCIDENTS_SCHEMA)?;
+
+        let tx = conn.unchecked_transaction()?;
+        let last_rowid: i64 = tx.query_row(
+            &format!("SELECT coalesce(max(last_rowid), 0) FROM {INCIDENTS_TABLE}"),
+            [],
+            |row| row.get(0),
+        )?;
+        let added = {
+            let mut errors = tx.prepare(&format!(
+                "SELECT rowid, time, {}, message, repeat_count FROM {LOGS_TABLE}
+                 WHERE level = 'ERROR' AND rowid > ?1 ORDER BY rowid",
+                interned("module", "main"),
+            ))?;
+            let mut latest = tx.prepare(&format!(
+                "SELECT rowid, ended FROM {INCIDENTS_TABLE} WHERE fingerprint = ?1
+                 ORDER BY ended DESC LIMIT 1"
+            ))?;
+            let mut extend = tx.prepare(&format!(
+                "UPDATE {INCIDENTS_TABLE} SET ended = ?2, count = count + ?3, last_rowid = ?4
+                 WHERE rowid = ?1"
+            ))?;
+            let mut start = tx.prepare(&format!(
+                "INSERT INTO {INCIDENTS_TABLE}
+                 (fingerprint, module, started, ended, count, sample_message, first_rowid, last_rowid)
+                 VALUES (?1, ?2, ?3, ?3, ?4, ?5, ?6, ?6)"
+            ))?;
+
+            let mut rows = errors.query([last_rowid])?;
+            let mut added = 0;
+            while let Some(row) = rows.next()? {
+                let rowid: i64 = row.get(0)?;
+                let time = row.get::<_, StoredTime>(1)?.0;
+                let module: Option<String> = row.get(2)?;
+                let message: String = row.get(3)?;
+                let count: u64 = row.get(4)?;
+                let fingerprint = fingerprint(module.as_deref(), &message);
+
+                let open = latest
+                    .query_row([&fingerprint], |row| {
+                        Ok((row.get::<_, i64>(0)?, row.get::<_, OffsetDateTime>(1)?))
+                    })
+                    .optional()?
+                    .filter(|(_, ended)| time - *ended <= window);
+                match open {
+                    Some((incident, _)) => {
+                        extend.execute((incident, time_value(time), count, rowid))?;
+                    }
+                    None => {
+                        start.execute((
+                            &fingerprint,
+                            &module,
+                            time_value(time),
+                            count,
+                            &message,
+                            rowid,
+                        ))?;
+                    }
+                }
+                added += 1;
+            }
+            added
+        };
+        tx.commit()?;
+        Ok(added)
+    }
+
+    /// The incidents that overlap `range`, oldest first. Empty if `rollup_incidents` was
+    /// never called.
+    pub fn incidents(&self, range: Range<OffsetDateTime>) -> rusqlite::Result<Vec<Incident>> {
+        let result = self.with_reader(|conn| {
+            conn.prepare(&format!(
+                "SELECT fingerprint, module, started, ended, count, sample_message
+                 FROM {INCIDENTS_TABLE} WHERE started < ?2 AND ended >= ?1 ORDER BY started"
+            ))?
+            .query_map((time_value(range.start), time_value(range.end)), |row| {
+                Ok(Incident {
+                    fingerprint: row.get(0)?,
+                    module: row.get(1)?,
+                    started: row.get(2)?,
+                    ended: row.get(3)?,
+                    count: row.get(4)?,
+                    sample_message: row.get(5)?,
+                })
+            })?
+            .collect()
+        });
+        match result {
+            Err(e) if is_missing_table(&e) => Ok(Vec::new()),
+            result => result,
+        }
+    }
+}
+
+/// FNV-1a of the module and the message with runs of digits replaced by `#`, stable across
+/// builds so incidents continue after an upgrade.
+fn fingerprint(module: Option<&str>, message: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut add = |byte: u8| {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    };
+
+    module.unwrap_or_default().bytes().for_each(&mut add);
+    add(0);
+    let mut in_number = false;
+    for byte in message.bytes() {
+        if byte.is_ascii_digit() {
+            if !in_number {
+                add(b'#');
+            }
+            in_number = true;
+        } else {
+            add(byte);
+            in_number = false;
+        }
+    }
+    format!("{hash:016x}")
+}
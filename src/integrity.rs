@@ -0,0 +1,93 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{Connection, ErrorCode};
+use time::OffsetDateTime;
+
+use crate::{export::io_error, LogHandle};
+
+impl LogHandle {
+    /// Runs `PRAGMA integrity_check` and returns the problems it found, empty if the database
+    /// is intact. This reads the whole database and can take a while.
+    pub fn integrity_check(&self) -> rusqlite::Result<Vec<String>> {
+        self.with_reader(|conn| check(conn, "integrity_check"))
+    }
+}
+
+/// The problems `PRAGMA <pragma>` reports, databases SQLite refuses to read report their error.
+fn check(conn: &Connection, pragma: &str) -> rusqlite::Result<Vec<String>> {
+    let rows = conn
+        .prepare(&format!("PRAGMA {pragma}"))
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        });
+    match rows {
+        Ok(rows) if rows == ["ok"] => Ok(Vec::new()),
+        Ok(rows) => Ok(rows),
+        Err(rusqlite::Error::SqliteFailure(e, message))
+            if matches!(e.code, ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase) =>
+        {
+            Ok(vec![message.unwrap_or_else(|| e.to_string())])
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Runs `PRAGMA quick_check` on the database file of `conn` and, if it is corrupt, moves it
+/// (and its journal files) aside to `<path>.corrupt-<unix time>` and opens a new empty
+/// database in its place with `reopen`. Returns where the corrupt database was moved and the
+/// problems found.
+///
+/// With `encrypted`, a database SQLite can't read is taken for a wrong key and reported as an
+/// error instead of being moved aside.
+pub(crate) fn recover_if_corrupt(
+    conn: &mut Connection,
+    encrypted: bool,
+    reopen: impl Fn(&Path) -> rusqlite::Result<Connection>,
+) -> rusqlite::Result<Option<(PathBuf, Vec<String>)>> {
+    let path = match conn.path() {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        // in-memory databases can't be corrupted by power loss
+        _ => return Ok(None),
+    };
+    if encrypted {
+        match conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(())) {
+            Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == ErrorCode::NotADatabase => {
+                return Err(rusqlite::Error::SqliteFailure(
+                    e,
+                    Some("the database can't be read, is the encryption key wrong?".into()),
+                ))
+            }
+            _ => {}
+        }
+    }
+    let problems = check(conn, "quick_check")?;
+    if problems.is_empty() {
+        return Ok(None);
+    }
+
+    let mut aside = path.clone().into_os_string();
+    aside.push(format!(
+        ".corrupt-{}",
+        OffsetDateTime::now_utc().unix_timestamp()
+    ));
+    let aside = PathBuf::from(aside);
+
+    // close the corrupt database before moving it
+    *conn = Connection::open_in_memory()?;
+    for suffix in ["", "-wal", "-shm", "-journal"] {
+        let mut from = path.clone().into_os_string();
+        from.push(suffix);
+        let mut to = aside.clone().into_os_string();
+        to.push(suffix);
+        match fs::rename(&from, &to) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(io_error(e)),
+            _ => {}
+        }
+    }
+    *conn = reopen(&path)?;
+    Ok(Some((aside, problems)))
+}
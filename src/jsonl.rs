@@ -0,0 +1,163 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+    time::Duration,
+};
+
+use serde_json::json;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+
+use crate::{Connect, LogEntry, Origin, TimePrecision, TraceContext};
+
+/// A `Connect` appending entries as JSON lines to a file, e.g. as a fallback for writes to the
+/// database that failed, see `SubscriberBuilder::with_fallback`.
+///
+/// Every line is written out immediately.
+#[derive(Debug)]
+pub struct JsonlConnect {
+    file: Mutex<File>,
+}
+
+impl JsonlConnect {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl Connect for JsonlConnect {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        let mut line = entry_to_json(&entry).to_string();
+        line.push('\n');
+        self.file
+            .lock()
+            .unwrap()
+            .write_all(line.as_bytes())
+            // `Connect` reports rusqlite errors, this is the variant that carries arbitrary ones
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+    }
+}
+
+pub(crate) fn entry_to_json(entry: &LogEntry<&str>) -> serde_json::Value {
+    json!({
+        "time": entry.time.format(&Rfc3339).ok(),
+        "level": entry.level.as_str(),
+        "module": entry.module,
+        "file": entry.file,
+        "line": entry.line,
+        "message": entry.message,
+        "fields": entry.structured.fields(),
+        "category": entry.category.map(|c| c.as_str()),
+        "utc_offset": entry.utc_offset.map(|o| o.whole_minutes()),
+        "repeat_count": entry.repeat_count,
+        "origin": entry.origin.map(|o| o.as_str()),
+        "expires_at": entry.expires_at.and_then(|t| t.format(&Rfc3339).ok()),
+        "elapsed_us": entry.elapsed.map(|e| e.as_micros() as u64),
+        "event_id": entry.event_id.map(|id| id.to_string()),
+        "trace_id": entry.trace_context.map(|c| c.trace_id_hex()),
+        "span_id": entry.trace_context.map(|c| c.span_id_hex()),
+    })
+}
+
+/// Parses a line written by [`entry_to_json`], or produced elsewhere in the same format.
+/// `time`, `level` and `message` are required, field values that aren't strings are stored
+/// as their JSON text.
+pub(crate) fn entry_from_json(line: &str) -> Result<LogEntry, String> {
+    let value: serde_json::Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    let object = value.as_object().ok_or("not a JSON object")?;
+
+    let string = |key: &str| -> Result<Option<String>, String> {
+        match object.get(key) {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(serde_json::Value::String(s)) => Ok(Some(s.clone())),
+            Some(_) => Err(format!("`{key}` is not a string")),
+        }
+    };
+    let integer = |key: &str| -> Result<Option<i64>, String> {
+        match object.get(key) {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(value) => value
+                .as_i64()
+                .map(Some)
+                .ok_or_else(|| format!("`{key}` is not an integer")),
+        }
+    };
+
+    let time = string("time")?.ok_or("missing `time`")?;
+    let level = string("level")?.ok_or("missing `level`")?;
+    let category = string("category")?;
+    let utc_offset = integer("utc_offset")?;
+    let fields = match object.get("fields") {
+        None | Some(serde_json::Value::Null) => HashMap::new(),
+        Some(serde_json::Value::Object(fields)) => fields
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    serde_json::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                (name.clone(), value)
+            })
+            .collect(),
+        Some(_) => return Err("`fields` is not an object".to_owned()),
+    };
+
+    Ok(LogEntry {
+        time: OffsetDateTime::parse(&time, &Rfc3339).map_err(|e| format!("`time`: {e}"))?,
+        time_precision: TimePrecision::Text,
+        level: level
+            .parse()
+            .map_err(|_| format!("`level`: unknown level {level:?}"))?,
+        module: string("module")?,
+        file: string("file")?,
+        line: integer("line")?
+            .map(|line| u32::try_from(line).map_err(|_| "`line` is out of range"))
+            .transpose()?,
+        message: string("message")?.ok_or("missing `message`")?,
+        structured: fields.into(),
+        category: category
+            .map(|c| {
+                c.parse()
+                    .map_err(|_| format!("`category`: unknown category {c:?}"))
+            })
+            .transpose()?,
+        utc_offset: utc_offset
+            .map(|minutes| {
+                i32::try_from(minutes)
+                    .ok()
+                    .and_then(|minutes| {
+                        UtcOffset::from_whole_seconds(minutes.checked_mul(60)?).ok()
+                    })
+                    .ok_or("`utc_offset` is out of range")
+            })
+            .transpose()?,
+        repeat_count: integer("repeat_count")?
+            .map(|count| u64::try_from(count).map_err(|_| "`repeat_count` is negative"))
+            .transpose()?
+            .unwrap_or(1),
+        origin: Some(Origin::Import),
+        expires_at: string("expires_at")?
+            .map(|t| OffsetDateTime::parse(&t, &Rfc3339).map_err(|e| format!("`expires_at`: {e}")))
+            .transpose()?,
+        elapsed: integer("elapsed_us")?
+            .map(|us| u64::try_from(us).map_err(|_| "`elapsed_us` is negative"))
+            .transpose()?
+            .map(Duration::from_micros),
+        event_id: string("event_id")?
+            .map(|id| id.parse().map_err(|e| format!("`event_id`: {e}")))
+            .transpose()?,
+        trace_context: match (string("trace_id")?, string("span_id")?) {
+            (None, None) => None,
+            (trace_id, span_id) => Some(
+                TraceContext::from_hex(trace_id.as_deref(), span_id.as_deref())
+                    .ok_or("invalid `trace_id` or `span_id`")?,
+            ),
+        },
+    })
+}
@@ -1,54 +1,375 @@
+mod aggregate;
+mod archive;
+#[cfg(feature = "tokio")]
+mod async_connect;
+mod backup;
+mod batch;
+mod bulk;
+mod canonical;
+mod checkpoint;
+mod clock;
+mod coalesce;
+mod context;
 mod db;
+mod db_stats;
+mod delete;
+#[cfg(feature = "self-diagnostics")]
+mod diagnostics;
+mod diff;
+mod drops;
+mod export;
+mod field_index;
+mod format;
+#[cfg(feature = "http")]
+mod http;
+mod import;
+mod incident;
+mod integrity;
+mod jsonl;
+#[cfg(feature = "libsql")]
+mod libsql_connect;
+mod lock;
+mod memory;
+mod merge;
+mod open;
+mod otel;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod page;
+mod per_thread;
+mod periodic;
+#[cfg(feature = "r2d2")]
+mod pool;
+mod profile;
+mod promote;
+mod query;
+#[cfg(feature = "tokio")]
+mod queue;
+mod ratelimit;
+mod redact;
+mod replay;
+mod rollup;
+mod rotate;
+mod run;
+mod shard;
+mod shipping;
+mod spans;
+#[cfg(feature = "sqlx")]
+mod sqlx_connect;
+mod stats;
+mod store;
+mod strict;
+mod structured;
+mod suppress;
+mod switch;
+mod tail;
+mod timestamp;
+mod trie;
+mod ttl;
+mod ulid;
+mod vacuum;
+mod viewer;
+mod views;
+mod watchdog;
 
+pub use aggregate::*;
+#[cfg(feature = "tokio")]
+pub use async_connect::*;
+pub use batch::*;
+pub use bulk::*;
+pub use canonical::CanonicalLineOptions;
+pub use checkpoint::{Checkpoint, CheckpointMode};
+pub use clock::{Clock, SystemClock};
+pub use context::{ContextFields, ContextProvider};
 pub use db::*;
-use time::OffsetDateTime;
+pub use db_stats::*;
+#[cfg(feature = "self-diagnostics")]
+pub use diagnostics::*;
+pub use diff::*;
+pub use format::StructuredFormat;
+#[cfg(feature = "http")]
+pub use http::*;
+pub use import::{ImportError, ImportReport};
+pub use incident::*;
+pub use jsonl::*;
+#[cfg(feature = "libsql")]
+pub use libsql_connect::*;
+pub use lock::*;
+pub use memory::*;
+pub use merge::*;
+pub use open::*;
+pub use otel::TraceContext;
+#[cfg(feature = "otel")]
+pub use otel::TraceContextSource;
+#[cfg(feature = "otlp")]
+pub use otlp::*;
+pub use page::*;
+pub use per_thread::*;
+pub use profile::*;
+pub use promote::*;
+pub use query::*;
+pub use redact::{Redaction, REDACTED};
+pub use rollup::*;
+pub use rotate::*;
+pub use run::*;
+pub use shard::*;
+pub use shipping::*;
+#[cfg(feature = "sqlx")]
+pub use sqlx_connect::*;
+pub use stats::*;
+pub use store::*;
+pub use strict::*;
+pub use structured::*;
+pub use switch::*;
+pub use tail::*;
+use time::UtcOffset;
+pub use timestamp::{parse_time, TimePrecision, Timezone};
+pub use trie::*;
+pub use ulid::Ulid;
+pub use vacuum::VacuumPolicy;
+pub use viewer::*;
+pub use views::*;
 
 use std::{
     collections::HashMap,
     fmt::Write,
-    sync::{atomic::AtomicU64, Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
+use bulk::BurstDetector;
+use canonical::CanonicalLines;
+use clock::ClockSource;
+use coalesce::Coalescer;
+use context::Provider;
+use drops::DropMarkers;
+use periodic::Periodic;
+use ratelimit::{Decision, RateLimit};
+use redact::{FieldLists, Redactor};
 use rusqlite::Connection;
-use tracing::{field::Visit, level_filters::LevelFilter, span};
+use spans::SpanTracker;
+use stats::{Stage, StatsReport};
+use suppress::{Suppressor, Verdict};
+use tracing::{field::Visit, level_filters::LevelFilter, span, subscriber::Interest};
 #[cfg(feature = "tracing-log")]
 use tracing_log::NormalizeEvent;
+use ulid::UlidGenerator;
+use watchdog::Watchdog;
 
 /// A `Layer` to write events to a sqlite database.
 /// This type can be composed with other `Subscriber`s and `Layer`s.
 #[derive(Debug)]
-pub struct Layer<C> {
+pub struct Layer<C: Connect> {
     logger: C,
     max_level: LevelFilter,
-    black_list: Option<Box<[&'static str]>>,
-    white_list: Option<Box<[&'static str]>>,
+    black_list: Option<ModuleList>,
+    white_list: Option<ModuleList>,
+    target_levels: Box<[(&'static str, LevelFilter)]>,
+    category_rules: Box<[CategoryRule]>,
+    field_filters: Box<[FieldFilter]>,
+    redactor: Redactor,
+    field_lists: FieldLists,
+    max_message_len: Option<usize>,
+    max_field_len: Option<usize>,
+    structured_format: StructuredFormat,
+    time_precision: TimePrecision,
+    clock: ClockSource,
+    /// When the layer was created, the start of `LogEntry::elapsed`.
+    started: Instant,
+    event_ids: UlidGenerator,
+    #[cfg(feature = "otel")]
+    trace_context: Option<otel::TraceSource>,
+    context_providers: Box<[Provider]>,
+    sampling: Sampling,
+    /// Only set with span sampling or canonical lines.
+    spans: Option<SpanTracker>,
+    canonical: Option<CanonicalLines>,
+    rate_limits: Box<[RateLimit]>,
+    suppressor: Option<Suppressor>,
+    drop_markers: Option<DropMarkers>,
+    stats: StatsHandle,
+    stats_report: Option<StatsReport>,
+    coalescer: Option<Coalescer>,
+    utc_offset: Option<UtcOffset>,
+    alert: Option<Alert>,
+    flush_level: Option<tracing::Level>,
+    fallback: Option<Fallback>,
+    /// Whether writes may still prepare the database, until one succeeds,
+    /// see `SubscriberBuilder::with_auto_prepare`.
+    auto_prepare: AtomicBool,
+    prepare_lock: Mutex<()>,
+    error_handler: ErrorHandler,
+    watchdog: Option<Watchdog>,
+    /// Only held to stop the maintenance threads with the layer.
+    _maintenance: Vec<Periodic>,
+    #[cfg(feature = "self-diagnostics")]
+    diagnostics: diagnostics::Diagnostics,
 }
 
-impl<C> Layer<C> {
-    pub fn black_list(&self) -> Option<&[&'static str]> {
-        self.black_list.as_deref()
+struct ErrorHandler(Box<dyn Fn(rusqlite::Error) + Send + Sync>);
+
+impl Default for ErrorHandler {
+    fn default() -> Self {
+        Self(Box::new(|e| panic!("failed to write log entry: {e}")))
+    }
+}
+
+pub(crate) struct Fallback(pub(crate) Box<dyn Connect + Send + Sync>);
+
+impl std::fmt::Debug for Fallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Fallback")
+    }
+}
+
+impl std::fmt::Debug for ErrorHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ErrorHandler")
     }
+}
+
+/// Fraction of events kept per level, indexed like `level_index`. WARN and ERROR are always kept.
+#[derive(Debug, Clone, Copy)]
+struct Sampling([f64; 3]);
 
-    pub fn white_list(&self) -> Option<&[&'static str]> {
-        self.white_list.as_deref()
+impl Default for Sampling {
+    fn default() -> Self {
+        Self([1.0; 3])
+    }
+}
+
+impl Sampling {
+    fn level_index(level: tracing::Level) -> Option<usize> {
+        match level {
+            tracing::Level::INFO => Some(0),
+            tracing::Level::DEBUG => Some(1),
+            tracing::Level::TRACE => Some(2),
+            _ => None,
+        }
+    }
+
+    fn keep(&self, level: tracing::Level) -> bool {
+        match Self::level_index(level).map(|i| self.0[i]) {
+            Some(rate) if rate < 1.0 => fastrand::f64() < rate,
+            _ => true,
+        }
+    }
+}
+
+type AlertFn = dyn Fn(&LogEntry<&str>) + Send + Sync;
+
+struct Alert {
+    level: tracing::Level,
+    callback: Box<AlertFn>,
+}
+
+impl std::fmt::Debug for Alert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Alert")
+            .field("level", &self.level)
+            .finish_non_exhaustive()
+    }
+}
+
+type FieldFilterFn = dyn Fn(&str, &str) -> bool + Send + Sync;
+
+struct FieldFilter(Box<FieldFilterFn>);
+
+impl std::fmt::Debug for FieldFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("FieldFilter")
+    }
+}
+
+#[derive(Debug)]
+enum CategoryRule {
+    Target(&'static str, ErrorCategory),
+    Field(&'static str, ErrorCategory),
+}
+
+impl<C: Connect> Layer<C> {
+    pub fn black_list(&self) -> Option<&ModuleList> {
+        self.black_list.as_ref()
+    }
+
+    pub fn white_list(&self) -> Option<&ModuleList> {
+        self.white_list.as_ref()
     }
 
     pub fn max_level(&self) -> &LevelFilter {
         &self.max_level
     }
 
+    /// Level overrides by target prefix, longest prefix first.
+    pub fn target_levels(&self) -> &[(&'static str, LevelFilter)] {
+        &self.target_levels
+    }
+
+    /// The level of the longest matching target prefix, or `max_level`.
+    fn level_for(&self, target: &str) -> &LevelFilter {
+        self.target_levels
+            .iter()
+            .find(|(prefix, _)| target.starts_with(prefix))
+            .map_or(&self.max_level, |(_, level)| level)
+    }
+
     fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
-        metadata.level() <= self.max_level()
+        metadata.level() <= self.level_for(metadata.target())
             && metadata.module_path().is_none_or(|m| {
-                let starts_with = |module: &&str| m.starts_with(module);
-                let has_module = |modules: &[&str]| modules.iter().any(starts_with);
+                let has_module = |modules: &ModuleList| modules.matches(m);
                 self.white_list().is_none_or(has_module)
                     && !(self.black_list().is_some_and(has_module))
             })
     }
 
     fn max_level_hint(&self) -> Option<tracing::level_filters::LevelFilter> {
-        Some(self.max_level)
+        self.target_levels
+            .iter()
+            .map(|(_, level)| *level)
+            .chain([self.max_level])
+            .max()
+    }
+
+    /// `enabled` only looks at a callsite's static metadata, so tracing can cache its result.
+    fn register_callsite(&self, metadata: &'static tracing::Metadata<'static>) -> Interest {
+        if self.enabled(metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn categorize(
+        &self,
+        meta: &tracing::Metadata<'_>,
+        structured: &HashMap<&'static str, String>,
+    ) -> Option<ErrorCategory> {
+        if *meta.level() > tracing::Level::WARN {
+            return None;
+        }
+
+        self.category_rules.iter().find_map(|rule| match rule {
+            CategoryRule::Target(target, category) if meta.target().starts_with(target) => {
+                Some(*category)
+            }
+            CategoryRule::Field(field, category) if structured.contains_key(field) => {
+                Some(*category)
+            }
+            _ => None,
+        })
+    }
+
+    /// Totals since the layer was built.
+    pub fn stats(&self) -> Stats {
+        self.stats.get()
+    }
+
+    /// A handle to read the stats once the layer has been installed.
+    pub fn stats_handle(&self) -> StatsHandle {
+        self.stats.clone()
     }
 
     pub fn to_subscriber(self) -> Subscriber<C> {
@@ -57,7 +378,100 @@ impl<C> Layer<C> {
 }
 
 impl<C: Connect> Layer<C> {
-    fn on_event(&self, event: &tracing::Event<'_>) {
+    /// Writes out everything recorded so far and blocks until it is in the database, so a
+    /// query or tail right after sees it, also with batching and async sinks (see
+    /// `Connect::flush_and_wait`). Repeats held back by coalescing are written too.
+    pub fn flush_and_wait(&self, timeout: Duration) -> rusqlite::Result<()> {
+        if let Some(coalescer) = &self.coalescer {
+            coalescer.flush(|entry| self.write(entry));
+        }
+        self.logger.flush_and_wait(timeout)
+    }
+
+    fn on_new_span(
+        &self,
+        attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        current_span: impl FnOnce() -> Option<span::Id>,
+    ) {
+        let Some(spans) = &self.spans else {
+            return;
+        };
+        let parent = match attrs.parent() {
+            Some(parent) => Some(parent.clone()),
+            None if attrs.is_contextual() => current_span(),
+            None => None,
+        };
+        let state = spans.new_span(id, parent.as_ref());
+
+        if let Some(canonical) = &self.canonical {
+            let fields = self.span_fields(|visitor| attrs.record(visitor));
+            if state.root == *id {
+                canonical.start(id, attrs.metadata(), &fields);
+            } else {
+                canonical.add_fields(&state.root, &fields);
+            }
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>) {
+        let (Some(spans), Some(canonical)) = (&self.spans, &self.canonical) else {
+            return;
+        };
+        if let Some(state) = spans.get(Some(id)) {
+            canonical.add_fields(
+                &state.root,
+                &self.span_fields(|visitor| values.record(visitor)),
+            );
+        }
+    }
+
+    fn on_close(&self, id: &span::Id) {
+        let Some(state) = self.spans.as_ref().and_then(|spans| spans.remove(id)) else {
+            return;
+        };
+        let line = self
+            .canonical
+            .as_ref()
+            .filter(|_| state.root == *id && state.keep != Some(false))
+            .and_then(|canonical| canonical.finish(id, self.clock.0.now()));
+        if let Some(entry) = line {
+            self.write(LogEntry {
+                utc_offset: self.utc_offset,
+                ..entry
+            });
+        }
+    }
+
+    /// The trace context of the current span, see `SubscriberBuilder::with_trace_context`.
+    fn trace_context(&self) -> Option<TraceContext> {
+        #[cfg(feature = "otel")]
+        if let Some(source) = &self.trace_context {
+            return source.0.current();
+        }
+        None
+    }
+
+    /// The fields of a span, recorded like those of events.
+    fn span_fields(&self, record: impl FnOnce(&mut Visitor<'_>)) -> HashMap<&'static str, String> {
+        let mut fields = HashMap::new();
+        record(&mut Visitor {
+            message: &mut String::new(),
+            kvs: &mut fields,
+            category: &mut None,
+            ttl_secs: &mut None,
+            redactor: &self.redactor,
+            field_lists: &self.field_lists,
+            max_field_len: self.max_field_len,
+        });
+        fields
+    }
+
+    fn on_event(
+        &self,
+        event: &tracing::Event<'_>,
+        current_span: impl FnOnce() -> Option<span::Id>,
+    ) {
         #[cfg(feature = "tracing-log")]
         let normalized_meta = event.normalized_metadata();
         #[cfg(feature = "tracing-log")]
@@ -67,36 +481,388 @@ impl<C: Connect> Layer<C> {
             _ => return,
         };
 
+        #[cfg(feature = "tracing-log")]
+        let origin = match normalized_meta {
+            Some(_) => Origin::Log,
+            None => Origin::Tracing,
+        };
+
         #[cfg(not(feature = "tracing-log"))]
         let meta = event.metadata();
+        #[cfg(not(feature = "tracing-log"))]
+        let origin = Origin::Tracing;
+
+        self.write_drop_markers();
+        self.write_stats_report();
 
         let level = *meta.level();
         let module = meta.module_path();
         let file = meta.file();
         let line = meta.line();
 
+        let span = self.spans.as_ref().and_then(|spans| {
+            let span = match event.parent() {
+                Some(parent) => Some(parent.clone()),
+                None if event.is_contextual() => current_span(),
+                None => None,
+            };
+            spans.get(span.as_ref())
+        });
+        // events in sampled spans follow the decision of their root span instead of their level
+        let keep = match span.as_ref().and_then(|span| span.keep) {
+            Some(keep) => keep || Sampling::level_index(level).is_none(),
+            None => self.sampling.keep(level),
+        };
+        if !keep {
+            self.stats.counters().dropped();
+            self.record_dropped(module, "sampling");
+            return;
+        }
+
         let mut message = String::new();
         let mut structured = HashMap::new();
+        let mut category = None;
+        let mut ttl_secs = None;
 
+        let recording = Instant::now();
         event.record(&mut Visitor {
             message: &mut message,
             kvs: &mut structured,
+            category: &mut category,
+            ttl_secs: &mut ttl_secs,
+            redactor: &self.redactor,
+            field_lists: &self.field_lists,
+            max_field_len: self.max_field_len,
         });
+        if let Some(max) = self.max_message_len {
+            truncate(&mut message, max);
+        }
+        if !self.context_providers.is_empty() {
+            let mut fields = ContextFields {
+                kvs: &mut structured,
+                redactor: &self.redactor,
+                field_lists: &self.field_lists,
+                max_field_len: self.max_field_len,
+            };
+            for provider in self.context_providers.iter() {
+                provider.0.provide(&mut fields);
+            }
+        }
+
+        self.stats
+            .counters()
+            .stage(Stage::Record, recording.elapsed());
+        let filtering = Instant::now();
+
+        let keep = |(name, value): (&&str, &String)| {
+            self.field_filters
+                .iter()
+                .all(|filter| (filter.0)(name, value))
+        };
+        if !structured.iter().all(keep) {
+            self.stats.counters().filtered();
+            self.record_dropped(module, "field filter");
+            return;
+        }
+
+        if let (Some(canonical), Some(span)) = (&self.canonical, &span) {
+            canonical.add_event(&span.root, level, &structured);
+            if canonical.options.replace_events {
+                return;
+            }
+        }
+
+        let rate_limit = self
+            .rate_limits
+            .iter()
+            .find(|limit| meta.target().starts_with(limit.prefix));
+        if let Some(limit) = rate_limit {
+            match limit.check() {
+                Decision::Allow { suppressed: 0 } => {}
+                Decision::Allow { suppressed } => self.write(LogEntry {
+                    module,
+                    ..self.marker_entry(
+                        tracing::Level::INFO,
+                        format!("{suppressed} events suppressed by rate limit"),
+                        [
+                            ("suppressed", suppressed.to_string()),
+                            ("rate_limit.target", format!("{:?}", limit.prefix)),
+                        ],
+                    )
+                }),
+                Decision::Deny => {
+                    self.stats.counters().dropped();
+                    return;
+                }
+            }
+        }
 
-        self.logger.log(LogEntry {
-            time: OffsetDateTime::now_utc(),
+        if let Some(suppressor) = self
+            .suppressor
+            .as_ref()
+            .filter(|_| matches!(level, tracing::Level::INFO | tracing::Level::WARN))
+        {
+            let verdict = suppressor.check(meta.callsite(), &message);
+            let marker = match verdict {
+                Verdict::Allow { suppressed: 0 } => None,
+                Verdict::Allow { suppressed } => Some((
+                    format!("{suppressed} repeated events suppressed"),
+                    ("suppressed", suppressed.to_string()),
+                )),
+                Verdict::Start => Some((
+                    format!(
+                        "suppressing repeats of this event for {:?}",
+                        suppressor.cooldown()
+                    ),
+                    (
+                        "suppression.cooldown",
+                        format!("{:?}", suppressor.cooldown()),
+                    ),
+                )),
+                Verdict::Suppress => None,
+            };
+            if let Some((marker, count)) = marker {
+                self.write(LogEntry {
+                    module,
+                    file,
+                    line,
+                    ..self.marker_entry(
+                        tracing::Level::INFO,
+                        marker,
+                        [count, ("suppression.message", format!("{message:?}"))],
+                    )
+                });
+            }
+            if matches!(verdict, Verdict::Start | Verdict::Suppress) {
+                self.stats.counters().dropped();
+                self.record_dropped(module, "auto-suppression");
+                return;
+            }
+        }
+
+        if category.is_none() {
+            category = self.categorize(meta, &structured);
+        }
+
+        let now = self.clock.0.now();
+        let entry = LogEntry {
+            time: now,
+            time_precision: TimePrecision::Text,
             level,
             module,
             file,
             line,
             message,
-            structured,
+            structured: structured.into(),
+            category,
+            utc_offset: self.utc_offset,
+            repeat_count: 1,
+            origin: Some(origin),
+            expires_at: ttl_secs.and_then(|secs| now.checked_add(time::Duration::seconds(secs))),
+            elapsed: Some(self.started.elapsed()),
+            event_id: Some(self.event_ids.generate(now)),
+            trace_context: self.trace_context(),
+        };
+        match &self.coalescer {
+            Some(coalescer) => coalescer.coalesce(entry, |entry| {
+                self.stats
+                    .counters()
+                    .stage(Stage::Filter, filtering.elapsed());
+                self.write(entry)
+            }),
+            None => {
+                self.stats
+                    .counters()
+                    .stage(Stage::Filter, filtering.elapsed());
+                self.write(entry)
+            }
+        }
+    }
+
+    fn record_dropped(&self, module: Option<&str>, reason: &'static str) {
+        self.record_dropped_many(module, reason, 1);
+    }
+
+    fn record_dropped_many(&self, module: Option<&str>, reason: &'static str, count: u64) {
+        if let Some(markers) = &self.drop_markers {
+            markers.record(module, reason, count);
+        }
+    }
+
+    fn write_stats_report(&self) {
+        if !self.stats_report.as_ref().is_some_and(StatsReport::is_due) {
+            return;
+        }
+
+        let stats = self.stats();
+        self.write(LogEntry {
+            module: Some(module_path!()),
+            ..self.marker_entry(
+                tracing::Level::INFO,
+                format!(
+                    "{} entries written, {} filtered, {} dropped, {} write errors",
+                    stats.written, stats.filtered, stats.dropped, stats.write_errors
+                ),
+                [
+                    ("stats.written", stats.written.to_string()),
+                    ("stats.filtered", stats.filtered.to_string()),
+                    ("stats.dropped", stats.dropped.to_string()),
+                    ("stats.write_errors", stats.write_errors.to_string()),
+                    (
+                        "stats.average_write_latency_us",
+                        stats.average_write_latency.as_micros().to_string(),
+                    ),
+                    (
+                        "stats.average_record_latency_us",
+                        stats.stages.record.as_micros().to_string(),
+                    ),
+                    (
+                        "stats.average_filter_latency_us",
+                        stats.stages.filter.as_micros().to_string(),
+                    ),
+                ],
+            )
         });
     }
+
+    fn write_drop_markers(&self) {
+        let Some(markers) = &self.drop_markers else {
+            return;
+        };
+
+        for dropped in markers.take_due() {
+            self.write(LogEntry {
+                module: dropped.module.as_deref(),
+                ..self.marker_entry(
+                    tracing::Level::INFO,
+                    format!("{} events dropped by {}", dropped.count, dropped.reason),
+                    [
+                        ("dropped", dropped.count.to_string()),
+                        ("dropped.reason", format!("{:?}", dropped.reason)),
+                    ],
+                )
+            });
+        }
+    }
+
+    /// A row the layer writes about itself, e.g. a rate limit or drop marker, without a
+    /// module or location.
+    fn marker_entry<'a, const N: usize>(
+        &self,
+        level: tracing::Level,
+        message: String,
+        fields: [(&'static str, String); N],
+    ) -> LogEntry<&'a str> {
+        LogEntry {
+            time: self.clock.0.now(),
+            time_precision: TimePrecision::Text,
+            level,
+            module: None,
+            file: None,
+            line: None,
+            message,
+            structured: HashMap::from(fields).into(),
+            category: None,
+            utc_offset: self.utc_offset,
+            repeat_count: 1,
+            origin: Some(Origin::Tracing),
+            expires_at: None,
+            elapsed: None,
+            event_id: None,
+            trace_context: None,
+        }
+    }
+
+    fn write(&self, mut entry: LogEntry<&str>) {
+        entry.structured = entry.structured.with_format(self.structured_format);
+        entry.time_precision = self.time_precision;
+        entry.elapsed = entry.elapsed.or_else(|| Some(self.started.elapsed()));
+        entry.event_id = entry
+            .event_id
+            .or_else(|| Some(self.event_ids.generate(entry.time)));
+        let alert = self
+            .alert
+            .as_ref()
+            .filter(|alert| entry.level <= alert.level)
+            .map(|alert| (alert, entry.clone()));
+
+        let flush = self.flush_level.is_some_and(|level| entry.level <= level);
+        // entries the sink keeps are written later, the fallback would write them twice
+        let fallback = self
+            .fallback
+            .as_ref()
+            .filter(|_| !self.logger.keeps_failed_entries())
+            .map(|fallback| (fallback, entry.clone()));
+        let module = entry.module;
+        let started = Instant::now();
+        let guard = self.watchdog.as_ref().map(Watchdog::arm);
+        let mut result = if self.auto_prepare.load(Ordering::Relaxed) {
+            // other threads' first writes wait here, so only one of them creates the tables
+            let _lock = self.prepare_lock.lock().unwrap();
+            let result = self.logger.log_or_prepare(entry);
+            if result.is_ok() {
+                self.auto_prepare.store(false, Ordering::Relaxed);
+            }
+            result
+        } else {
+            self.logger.log(entry)
+        };
+        self.stats.counters().stage(Stage::Sink, started.elapsed());
+        let dropped = self.logger.take_dropped();
+        if dropped > 0 {
+            self.stats.counters().dropped_many(dropped);
+            self.record_dropped_many(module, "a full queue", dropped);
+        }
+        if flush && result.is_ok() {
+            let flushing = Instant::now();
+            result = self.logger.flush();
+            self.stats
+                .counters()
+                .stage(Stage::Flush, flushing.elapsed());
+        }
+        drop(guard);
+        self.stats
+            .counters()
+            .write(result.is_ok(), started.elapsed());
+        #[cfg(feature = "self-diagnostics")]
+        self.diagnostics.record(&result);
+        match result {
+            Ok(()) => {
+                if let Some((alert, entry)) = alert {
+                    (alert.callback)(&entry);
+                }
+            }
+            Err(e) => {
+                let rescued =
+                    fallback.is_some_and(|(fallback, entry)| fallback.0.log(entry).is_ok());
+                if !rescued {
+                    (self.error_handler.0)(e);
+                }
+            }
+        }
+    }
+}
+
+impl<C: Connect> Drop for Layer<C> {
+    /// Writes the repeats still held back by coalescing, what happens to them then is up to
+    /// the sink, like for any other entry.
+    fn drop(&mut self) {
+        // writing could panic again in the error handler
+        if std::thread::panicking() {
+            return;
+        }
+        if let Some(coalescer) = &self.coalescer {
+            coalescer.flush(|entry| self.write(entry));
+        }
+    }
 }
 
 #[cfg(feature = "layer")]
 impl<S: tracing::Subscriber, C: Connect + 'static> tracing_subscriber::Layer<S> for Layer<C> {
+    fn register_callsite(&self, metadata: &'static tracing::Metadata<'static>) -> Interest {
+        self.register_callsite(metadata)
+    }
+
     fn enabled(
         &self,
         metadata: &tracing::Metadata<'_>,
@@ -105,23 +871,54 @@ impl<S: tracing::Subscriber, C: Connect + 'static> tracing_subscriber::Layer<S>
         self.enabled(metadata)
     }
 
-    fn on_event(&self, event: &tracing::Event<'_>, _: tracing_subscriber::layer::Context<'_, S>) {
-        self.on_event(event)
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        self.max_level_hint()
+    }
+
+    fn on_new_span(
+        &self,
+        attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        self.on_new_span(attrs, id, || ctx.current_span().id().cloned())
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        self.on_event(event, || ctx.current_span().id().cloned())
+    }
+
+    fn on_record(
+        &self,
+        id: &span::Id,
+        values: &span::Record<'_>,
+        _: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        self.on_record(id, values)
+    }
+
+    fn on_close(&self, id: span::Id, _: tracing_subscriber::layer::Context<'_, S>) {
+        self.on_close(&id)
     }
 }
 
 /// A simple `Subscriber` that wraps `Layer`[crate::Layer].
 #[derive(Debug)]
-pub struct Subscriber<C> {
+pub struct Subscriber<C: Connect> {
     id: AtomicU64,
     layer: Layer<C>,
 }
 
-impl<C> Subscriber<C> {
+impl<C: Connect> Subscriber<C> {
     pub fn new(connection: C) -> Self {
         Self::with_max_level(connection, LevelFilter::TRACE)
     }
 
+    /// Identifies the spans entered through this subscriber on a thread.
+    fn owner(&self) -> usize {
+        &self.layer as *const Layer<C> as usize
+    }
+
     fn with_layer(layer: Layer<C>) -> Self {
         Self {
             id: AtomicU64::new(1),
@@ -130,24 +927,44 @@ impl<C> Subscriber<C> {
     }
 
     pub fn with_max_level(connection: C, max_level: LevelFilter) -> Self {
-        Self::with_layer(Layer {
-            logger: connection,
-            max_level,
-            black_list: None,
-            white_list: None,
-        })
+        SubscriberBuilder::new()
+            .with_max_level(max_level)
+            .build(connection)
     }
 
-    pub fn black_list(&self) -> Option<&[&'static str]> {
+    pub fn black_list(&self) -> Option<&ModuleList> {
         self.layer.black_list()
     }
 
-    pub fn white_list(&self) -> Option<&[&'static str]> {
+    pub fn white_list(&self) -> Option<&ModuleList> {
         self.layer.white_list()
     }
+
+    pub fn target_levels(&self) -> &[(&'static str, LevelFilter)] {
+        self.layer.target_levels()
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.layer.stats()
+    }
+
+    pub fn stats_handle(&self) -> StatsHandle {
+        self.layer.stats_handle()
+    }
+}
+
+impl<C: Connect> Subscriber<C> {
+    /// See `Layer::flush_and_wait`.
+    pub fn flush_and_wait(&self, timeout: Duration) -> rusqlite::Result<()> {
+        self.layer.flush_and_wait(timeout)
+    }
 }
 
 impl<C: Connect + 'static> tracing::Subscriber for Subscriber<C> {
+    fn register_callsite(&self, metadata: &'static tracing::Metadata<'static>) -> Interest {
+        self.layer.register_callsite(metadata)
+    }
+
     fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
         self.layer.enabled(metadata)
     }
@@ -156,54 +973,221 @@ impl<C: Connect + 'static> tracing::Subscriber for Subscriber<C> {
         self.layer.max_level_hint()
     }
 
-    fn new_span(&self, _span: &span::Attributes<'_>) -> span::Id {
+    fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
         let id = self.id.fetch_add(1, std::sync::atomic::Ordering::AcqRel);
-        span::Id::from_u64(id)
+        let id = span::Id::from_u64(id);
+        self.layer
+            .on_new_span(span, &id, || SpanTracker::current(self.owner()));
+        id
     }
 
-    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+    fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+        self.layer.on_record(span, values)
+    }
 
     fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
 
     fn event(&self, event: &tracing::Event<'_>) {
-        self.layer.on_event(event)
+        self.layer
+            .on_event(event, || SpanTracker::current(self.owner()))
     }
 
-    fn enter(&self, _span: &span::Id) {}
+    // spans are only tracked for span sampling and canonical lines
+    fn enter(&self, span: &span::Id) {
+        if self.layer.spans.is_some() {
+            SpanTracker::enter(self.owner(), span);
+        }
+    }
+
+    fn exit(&self, span: &span::Id) {
+        if self.layer.spans.is_some() {
+            SpanTracker::exit(self.owner(), span);
+        }
+    }
 
-    fn exit(&self, _span: &span::Id) {}
+    fn clone_span(&self, span: &span::Id) -> span::Id {
+        if let Some(spans) = &self.layer.spans {
+            spans.clone_span(span);
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: span::Id) -> bool {
+        let closed = self
+            .layer
+            .spans
+            .as_ref()
+            .is_some_and(|spans| spans.release(&span));
+        if closed {
+            self.layer.on_close(&span);
+        }
+        closed
+    }
 }
 
 struct Visitor<'a> {
     pub message: &'a mut String,
     pub kvs: &'a mut HashMap<&'static str, String>, // todo: store structured key-value data
+    pub category: &'a mut Option<ErrorCategory>,
+    pub ttl_secs: &'a mut Option<i64>,
+    pub redactor: &'a Redactor,
+    pub field_lists: &'a FieldLists,
+    pub max_field_len: Option<usize>,
 }
 
 impl Visit for Visitor<'_> {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        match field.name() {
+            "error.category" => *self.category = value.parse().ok(),
+            _ => self.record_debug(field, &value),
+        }
+    }
+
     fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
         match field.name() {
             "message" => write!(self.message, "{value:?}").unwrap(),
+            "error.category" => *self.category = format!("{value:?}").parse().ok(),
+            "log_ttl_secs" => *self.ttl_secs = format!("{value:?}").parse().ok(),
             #[cfg(feature = "tracing-log")]
             "log.line" | "log.file" | "log.target" | "log.module_path" => {}
+            name if !self.field_lists.keeps(name) => {}
             name => {
-                self.kvs.insert(name, format!("{value:?}"));
+                let mut value = self.redactor.redact(name, format!("{value:?}"));
+                if let Some(max) = self.max_field_len {
+                    truncate(&mut value, max);
+                }
+                self.kvs.insert(name, value);
             }
         }
     }
 }
 
+/// Cuts `text` to at most `max` bytes on a character boundary and appends a marker with the
+/// number of bytes removed.
+fn truncate(text: &mut String, max: usize) {
+    if text.len() <= max {
+        return;
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let removed = text.len() - end;
+    text.truncate(end);
+    write!(text, "… [truncated {removed} bytes]").unwrap();
+}
+
 #[derive(Debug)]
 pub struct SubscriberBuilder {
     max_level: LevelFilter,
-    black_list: Option<Box<[&'static str]>>,
-    white_list: Option<Box<[&'static str]>>,
+    black_list: Option<ModuleList>,
+    white_list: Option<ModuleList>,
+    target_levels: Vec<(&'static str, LevelFilter)>,
+    category_rules: Vec<CategoryRule>,
+    field_filters: Vec<FieldFilter>,
+    redactor: Redactor,
+    field_lists: FieldLists,
+    max_message_len: Option<usize>,
+    max_field_len: Option<usize>,
+    structured_format: StructuredFormat,
+    time_precision: TimePrecision,
+    clock: ClockSource,
+    #[cfg(feature = "otel")]
+    trace_context: Option<otel::TraceSource>,
+    context_providers: Vec<Provider>,
+    sampling: Sampling,
+    span_sample_rate: Option<f64>,
+    canonical_lines: Option<CanonicalLineOptions>,
+    rate_limits: Vec<(&'static str, u32)>,
+    suppressor: Option<Suppressor>,
+    drop_marker_interval: Option<Duration>,
+    stats_interval: Option<Duration>,
+    coalesce_window: Option<Duration>,
+    utc_offset: Option<UtcOffset>,
+    alert: Option<Alert>,
+    flush_level: Option<tracing::Level>,
+    fallback: Option<Fallback>,
+    auto_prepare: bool,
+    error_handler: ErrorHandler,
+    write_timeout: Option<Duration>,
+    pragmas: Vec<(&'static str, String)>,
+    promoted_fields: Vec<&'static str>,
+    views: Option<Vec<&'static str>>,
+    recover_corrupt: bool,
+    checkpoint_interval: Option<(Duration, CheckpointMode)>,
+    vacuum_policy: Option<(VacuumPolicy, Duration)>,
+    burst_index_suspension: Option<(u64, Duration)>,
+    retention: Option<(Duration, Duration)>,
+    rollup_interval: Option<Duration>,
+    expiry_interval: Option<Duration>,
+    batching: Option<(usize, Duration)>,
+    #[cfg(feature = "sqlcipher")]
+    encryption_key: Option<String>,
+    #[cfg(feature = "self-diagnostics")]
+    diagnostics: diagnostics::Diagnostics,
 }
 
 impl SubscriberBuilder {
+    /// Records every level by default, same as [`Subscriber::new`].
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Preset for long-running services: `INFO` and above, WAL journal with `synchronous = NORMAL`
+    /// and a passive checkpoint every minute, so the WAL file doesn't grow while readers keep
+    /// the database open. Entries are written in batches of up to 500 at least every second and
+    /// kept for 30 days, checked hourly.
+    ///
+    /// Build with `build_batched` to get the batching, `build_prepared` writes every entry on
+    /// its own.
+    pub fn production() -> Self {
+        Self::new()
+            .with_max_level(LevelFilter::INFO)
+            .with_pragma("journal_mode", "WAL")
+            .with_pragma("synchronous", "NORMAL")
+            .with_checkpoint_interval(Duration::from_secs(60), CheckpointMode::Passive)
+            .with_batching(500, Duration::from_secs(1))
+            .with_retention(
+                Duration::from_secs(30 * 24 * 60 * 60),
+                Duration::from_secs(60 * 60),
+            )
+    }
+
+    /// Preset for local development: everything is recorded, WAL journal so the database can be
+    /// inspected while running. Batches are small and written at least every 100ms so new
+    /// entries show up quickly; entries are kept for 3 days, checked hourly.
+    pub fn development() -> Self {
+        Self::new()
+            .with_max_level(LevelFilter::TRACE)
+            .with_pragma("journal_mode", "WAL")
+            .with_batching(50, Duration::from_millis(100))
+            .with_retention(
+                Duration::from_secs(3 * 24 * 60 * 60),
+                Duration::from_secs(60 * 60),
+            )
+    }
+
+    /// Preset for devices with little storage: `WARN` and above, a small page cache and
+    /// `synchronous = FULL` because power loss is more likely than on servers. The WAL file is
+    /// truncated every minute and free pages are given back hourly with incremental vacuum,
+    /// which rebuilds an existing database once when it is prepared. Batches of up to 100
+    /// entries are written at least every 5 seconds to save flash writes, entries are kept for
+    /// 7 days, checked hourly.
+    pub fn embedded() -> Self {
+        Self::new()
+            .with_max_level(LevelFilter::WARN)
+            .with_pragma("journal_mode", "WAL")
+            .with_pragma("synchronous", "FULL")
+            .with_pragma("cache_size", "-512")
+            .with_checkpoint_interval(Duration::from_secs(60), CheckpointMode::Truncate)
+            .with_vacuum_policy(VacuumPolicy::Incremental, Duration::from_secs(60 * 60))
+            .with_batching(100, Duration::from_secs(5))
+            .with_retention(
+                Duration::from_secs(7 * 24 * 60 * 60),
+                Duration::from_secs(60 * 60),
+            )
+    }
+
     pub fn with_max_level(self, max_level: LevelFilter) -> Self {
         Self { max_level, ..self }
     }
@@ -211,7 +1195,7 @@ impl SubscriberBuilder {
     /// A log will not be recorded if its module path starts with any of item in the black list.
     pub fn with_black_list(self, black_list: impl IntoIterator<Item = &'static str>) -> Self {
         Self {
-            black_list: Some(black_list.into_iter().collect()),
+            black_list: Some(ModuleList::new(black_list)),
             ..self
         }
     }
@@ -219,12 +1203,491 @@ impl SubscriberBuilder {
     /// A log may be recorded only if its module path starts with any of item in the white list.
     pub fn with_white_list(self, white_list: impl IntoIterator<Item = &'static str>) -> Self {
         Self {
-            white_list: Some(white_list.into_iter().collect()),
+            white_list: Some(ModuleList::new(white_list)),
             ..self
         }
     }
 
-    pub fn build<C>(self, conn: C) -> Subscriber<C> {
+    /// Same as `with_black_list`, with a prefix trie that can be loaded at runtime and shared.
+    pub fn with_black_list_trie(self, black_list: Arc<PrefixTrie>) -> Self {
+        Self {
+            black_list: Some(ModuleList::Trie(black_list)),
+            ..self
+        }
+    }
+
+    /// Same as `with_white_list`, with a prefix trie that can be loaded at runtime and shared.
+    pub fn with_white_list_trie(self, white_list: Arc<PrefixTrie>) -> Self {
+        Self {
+            white_list: Some(ModuleList::Trie(white_list)),
+            ..self
+        }
+    }
+
+    /// Use `level` instead of the max level for events whose target starts with `target`.
+    /// The longest matching prefix wins, e.g. `with_target_level("sqlx", LevelFilter::WARN)`
+    /// keeps warnings and errors from a noisy crate while dropping the rest.
+    pub fn with_target_level(mut self, target: &'static str, level: LevelFilter) -> Self {
+        self.target_levels.retain(|(prefix, _)| *prefix != target);
+        self.target_levels.push((target, level));
+        self
+    }
+
+    /// Warnings and errors whose target starts with `target` are stored with `category`,
+    /// unless the event sets `error.category` itself. Rules are checked in the order they are added.
+    pub fn with_target_category(mut self, target: &'static str, category: ErrorCategory) -> Self {
+        self.category_rules
+            .push(CategoryRule::Target(target, category));
+        self
+    }
+
+    /// Warnings and errors that record `field` are stored with `category`,
+    /// unless the event sets `error.category` itself. Rules are checked in the order they are added.
+    pub fn with_field_category(mut self, field: &'static str, category: ErrorCategory) -> Self {
+        self.category_rules
+            .push(CategoryRule::Field(field, category));
+        self
+    }
+
+    /// An event is dropped if `filter` returns `false` for any of its structured fields.
+    /// The filter gets the field name and the `Debug` representation of its value,
+    /// e.g. `with_field_filter(|name, value| !(name == "health_check" && value == "true"))`.
+    pub fn with_field_filter(
+        mut self,
+        filter: impl Fn(&str, &str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.field_filters.push(FieldFilter(Box::new(filter)));
+        self
+    }
+
+    /// Values of fields whose name matches `pattern` are redacted before the entry is built,
+    /// so they never reach a `Connect` or field filter. `*` matches any number of characters
+    /// and ASCII case is ignored, e.g. `password`, `*_token` or `email*`.
+    /// Patterns are checked in the order they are added.
+    pub fn with_redaction(mut self, pattern: &'static str, redaction: Redaction) -> Self {
+        self.redactor.push(pattern, redaction);
+        self
+    }
+
+    /// Cut messages longer than `len` bytes and append `… [truncated N bytes]`.
+    /// The cut never splits a character, so the stored text may be a few bytes shorter.
+    pub fn with_max_message_len(self, len: usize) -> Self {
+        Self {
+            max_message_len: Some(len),
+            ..self
+        }
+    }
+
+    /// Same as `with_max_message_len` for the value of each structured field.
+    pub fn with_max_field_len(self, len: usize) -> Self {
+        Self {
+            max_field_len: Some(len),
+            ..self
+        }
+    }
+
+    /// Store structured fields in `format` in databases written through rusqlite connections,
+    /// e.g. `StructuredFormat::MessagePack` (feature `msgpack`), or `StructuredFormat::JsonText`
+    /// to keep JSON text where JSONB is supported. Other sinks keep writing JSON text.
+    pub fn with_structured_format(self, format: StructuredFormat) -> Self {
+        Self {
+            structured_format: format,
+            ..self
+        }
+    }
+
+    /// Store `time` in `precision`, e.g. `TimePrecision::Micros` for smaller rows and faster
+    /// range filters. Entries are read back as `OffsetDateTime` either way, see
+    /// [`TimePrecision`] for databases with rows of both. `build_prepared` and
+    /// `build_layer_prepared` fail with `InvalidColumnType` for databases created by versions
+    /// that declared `time` as `TEXT`.
+    pub fn with_time_precision(self, precision: TimePrecision) -> Self {
+        Self {
+            time_precision: precision,
+            ..self
+        }
+    }
+
+    /// Take the timestamps of entries from `clock` instead of the system clock, e.g. for
+    /// reproducible timestamps in tests, see [`Clock`]. Rate limits, coalescing and other
+    /// windows still use `Instant`.
+    pub fn with_clock(self, clock: impl Clock + 'static) -> Self {
+        Self {
+            clock: ClockSource(Box::new(clock)),
+            ..self
+        }
+    }
+
+    /// Record the OpenTelemetry trace and span of each event from `source`, e.g. the context
+    /// `tracing-opentelemetry` keeps for the current span, see [`TraceContextSource`].
+    /// The rows the layer writes itself, e.g. canonical lines and drop markers, have none.
+    #[cfg(feature = "otel")]
+    pub fn with_trace_context(self, source: impl TraceContextSource + 'static) -> Self {
+        Self {
+            trace_context: Some(otel::TraceSource(Box::new(source))),
+            ..self
+        }
+    }
+
+    /// Merge the fields of `provider` into every recorded event, see [`ContextProvider`].
+    /// Providers are called in the order they are added.
+    pub fn with_context_provider(mut self, provider: impl ContextProvider + 'static) -> Self {
+        self.context_providers.push(Provider(Box::new(provider)));
+        self
+    }
+
+    /// Only store the structured fields whose name matches one of `patterns`, the others are
+    /// left out of the entry (the event is still recorded), e.g. to keep large debug payloads
+    /// of third-party libraries out of the `structured` column. Patterns are matched like in
+    /// `with_redaction`, calling this again adds to the list.
+    pub fn with_field_allow_list(
+        mut self,
+        patterns: impl IntoIterator<Item = &'static str>,
+    ) -> Self {
+        self.field_lists.allow(patterns);
+        self
+    }
+
+    /// Leave out the structured fields whose name matches one of `patterns`,
+    /// also if they are in the allow list.
+    pub fn with_field_deny_list(
+        mut self,
+        patterns: impl IntoIterator<Item = &'static str>,
+    ) -> Self {
+        self.field_lists.deny(patterns);
+        self
+    }
+
+    /// Only persist a `rate` fraction (`0.0..=1.0`) of the events at `level`, chosen at random.
+    /// `WARN` and `ERROR` events are always persisted, rates for them are ignored.
+    pub fn with_sampling(mut self, level: tracing::Level, rate: f64) -> Self {
+        if let Some(i) = Sampling::level_index(level) {
+            self.sampling.0[i] = rate.clamp(0.0, 1.0);
+        }
+        self
+    }
+
+    /// Persist a `rate` fraction (`0.0..=1.0`) of traces: the decision is made once per root
+    /// span and inherited by its child spans and events, so traces are either complete or absent.
+    /// Events in kept traces skip `with_sampling`, `WARN` and `ERROR` events are always persisted.
+    pub fn with_span_sampling(self, rate: f64) -> Self {
+        Self {
+            span_sample_rate: Some(rate),
+            ..self
+        }
+    }
+
+    /// Write one wide row per root span when it closes, with the fields of the span, its child
+    /// spans and their events, e.g. one row per request for analytics. The row is named after
+    /// the span, has the most severe level of its events (at least `INFO`), and the
+    /// `canonical.events` and `canonical.duration_us` fields. Events dropped by sampling or
+    /// filters don't contribute.
+    pub fn with_canonical_lines(self, options: CanonicalLineOptions) -> Self {
+        Self {
+            canonical_lines: Some(options),
+            ..self
+        }
+    }
+
+    /// Write at most `per_second` events per second whose target starts with `target`,
+    /// with bursts of up to one second worth of events. The longest matching prefix wins.
+    ///
+    /// Once events are let through again, a row with the number of suppressed events is written first.
+    pub fn with_rate_limit(mut self, target: &'static str, per_second: u32) -> Self {
+        self.rate_limits.retain(|(prefix, _)| *prefix != target);
+        self.rate_limits.push((target, per_second));
+        self
+    }
+
+    /// Suppress an INFO or WARN message for `cooldown` once its callsite emitted it more than
+    /// `max_repeats` times within `window`, so dependency noise doesn't drown the database.
+    ///
+    /// A marker row is written when suppression starts, and one with the number of suppressed
+    /// events before the message is written again.
+    pub fn with_auto_suppression(
+        self,
+        max_repeats: u32,
+        window: Duration,
+        cooldown: Duration,
+    ) -> Self {
+        Self {
+            suppressor: Some(Suppressor::new(max_repeats, window, cooldown)),
+            ..self
+        }
+    }
+
+    /// Persist a marker row per module every `interval` with the number of events dropped by
+    /// sampling and field filters, so analyses know the data is incomplete. Markers are written together
+    /// with the next event after the interval has elapsed.
+    ///
+    /// Rate limited events are reported by the rate limiter's own rows instead.
+    pub fn with_drop_markers(self, interval: Duration) -> Self {
+        Self {
+            drop_marker_interval: Some(interval),
+            ..self
+        }
+    }
+
+    /// Write a row with the layer's [`Stats`] every `interval`, so you can tell from the
+    /// database itself whether the logging pipeline lost data. Like drop markers, the row is
+    /// written together with the next event after the interval has elapsed.
+    pub fn with_stats_interval(self, interval: Duration) -> Self {
+        Self {
+            stats_interval: Some(interval),
+            ..self
+        }
+    }
+
+    /// Collapse identical consecutive events (same level, module and message) within `window`.
+    ///
+    /// The first event is written right away, its repeats are written as one row with their
+    /// number in the `repeat_count` column once a different event, or any event after the window, arrives,
+    /// or when the layer is flushed or dropped.
+    pub fn with_coalescing(self, window: Duration) -> Self {
+        Self {
+            coalesce_window: Some(window),
+            ..self
+        }
+    }
+
+    /// Store `offset` as the writer's UTC offset with every entry, so viewers can show device-local
+    /// times. Timestamps themselves are always stored in UTC.
+    pub fn with_utc_offset(self, offset: UtcOffset) -> Self {
+        Self {
+            utc_offset: Some(offset),
+            ..self
+        }
+    }
+
+    /// Like `with_utc_offset`, with the local offset of the system when this is called.
+    ///
+    /// Detecting the local offset can fail, e.g. on Unix once several threads are running,
+    /// in which case no offset is stored. Call it early in `main`.
+    pub fn with_local_utc_offset(self) -> Self {
+        Self {
+            utc_offset: UtcOffset::current_local_offset().ok(),
+            ..self
+        }
+    }
+
+    /// Call `callback` whenever an entry at `level` or more severe has been written,
+    /// so alerting or pager integrations don't have to poll the database.
+    ///
+    /// The callback runs on the thread that emitted the event and must not emit `tracing` events.
+    pub fn with_alert(
+        self,
+        level: tracing::Level,
+        callback: impl Fn(&LogEntry<&str>) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            alert: Some(Alert {
+                level,
+                callback: Box::new(callback),
+            }),
+            ..self
+        }
+    }
+
+    /// Flush batching sinks like [`BatchConnect`] right after writing an entry at `level`
+    /// or more severe, so it is durable even if the process crashes right after.
+    /// `Some(ERROR)` by default, `None` never flushes.
+    pub fn with_flush_level(self, level: Option<tracing::Level>) -> Self {
+        Self {
+            flush_level: level,
+            ..self
+        }
+    }
+
+    /// Write entries that could not be written to the database to `sink` instead, e.g. a
+    /// [`JsonlConnect`]. The error handler is only called if that fails too.
+    ///
+    /// Only the entry whose write failed reaches `sink`; for the other entries of a failed
+    /// batch use [`BatchConnect::with_fallback`]. Entries the sink keeps to write them later
+    /// (see `Connect::keeps_failed_entries`) don't reach `sink`.
+    pub fn with_fallback(self, sink: impl Connect + Send + Sync + 'static) -> Self {
+        Self {
+            fallback: Some(Fallback(Box::new(sink))),
+            ..self
+        }
+    }
+
+    /// Prepare the database and retry if writes fail because the log table is missing,
+    /// with a warning on stderr (see `Connect::log_or_prepare`). Only until the first write
+    /// succeeds. Enabled by default.
+    pub fn with_auto_prepare(self, enabled: bool) -> Self {
+        Self {
+            auto_prepare: enabled,
+            ..self
+        }
+    }
+
+    /// Called when an entry could not be written. Panics by default.
+    pub fn with_error_handler(
+        self,
+        handler: impl Fn(rusqlite::Error) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            error_handler: ErrorHandler(Box::new(handler)),
+            ..self
+        }
+    }
+
+    /// Where internal diagnostics of the layer are sent, stderr by default.
+    /// The sink must not emit `tracing` events.
+    #[cfg(feature = "self-diagnostics")]
+    pub fn with_diagnostics(self, sink: impl Fn(Diagnostic<'_>) + Send + Sync + 'static) -> Self {
+        Self {
+            diagnostics: diagnostics::Diagnostics::new(sink),
+            ..self
+        }
+    }
+
+    /// Interrupt a write that takes longer than `timeout`, so a wedged filesystem degrades
+    /// logging instead of hanging application threads. The interrupted write is reported to
+    /// the error handler as `SQLITE_INTERRUPT`.
+    ///
+    /// The timeout applies to each statement of the write and starts when the statement does,
+    /// waiting for the connection and for the database lock doesn't count; the connection's
+    /// busy timeout is set to `timeout` to bound the latter. A statement blocked in the file
+    /// system, e.g. in an `fsync` that doesn't return, can only be interrupted once the call
+    /// returns. It uses the connection's progress handler and trace callback, don't replace
+    /// them.
+    /// Only `build_prepared` and `build_layer_prepared` can interrupt the connection,
+    /// other build methods ignore this setting.
+    pub fn with_write_timeout(self, timeout: Duration) -> Self {
+        Self {
+            write_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Set a `PRAGMA` when the database is prepared by `build_prepared` or `build_layer_prepared`.
+    /// Pragmas are applied in the order they are added.
+    pub fn with_pragma(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.pragmas.push((name, value.into()));
+        self
+    }
+
+    /// Promote the structured fields `fields` to indexed columns of `logs_v0` when the
+    /// database is prepared by `build_prepared` or `build_layer_prepared`, see [`promote_fields`].
+    pub fn with_promoted_fields(mut self, fields: &[&'static str]) -> Self {
+        self.promoted_fields.extend(fields);
+        self
+    }
+
+    /// Create views for browsing the database, with a column per structured field in `fields`,
+    /// when it is prepared by `build_prepared` or `build_layer_prepared`, see [`prepare_views`].
+    pub fn with_views(self, fields: &[&'static str]) -> Self {
+        Self {
+            views: Some(fields.to_vec()),
+            ..self
+        }
+    }
+
+    /// Run `PRAGMA quick_check` when `build_prepared` or `build_layer_prepared` prepares the
+    /// database. If it is corrupt (e.g. after a power loss), it is moved aside to
+    /// `<path>.corrupt-<unix time>` with a warning (to the diagnostics sink with the
+    /// `self-diagnostics` feature, stderr otherwise) and a new database is created, instead of
+    /// failing every write. The check reads the whole database.
+    ///
+    /// With an encryption key, a database that can't be read fails instead, as the key may be
+    /// wrong. The new database is opened with `Connection::open`, so open flags of the given
+    /// connection are not kept; the pragmas of `with_pragma` are applied to it as usual.
+    pub fn with_corruption_recovery(self) -> Self {
+        Self {
+            recover_corrupt: true,
+            ..self
+        }
+    }
+
+    /// Checkpoint the WAL file every `interval` from a background thread, so it doesn't grow
+    /// without bound while readers keep the database open, see `LogHandle::checkpoint`.
+    /// Writes wait while a checkpoint runs. Only `build_prepared` and `build_layer_prepared`
+    /// start the thread, other build methods ignore this setting.
+    pub fn with_checkpoint_interval(self, interval: Duration, mode: CheckpointMode) -> Self {
+        Self {
+            checkpoint_interval: Some((interval, mode)),
+            ..self
+        }
+    }
+
+    /// Return the space of deleted rows (e.g. after `delete_expired` or `archive_to`) to the file
+    /// system every `interval` from a background thread, see `LogHandle::reclaim_space`.
+    /// `VacuumPolicy::Incremental` switches the database to incremental auto-vacuum when it is
+    /// prepared, which rebuilds existing databases once. Only `build_prepared` and
+    /// `build_layer_prepared` apply it, other build methods ignore this setting.
+    pub fn with_vacuum_policy(self, policy: VacuumPolicy, interval: Duration) -> Self {
+        Self {
+            vacuum_policy: Some((policy, interval)),
+            ..self
+        }
+    }
+
+    /// Drop the secondary indexes of `logs_v0` while more than `rows_per_second` rows are
+    /// written, checked every `interval` from a background thread, and recreate them once the
+    /// rate is lower again, see `LogHandle::suspend_indexes`. Trades query speed during bursts
+    /// for write throughput. Only `build_prepared` and `build_layer_prepared` start the thread,
+    /// other build methods ignore this setting.
+    pub fn with_burst_index_suspension(self, rows_per_second: u64, interval: Duration) -> Self {
+        Self {
+            burst_index_suspension: Some((rows_per_second, interval)),
+            ..self
+        }
+    }
+
+    /// Delete entries older than `max_age` every `interval` from a background thread, see
+    /// `LogHandle::delete_before`. The newest entry is always kept, so rowids are not reused.
+    /// Only `build_prepared` and `build_layer_prepared` start the thread, other build methods
+    /// ignore this setting.
+    pub fn with_retention(self, max_age: Duration, interval: Duration) -> Self {
+        Self {
+            retention: Some((max_age, interval)),
+            ..self
+        }
+    }
+
+    /// Delete the entries whose time to live has passed every `interval` from a background
+    /// thread, see `LogHandle::delete_expired`. Only `build_prepared` and `build_layer_prepared`
+    /// start the thread, other build methods ignore this setting.
+    pub fn with_expiry_sweep(self, interval: Duration) -> Self {
+        Self {
+            expiry_interval: Some(interval),
+            ..self
+        }
+    }
+
+    /// Update the daily statistics per module every `interval` from a background thread, see
+    /// `LogHandle::rollup_daily_module_stats`. Only `build_prepared` and `build_layer_prepared`
+    /// start the thread, other build methods ignore this setting.
+    pub fn with_daily_rollup(self, interval: Duration) -> Self {
+        Self {
+            rollup_interval: Some(interval),
+            ..self
+        }
+    }
+
+    /// Batch size and maximum delay of the [`BatchConnect`] created by `build_batched` and
+    /// `build_layer_batched`, 100 entries and one second if not set.
+    pub fn with_batching(self, batch_size: usize, max_delay: Duration) -> Self {
+        Self {
+            batching: Some((batch_size, max_delay)),
+            ..self
+        }
+    }
+
+    /// Encrypt the database with SQLCipher, `key` is set with `PRAGMA key` before anything else
+    /// by `build_prepared` and `build_layer_prepared`. Enabled by the `sqlcipher` feature.
+    ///
+    /// Other connections to the database need the key too, see `LogHandle::with_encryption_key`.
+    #[cfg(feature = "sqlcipher")]
+    pub fn with_encryption_key(self, key: impl Into<String>) -> Self {
+        Self {
+            encryption_key: Some(key.into()),
+            ..self
+        }
+    }
+
+    pub fn build<C: Connect>(self, conn: C) -> Subscriber<C> {
         self.build_layer(conn).to_subscriber()
     }
 
@@ -235,12 +1698,68 @@ impl SubscriberBuilder {
         self.build_layer_prepared(conn).map(|l| l.to_subscriber())
     }
 
-    pub fn build_layer<C>(self, conn: C) -> Layer<C> {
+    /// Same as `build_prepared`, writing through a [`BatchConnect`] configured with
+    /// `with_batching`. Settings said to apply only to `build_prepared` apply here too.
+    pub fn build_batched(
+        self,
+        conn: Arc<Mutex<Connection>>,
+    ) -> Result<Subscriber<BatchConnect>, rusqlite::Error> {
+        self.build_layer_batched(conn).map(|l| l.to_subscriber())
+    }
+
+    pub fn build_layer<C: Connect>(self, conn: C) -> Layer<C> {
         Layer {
             logger: conn,
             max_level: self.max_level,
             black_list: self.black_list,
             white_list: self.white_list,
+            target_levels: {
+                let mut target_levels = self.target_levels;
+                target_levels.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+                target_levels.into()
+            },
+            category_rules: self.category_rules.into(),
+            field_filters: self.field_filters.into(),
+            redactor: self.redactor,
+            field_lists: self.field_lists,
+            max_message_len: self.max_message_len,
+            max_field_len: self.max_field_len,
+            structured_format: self.structured_format,
+            time_precision: self.time_precision,
+            clock: self.clock,
+            started: Instant::now(),
+            event_ids: UlidGenerator::default(),
+            #[cfg(feature = "otel")]
+            trace_context: self.trace_context,
+            context_providers: self.context_providers.into(),
+            sampling: self.sampling,
+            spans: (self.span_sample_rate.is_some() || self.canonical_lines.is_some())
+                .then(|| SpanTracker::new(self.span_sample_rate)),
+            canonical: self.canonical_lines.map(CanonicalLines::new),
+            rate_limits: {
+                let mut rate_limits = self.rate_limits;
+                rate_limits.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+                rate_limits
+                    .into_iter()
+                    .map(|(prefix, per_second)| RateLimit::new(prefix, per_second))
+                    .collect()
+            },
+            suppressor: self.suppressor,
+            drop_markers: self.drop_marker_interval.map(DropMarkers::new),
+            stats: StatsHandle::default(),
+            stats_report: self.stats_interval.map(StatsReport::new),
+            coalescer: self.coalesce_window.map(Coalescer::new),
+            utc_offset: self.utc_offset,
+            alert: self.alert,
+            flush_level: self.flush_level,
+            fallback: self.fallback,
+            auto_prepare: AtomicBool::new(self.auto_prepare),
+            prepare_lock: Mutex::new(()),
+            error_handler: self.error_handler,
+            watchdog: None,
+            _maintenance: Vec::new(),
+            #[cfg(feature = "self-diagnostics")]
+            diagnostics: self.diagnostics,
         }
     }
 
@@ -248,18 +1767,205 @@ impl SubscriberBuilder {
         self,
         conn: Arc<Mutex<Connection>>,
     ) -> Result<Layer<Arc<Mutex<Connection>>>, rusqlite::Error> {
-        prepare_database(&conn.lock().unwrap())?;
+        let (watchdog, maintenance) = self.prepare(&conn)?;
+        let mut layer = self.build_layer(conn);
+        layer.watchdog = watchdog;
+        layer._maintenance = maintenance;
+        Ok(layer)
+    }
+
+    /// Same as `build_layer_prepared`, writing through a [`BatchConnect`] configured with
+    /// `with_batching`. Settings said to apply only to `build_layer_prepared` apply here too.
+    pub fn build_layer_batched(
+        self,
+        conn: Arc<Mutex<Connection>>,
+    ) -> Result<Layer<BatchConnect>, rusqlite::Error> {
+        let (watchdog, maintenance) = self.prepare(&conn)?;
+        let (batch_size, max_delay) = self.batching.unwrap_or((100, Duration::from_secs(1)));
+        let mut layer = self.build_layer(BatchConnect::new(conn, batch_size, max_delay));
+        layer.watchdog = watchdog;
+        layer._maintenance = maintenance;
+        // the database is prepared, writes that prepare it would bypass the batch
+        layer.auto_prepare = AtomicBool::new(false);
+        Ok(layer)
+    }
+
+    /// Prepares the database and starts the maintenance threads of the prepared build methods.
+    fn prepare(
+        &self,
+        conn: &Arc<Mutex<Connection>>,
+    ) -> rusqlite::Result<(Option<Watchdog>, Vec<Periodic>)> {
+        let watchdog = {
+            let mut conn = conn.lock().unwrap();
+            let apply_key = |_conn: &Connection| -> rusqlite::Result<()> {
+                #[cfg(feature = "sqlcipher")]
+                if let Some(key) = &self.encryption_key {
+                    _conn.pragma_update(None, "key", key)?;
+                }
+                Ok(())
+            };
+            apply_key(&conn)?;
+            if self.recover_corrupt {
+                #[cfg(feature = "sqlcipher")]
+                let encrypted = self.encryption_key.is_some();
+                #[cfg(not(feature = "sqlcipher"))]
+                let encrypted = false;
+                let recovered = integrity::recover_if_corrupt(&mut conn, encrypted, |path| {
+                    let conn = Connection::open(path)?;
+                    apply_key(&conn)?;
+                    Ok(conn)
+                })?;
+                if let Some((moved_to, problems)) = recovered {
+                    #[cfg(feature = "self-diagnostics")]
+                    self.diagnostics.report(Diagnostic::CorruptionRecovered {
+                        problems: &problems,
+                        moved_to: &moved_to,
+                    });
+                    #[cfg(not(feature = "self-diagnostics"))]
+                    eprintln!(
+                        "tracing-subscriber-sqlite: the log database is corrupt ({}), moved it \
+                         to {} and created a new one.",
+                        problems.join("; "),
+                        moved_to.display()
+                    );
+                }
+            }
+            for (name, value) in &self.pragmas {
+                conn.pragma_update(None, name, value)?;
+            }
+            if let Some((VacuumPolicy::Incremental, _)) = self.vacuum_policy {
+                vacuum::enable_incremental(&conn)?;
+            }
+            prepare_database(&conn)?;
+            promote_fields(&conn, &self.promoted_fields)?;
+            if let Some(fields) = &self.views {
+                prepare_views(&conn, fields)?;
+            }
+            timestamp::check_time_column(&conn, self.time_precision)?;
+
+            match self.write_timeout {
+                Some(timeout) => {
+                    conn.busy_timeout(timeout)?;
+                    Some(Watchdog::new(&mut conn, timeout))
+                }
+                None => None,
+            }
+        };
+
+        let mut maintenance = Vec::new();
+        if let Some((interval, mode)) = self.checkpoint_interval {
+            maintenance.push(Periodic::new(
+                "tracing-sqlite-checkpoint",
+                conn.clone(),
+                interval,
+                // a checkpoint that fails, e.g. because the database is busy, is retried next time
+                move |conn| drop(checkpoint::checkpoint(conn, mode)),
+            ));
+        }
+        if let Some((policy, interval)) = self.vacuum_policy {
+            let min_free_ratio = match policy {
+                VacuumPolicy::Incremental => 0.0,
+                VacuumPolicy::FreeRatio(ratio) => ratio,
+            };
+            maintenance.push(Periodic::new(
+                "tracing-sqlite-vacuum",
+                conn.clone(),
+                interval,
+                move |conn| drop(vacuum::reclaim_space(conn, min_free_ratio)),
+            ));
+        }
+
+        if let Some((rows_per_second, interval)) = self.burst_index_suspension {
+            let rows_per_check = (rows_per_second as f64 * interval.as_secs_f64()) as i64;
+            let detector = Mutex::new(BurstDetector::new(conn.clone(), rows_per_check));
+            maintenance.push(Periodic::new(
+                "tracing-sqlite-bursts",
+                conn.clone(),
+                interval,
+                // a failed check is repeated next time
+                move |conn| drop(detector.lock().unwrap().check(conn)),
+            ));
+        }
+        if let Some((max_age, interval)) = self.retention {
+            maintenance.push(Periodic::new(
+                "tracing-sqlite-retention",
+                conn.clone(),
+                interval,
+                // failed deletes are retried next time
+                move |conn| drop(delete::delete_older_than(conn, max_age)),
+            ));
+        }
+        if let Some(interval) = self.expiry_interval {
+            maintenance.push(Periodic::new(
+                "tracing-sqlite-expiry",
+                conn.clone(),
+                interval,
+                |conn| drop(ttl::delete_expired(conn)),
+            ));
+        }
+        if let Some(interval) = self.rollup_interval {
+            maintenance.push(Periodic::new(
+                "tracing-sqlite-rollup",
+                conn.clone(),
+                interval,
+                // days that failed are computed again next time
+                |conn| drop(rollup::rollup_daily_module_stats(conn)),
+            ));
+        }
 
-        Ok(self.build_layer(conn))
+        Ok((watchdog, maintenance))
     }
 }
 
 impl Default for SubscriberBuilder {
     fn default() -> Self {
         Self {
-            max_level: LevelFilter::DEBUG,
+            max_level: LevelFilter::TRACE,
             black_list: None,
             white_list: None,
+            target_levels: Vec::new(),
+            category_rules: Vec::new(),
+            field_filters: Vec::new(),
+            redactor: Redactor::default(),
+            field_lists: FieldLists::default(),
+            max_message_len: None,
+            max_field_len: None,
+            structured_format: StructuredFormat::Json,
+            time_precision: TimePrecision::Text,
+            clock: ClockSource::default(),
+            #[cfg(feature = "otel")]
+            trace_context: None,
+            context_providers: Vec::new(),
+            sampling: Sampling::default(),
+            span_sample_rate: None,
+            canonical_lines: None,
+            rate_limits: Vec::new(),
+            suppressor: None,
+            drop_marker_interval: None,
+            stats_interval: None,
+            coalesce_window: None,
+            utc_offset: None,
+            alert: None,
+            flush_level: Some(tracing::Level::ERROR),
+            fallback: None,
+            auto_prepare: true,
+            error_handler: ErrorHandler::default(),
+            write_timeout: None,
+            pragmas: Vec::new(),
+            promoted_fields: Vec::new(),
+            views: None,
+            recover_corrupt: false,
+            checkpoint_interval: None,
+            vacuum_policy: None,
+            burst_index_suspension: None,
+            retention: None,
+            rollup_interval: None,
+            expiry_interval: None,
+            batching: None,
+            #[cfg(feature = "sqlcipher")]
+            encryption_key: None,
+            #[cfg(feature = "self-diagnostics")]
+            diagnostics: diagnostics::Diagnostics::default(),
         }
     }
 }
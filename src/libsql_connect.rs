@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+use libsql::{Database, Value};
+
+use crate::{
+    query::time_value,
+    queue::{Queue, Receiver},
+    Connect, LogEntry, SQL_SCHEMA,
+};
+
+/// A `Connect` writing to a remote libsql database (e.g. Turso) from a tokio task, so edge
+/// devices ship their logs to a central database without a collector of their own.
+/// Enabled by the `libsql` feature.
+///
+/// Build the database with `libsql::Builder::new_remote`. Embedded replicas are not supported:
+/// they need libsql's own build of SQLite, which clashes with the one rusqlite bundles when
+/// linking. To keep a local copy as well, tee into a local database, e.g.
+/// `(Arc<Mutex<Connection>>, LibsqlConnect)`.
+///
+/// Like [`TokioConnect`](crate::TokioConnect), queued entries are committed together, errors of
+/// the task are returned by the next `log` call and [`LibsqlConnect::flushed`] waits for
+/// pending entries.
+#[derive(Debug, Clone)]
+pub struct LibsqlConnect {
+    queue: Queue,
+}
+
+impl LibsqlConnect {
+    /// Starts the writer task on the current runtime, panics outside of one.
+    /// The database must be prepared already, see [`LibsqlConnect::prepared`].
+    pub fn new(db: Database) -> libsql::Result<Self> {
+        let conn = db.connect()?;
+        let (queue, receiver) = Queue::new();
+        tokio::spawn(run(conn, receiver));
+        Ok(Self { queue })
+    }
+
+    /// Same as `new`, creating the log table first.
+    ///
+    /// Unlike `prepare_database` this doesn't add columns to tables created by older versions.
+    pub async fn prepared(db: Database) -> libsql::Result<Self> {
+        db.connect()?.execute_batch(SQL_SCHEMA).await?;
+        Self::new(db)
+    }
+
+    /// Let up to `entries` wait for the writer task before new ones are dropped, 10 000 by
+    /// default. Dropped entries are counted in `Stats::dropped`.
+    pub fn with_capacity(self, entries: usize) -> Self {
+        self.queue.set_capacity(entries);
+        self
+    }
+
+    /// Completes once every entry logged before the call has been written.
+    pub async fn flushed(&self) {
+        self.queue.flushed().await
+    }
+}
+
+impl Connect for LibsqlConnect {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self.queue.log(entry)
+    }
+
+    /// Blocks the calling thread, async code should await `flushed` instead.
+    fn flush_and_wait(&self, timeout: Duration) -> rusqlite::Result<()> {
+        self.queue.wait_flushed(timeout)
+    }
+
+    /// Queues `entry` like `log`; the writer task prepares the database before each batch
+    /// until one is written.
+    fn log_or_prepare(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self.queue.log_or_prepare(entry)
+    }
+
+    fn take_dropped(&self) -> u64 {
+        self.queue.take_dropped()
+    }
+}
+
+async fn run(conn: libsql::Connection, mut receiver: Receiver) {
+    while let Some(batch) = receiver.next().await {
+        let result = write(&conn, &batch.entries, batch.prepare)
+            .await
+            // `Connect` reports rusqlite errors, this is the variant that carries arbitrary ones
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)));
+        receiver.done(batch, result);
+    }
+}
+
+/// Stores `module` and `file` as text, there are no interned strings here.
+const INSERT_SQL: &str = "INSERT INTO logs_v0 (time, level, module, file, line, message, \
+    structured, category, utc_offset, repeat_count, origin, expires_at, elapsed_us, event_id, \
+    trace_id, span_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)";
+
+async fn write(
+    conn: &libsql::Connection,
+    entries: &[LogEntry],
+    prepare: bool,
+) -> libsql::Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    if prepare {
+        conn.execute_batch(SQL_SCHEMA).await?;
+    }
+
+    let tx = conn.transaction().await?;
+    for entry in entries {
+        tx.execute(INSERT_SQL, params(entry)).await?;
+    }
+    tx.commit().await
+}
+
+fn params(entry: &LogEntry) -> Vec<Value> {
+    let text = |s: Option<&str>| s.map_or(Value::Null, |s| Value::Text(s.to_owned()));
+    let integer = |i: Option<i64>| i.map_or(Value::Null, Value::Integer);
+    let stored = |value| match value {
+        rusqlite::types::Value::Text(text) => Value::Text(text),
+        rusqlite::types::Value::Integer(i) => Value::Integer(i),
+        _ => Value::Null,
+    };
+    let time = |time| stored(time_value(time));
+
+    vec![
+        stored(entry.time_precision.to_sql(entry.time)),
+        Value::Text(entry.level.as_str().to_owned()),
+        text(entry.module.as_deref()),
+        text(entry.file.as_deref()),
+        integer(entry.line.map(i64::from)),
+        Value::Text(entry.message.clone()),
+        Value::Text(entry.structured.to_json().into_owned()),
+        text(entry.category.map(|c| c.as_str())),
+        integer(entry.utc_offset.map(|o| o.whole_minutes().into())),
+        Value::Integer(entry.repeat_count as i64),
+        text(entry.origin.map(|o| o.as_str())),
+        entry.expires_at.map_or(Value::Null, time),
+        integer(entry.elapsed.map(|e| e.as_micros() as i64)),
+        entry
+            .event_id
+            .map_or(Value::Null, |id| Value::Text(id.to_string())),
+        text(entry.trace_context.map(|c| c.trace_id_hex()).as_deref()),
+        text(entry.trace_context.map(|c| c.span_id_hex()).as_deref()),
+    ]
+}
@@ -0,0 +1,88 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::export::io_error;
+
+/// The lock file `single_writer_guard` creates in the database directory.
+pub const LOCK_FILE: &str = "tracing-sqlite.lock";
+
+/// Held while this process is the only writer of the log databases in a directory,
+/// see [`single_writer_guard`]. Dropping it releases the lock.
+#[derive(Debug)]
+pub struct WriterGuard {
+    path: PathBuf,
+    _file: File,
+}
+
+impl WriterGuard {
+    /// The lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// The error of `single_writer_guard` when another guard holds the lock, in a
+/// `rusqlite::Error::ToSqlConversionFailure`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlreadyLocked {
+    pub path: PathBuf,
+    /// The process holding the lock, if it could be read from the lock file.
+    pub pid: Option<u32>,
+}
+
+impl std::fmt::Display for AlreadyLocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pid {
+            Some(pid) => write!(f, "process {pid} already writes these logs"),
+            None => f.write_str("another process already writes these logs"),
+        }?;
+        write!(f, ", it holds {}", self.path.display())
+    }
+}
+
+impl std::error::Error for AlreadyLocked {}
+
+/// Locks [`LOCK_FILE`] in `dir` (created if needed), so a second instance of the app fails
+/// with [`AlreadyLocked`] at startup instead of competing for the databases in `dir` with
+/// `SQLITE_BUSY` errors. Keep the guard alive as long as the subscriber writes, e.g.
+/// `let _guard = single_writer_guard(dir)?;` in `main`.
+///
+/// The lock is advisory, only processes taking it are kept out. It is released when the
+/// process exits, also when it crashes.
+pub fn single_writer_guard(dir: impl AsRef<Path>) -> rusqlite::Result<WriterGuard> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir).map_err(io_error)?;
+    let path = dir.join(LOCK_FILE);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .map_err(io_error)?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let mut pid = String::new();
+            let pid = file
+                .read_to_string(&mut pid)
+                .ok()
+                .and_then(|_| pid.trim().parse().ok());
+            return Err(rusqlite::Error::ToSqlConversionFailure(Box::new(
+                AlreadyLocked { path, pid },
+            )));
+        }
+        Err(TryLockError::Error(e)) => return Err(io_error(e)),
+    }
+
+    file.set_len(0)
+        .and_then(|()| file.rewind())
+        .and_then(|()| write!(file, "{}", std::process::id()))
+        .and_then(|()| file.flush())
+        .map_err(io_error)?;
+    Ok(WriterGuard { path, _file: file })
+}
@@ -0,0 +1,131 @@
+use std::sync::{Arc, Mutex};
+
+use tracing::Level;
+
+use crate::{
+    store::TailSource, Connect, LogEntry, LogQuery, LogStore, StoreStats, Tail, TailOptions,
+};
+
+/// A `Connect` that keeps entries in memory, useful to test which events your code emits.
+///
+/// Clones share the same storage, so keep one clone around to inspect what the layer wrote.
+/// It is also a [`LogStore`], to test code reading logs without a database. Queries ignore
+/// `LogQuery::table`.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryConnect(Arc<Mutex<Memory>>);
+
+#[derive(Debug, Default)]
+struct Memory {
+    entries: Vec<LogEntry>,
+    /// Number of entries removed by `clear`, so ids keep growing like rowids.
+    cleared: i64,
+}
+
+impl MemoryConnect {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entries(&self) -> Vec<LogEntry> {
+        self.0.lock().unwrap().entries.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        let mut memory = self.0.lock().unwrap();
+        memory.cleared += memory.entries.len() as i64;
+        memory.entries.clear();
+    }
+
+    /// Whether an entry with `level` whose message contains `substring` was logged.
+    pub fn contains(&self, level: Level, substring: &str) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .any(|e| e.level == level && e.message.contains(substring))
+    }
+
+    /// Panics unless an entry with `level` whose message contains `substring` was logged.
+    #[track_caller]
+    pub fn assert_logged(&self, level: Level, substring: &str) {
+        if !self.contains(level, substring) {
+            let memory = self.0.lock().unwrap();
+            let logged: Vec<_> = memory
+                .entries
+                .iter()
+                .map(|e| format!("{} {}", e.level, e.message))
+                .collect();
+            panic!("no {level} entry containing {substring:?} was logged, got: {logged:#?}");
+        }
+    }
+}
+
+impl Connect for MemoryConnect {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self.0.lock().unwrap().entries.push(entry.into_owned());
+        Ok(())
+    }
+}
+
+impl LogStore for MemoryConnect {
+    fn query(&self, query: &LogQuery) -> rusqlite::Result<Vec<LogEntry>> {
+        let memory = self.0.lock().unwrap();
+        Ok(query.apply(memory.entries.iter()))
+    }
+
+    fn tail(&self, query: LogQuery, options: TailOptions) -> rusqlite::Result<Tail> {
+        Tail::new(self.clone(), query, options)
+    }
+
+    fn stats(&self) -> rusqlite::Result<StoreStats> {
+        let memory = self.0.lock().unwrap();
+        Ok(StoreStats {
+            rows: memory.entries.len() as u64,
+            earliest: memory.entries.iter().map(|e| e.time).min(),
+            latest: memory.entries.iter().map(|e| e.time).max(),
+        })
+    }
+}
+
+impl TailSource for MemoryConnect {
+    fn last_id(&self, _query: &LogQuery) -> rusqlite::Result<i64> {
+        let memory = self.0.lock().unwrap();
+        Ok(memory.cleared + memory.entries.len() as i64)
+    }
+
+    fn entries_after(
+        &self,
+        id: i64,
+        query: &LogQuery,
+        limit: u64,
+    ) -> rusqlite::Result<(i64, Vec<LogEntry>)> {
+        let memory = self.0.lock().unwrap();
+        let skip = (id - memory.cleared).max(0) as usize;
+        let matching: Vec<_> = memory
+            .entries
+            .iter()
+            .zip(memory.cleared + 1..)
+            .skip(skip)
+            .filter(|(entry, _)| query.matches(entry))
+            .take(limit as usize)
+            .collect();
+        let scanned = match matching.last() {
+            Some((_, last)) if matching.len() as u64 == limit => *last,
+            _ => id.max(memory.cleared + memory.entries.len() as i64),
+        };
+        let entries = matching
+            .into_iter()
+            .map(|(entry, _)| query.project(entry.clone()))
+            .collect();
+        Ok((scanned, entries))
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::Path;
+
+use rusqlite::Connection;
+
+use crate::{db::interned, format::STRUCTURED_JSON, Column, LogHandle, LOGS_TABLE};
+
+/// How `LogHandle::merge_from` copies rows.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MergeOptions {
+    /// Skip rows whose structured field with this name has a value that a row in this database
+    /// already has, e.g. a unique event id. Rows without the field are always copied.
+    pub dedup_field: Option<&'static str>,
+}
+
+impl LogHandle {
+    /// Copies the `logs_v0` rows of the log database at `path` into this one in time order,
+    /// e.g. to analyze the databases of several devices together. Returns the number of rows
+    /// copied.
+    ///
+    /// Rows whose `event_id` this database already has are skipped, so merging a database
+    /// again only copies its new rows. `path` is only read. Columns it doesn't have yet
+    /// (databases of older versions) get their defaults. Runs in one transaction on the
+    /// connection the handle writes through.
+    pub fn merge_from(
+        &self,
+        path: impl AsRef<Path>,
+        options: MergeOptions,
+    ) -> rusqlite::Result<u64> {
+        let path = path.as_ref();
+        let path = path
+            .to_str()
+            .ok_or_else(|| rusqlite::Error::InvalidPath(path.to_owned()))?;
+
+        let conn = self.writer();
+        conn.execute("ATTACH DATABASE ?1 AS merge_source", [path])?;
+        let result = merge_attached(&conn, options);
+
+        let detached = conn.execute_batch("DETACH DATABASE merge_source");
+        let copied = result?;
+        detached?;
+        Ok(copied)
+    }
+}
+
+/// Copies the rows of the database attached as `merge_source`.
+fn merge_attached(conn: &Connection, options: MergeOptions) -> rusqlite::Result<u64> {
+    let existing = conn
+        .prepare("SELECT name FROM pragma_table_info(?1, 'merge_source')")?
+        .query_map([LOGS_TABLE], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let columns = Column::ALL.map(|c| c.name());
+    let has = |name: &str| existing.iter().any(|c| c == name);
+    let select = columns
+        .iter()
+        .map(|&name| match name {
+            "module" | "file" if has(&format!("{name}_id")) => interned(name, "merge_source"),
+            _ if has(name) => name.to_owned(),
+            "repeat_count" => "1".to_owned(),
+            _ => "NULL".to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut sql = format!(
+        "INSERT INTO main.{LOGS_TABLE} ({}) SELECT {select} FROM merge_source.{LOGS_TABLE}",
+        columns.join(", "),
+    );
+    let mut conditions = Vec::new();
+    let mut params = Vec::new();
+    if has("event_id") {
+        conditions.push(format!(
+            "(event_id IS NULL OR event_id NOT IN \
+             (SELECT event_id FROM main.{LOGS_TABLE} WHERE event_id IS NOT NULL))"
+        ));
+    }
+    if let Some(field) = options.dedup_field {
+        let value = format!("({STRUCTURED_JSON}) ->> ?1");
+        conditions.push(format!(
+            "({value} IS NULL OR {value} NOT IN \
+             (SELECT {value} FROM main.logs_v0 WHERE {value} IS NOT NULL))"
+        ));
+        params.push(format!("$.\"{}\"", field.replace('"', "\\\"")));
+    }
+    if !conditions.is_empty() {
+        sql += &format!(" WHERE {}", conditions.join(" AND "));
+    }
+    sql += " ORDER BY time, rowid";
+
+    let tx = conn.unchecked_transaction()?;
+    let copied = tx.execute(&sql, rusqlite::params_from_iter(params))?;
+    tx.commit()?;
+    Ok(copied as u64)
+}
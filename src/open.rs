@@ -0,0 +1,162 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rusqlite::Connection;
+
+use crate::{prepare_database, Connect, LogEntry};
+
+/// How often and how patiently to try opening the database, e.g. when it lives on a network
+/// mount that appears late at boot.
+///
+/// The delay between attempts starts at `initial_backoff` and doubles up to `max_backoff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenRetry {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl OpenRetry {
+    /// Up to `max_attempts` attempts (at least one), 100ms apart at first and at most 5s.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+
+    pub fn with_backoff(self, initial: Duration, max: Duration) -> Self {
+        Self {
+            initial_backoff: initial,
+            max_backoff: max.max(initial),
+            ..self
+        }
+    }
+
+    /// The delay after the `failures`th failed attempt.
+    fn backoff(&self, failures: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+
+    /// Opens and prepares the database at `path`, sleeping between failed attempts.
+    /// Returns the error of the last attempt.
+    pub fn open(&self, path: impl AsRef<Path>) -> rusqlite::Result<Connection> {
+        let mut failures = 0;
+        loop {
+            match open_prepared(path.as_ref()) {
+                Ok(conn) => return Ok(conn),
+                Err(e) => {
+                    failures += 1;
+                    if failures >= self.max_attempts {
+                        return Err(e);
+                    }
+                    std::thread::sleep(self.backoff(failures));
+                }
+            }
+        }
+    }
+
+    /// A `Connect` that opens the database on the first event instead, see [`LazyConnect`].
+    pub fn lazy(self, path: impl Into<PathBuf>) -> LazyConnect {
+        LazyConnect {
+            path: path.into(),
+            retry: self,
+            state: Mutex::default(),
+        }
+    }
+}
+
+impl Default for OpenRetry {
+    /// 10 attempts.
+    fn default() -> Self {
+        Self::new(10)
+    }
+}
+
+fn open_prepared(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    prepare_database(&conn)?;
+    Ok(conn)
+}
+
+/// A `Connect` that opens and prepares the database on the first event, so a service can start
+/// before the database is reachable. Created with [`OpenRetry::lazy`].
+///
+/// Events never wait for a retry: while the database can't be opened, and until the backoff
+/// of the last attempt has passed, their entries fail with `SQLITE_CANTOPEN` and go to the
+/// fallback or the error handler. After `max_attempts` failed attempts it stops trying.
+#[derive(Debug)]
+pub struct LazyConnect {
+    path: PathBuf,
+    retry: OpenRetry,
+    state: Mutex<LazyState>,
+}
+
+#[derive(Debug, Default)]
+struct LazyState {
+    conn: Option<Connection>,
+    failures: u32,
+    next_attempt: Option<Instant>,
+}
+
+impl LazyConnect {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the database has been opened.
+    pub fn is_open(&self) -> bool {
+        self.state.lock().unwrap().conn.is_some()
+    }
+
+    /// Runs `f` with the connection, opening it first if an attempt is due.
+    fn with_conn(
+        &self,
+        f: impl FnOnce(&Connection) -> rusqlite::Result<()>,
+    ) -> rusqlite::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.conn.is_none() {
+            if state.failures >= self.retry.max_attempts {
+                return Err(cant_open("gave up opening the database"));
+            }
+            if state.next_attempt.is_some_and(|next| Instant::now() < next) {
+                return Err(cant_open("waiting to retry opening the database"));
+            }
+
+            match open_prepared(&self.path) {
+                Ok(conn) => state.conn = Some(conn),
+                Err(e) => {
+                    state.failures += 1;
+                    state.next_attempt = Some(Instant::now() + self.retry.backoff(state.failures));
+                    return Err(e);
+                }
+            }
+        }
+
+        f(state.conn.as_ref().unwrap())
+    }
+}
+
+fn cant_open(message: &str) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN),
+        Some(message.to_owned()),
+    )
+}
+
+impl Connect for LazyConnect {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self.with_conn(|conn| conn.log(entry))
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        // opening prepares the database
+        self.with_conn(|_| Ok(()))
+    }
+}
@@ -0,0 +1,76 @@
+/// The OpenTelemetry trace and span an event was recorded in, stored as lowercase hex in the
+/// `trace_id` and `span_id` columns like in W3C `traceparent` headers, so rows can be joined
+/// with the traces an APM shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceContext {
+    pub trace_id: u128,
+    pub span_id: u64,
+}
+
+impl TraceContext {
+    /// The 32 hex digits of `trace_id`.
+    pub fn trace_id_hex(&self) -> String {
+        format!("{:032x}", self.trace_id)
+    }
+
+    /// The 16 hex digits of `span_id`.
+    pub fn span_id_hex(&self) -> String {
+        format!("{:016x}", self.span_id)
+    }
+
+    /// Parses the stored columns, `None` unless both are valid.
+    pub(crate) fn from_hex(trace_id: Option<&str>, span_id: Option<&str>) -> Option<Self> {
+        Some(Self {
+            trace_id: u128::from_str_radix(trace_id?, 16).ok()?,
+            span_id: u64::from_str_radix(span_id?, 16).ok()?,
+        })
+    }
+}
+
+/// Looks up the trace context of the current span, see
+/// `SubscriberBuilder::with_trace_context`.
+///
+/// Closures implement it. With `tracing-opentelemetry` in the stack, the ids come from the
+/// span's OpenTelemetry context:
+///
+/// ```text
+/// use opentelemetry::trace::TraceContextExt;
+/// use tracing_opentelemetry::OpenTelemetrySpanExt;
+///
+/// let builder = SubscriberBuilder::new().with_trace_context(|| {
+///     let context = tracing::Span::current().context();
+///     let span = context.span();
+///     let ids = span.span_context();
+///     ids.is_valid().then(|| TraceContext {
+///         trace_id: u128::from_be_bytes(ids.trace_id().to_bytes()),
+///         span_id: u64::from_be_bytes(ids.span_id().to_bytes()),
+///     })
+/// });
+/// ```
+///
+/// The crate doesn't depend on `opentelemetry` itself, so any of its versions works.
+#[cfg(feature = "otel")]
+pub trait TraceContextSource: Send + Sync {
+    /// Called on the thread that records the event, `None` outside of a sampled trace.
+    fn current(&self) -> Option<TraceContext>;
+}
+
+#[cfg(feature = "otel")]
+impl<F> TraceContextSource for F
+where
+    F: Fn() -> Option<TraceContext> + Send + Sync,
+{
+    fn current(&self) -> Option<TraceContext> {
+        self()
+    }
+}
+
+#[cfg(feature = "otel")]
+pub(crate) struct TraceSource(pub(crate) Box<dyn TraceContextSource>);
+
+#[cfg(feature = "otel")]
+impl std::fmt::Debug for TraceSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TraceContextSource")
+    }
+}
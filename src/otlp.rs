@@ -0,0 +1,342 @@
+use std::io;
+
+use hyper::{client::HttpConnector, header, Body, Client, Request, Uri};
+use prost::Message;
+use serde_json::Value;
+
+use crate::{
+    export::io_error,
+    profile::{open_telemetry_attributes, severity_number},
+    LogEntry, LogHandle, LogQuery,
+};
+
+/// An `ExportLogsServiceRequest` of the OTLP protocol, see `LogHandle::otlp_logs`.
+///
+/// The messages are those of `opentelemetry-proto`, with the fields this crate fills, so they
+/// can also be sent with another client, e.g. over gRPC.
+#[derive(Clone, PartialEq, Message)]
+pub struct OtlpLogsRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub resource_logs: Vec<OtlpResourceLogs>,
+}
+
+impl OtlpLogsRequest {
+    /// `entries` as the logs of `resource`, in one scope named after this crate.
+    pub fn new(resource: OtlpResource, entries: &[LogEntry]) -> Self {
+        Self {
+            resource_logs: vec![OtlpResourceLogs {
+                resource: Some(resource),
+                scope_logs: vec![OtlpScopeLogs {
+                    scope: Some(OtlpScope {
+                        name: env!("CARGO_PKG_NAME").to_owned(),
+                        version: env!("CARGO_PKG_VERSION").to_owned(),
+                        ..OtlpScope::default()
+                    }),
+                    log_records: entries.iter().map(OtlpLogRecord::from).collect(),
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+        }
+    }
+
+    /// The records of all resources and scopes.
+    pub fn log_records(&self) -> impl Iterator<Item = &OtlpLogRecord> {
+        self.resource_logs
+            .iter()
+            .flat_map(|r| &r.scope_logs)
+            .flat_map(|s| &s.log_records)
+    }
+
+    /// The protobuf encoding, the body of an OTLP/HTTP request.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encode_to_vec()
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct OtlpResourceLogs {
+    #[prost(message, optional, tag = "1")]
+    pub resource: Option<OtlpResource>,
+    #[prost(message, repeated, tag = "2")]
+    pub scope_logs: Vec<OtlpScopeLogs>,
+    #[prost(string, tag = "3")]
+    pub schema_url: String,
+}
+
+/// The application the logs come from, e.g. `OtlpResource::service("checkout")`.
+#[derive(Clone, PartialEq, Message)]
+pub struct OtlpResource {
+    #[prost(message, repeated, tag = "1")]
+    pub attributes: Vec<OtlpKeyValue>,
+    #[prost(uint32, tag = "2")]
+    pub dropped_attributes_count: u32,
+}
+
+impl OtlpResource {
+    /// A resource with the `service.name` attribute.
+    pub fn service(name: impl Into<String>) -> Self {
+        Self::default().with_attribute("service.name", name)
+    }
+
+    /// Adds a string attribute, e.g. `service.version` or `host.name`.
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes
+            .push(OtlpKeyValue::new(key, OtlpValue::String(value.into())));
+        self
+    }
+}
+
+/// An `InstrumentationScope`.
+#[derive(Clone, PartialEq, Message)]
+pub struct OtlpScope {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub version: String,
+    #[prost(message, repeated, tag = "3")]
+    pub attributes: Vec<OtlpKeyValue>,
+    #[prost(uint32, tag = "4")]
+    pub dropped_attributes_count: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct OtlpScopeLogs {
+    #[prost(message, optional, tag = "1")]
+    pub scope: Option<OtlpScope>,
+    #[prost(message, repeated, tag = "2")]
+    pub log_records: Vec<OtlpLogRecord>,
+    #[prost(string, tag = "3")]
+    pub schema_url: String,
+}
+
+/// One entry, with the attributes of `ExportProfile::OpenTelemetry`.
+#[derive(Clone, PartialEq, Message)]
+pub struct OtlpLogRecord {
+    #[prost(fixed64, tag = "1")]
+    pub time_unix_nano: u64,
+    #[prost(int32, tag = "2")]
+    pub severity_number: i32,
+    #[prost(string, tag = "3")]
+    pub severity_text: String,
+    #[prost(message, optional, tag = "5")]
+    pub body: Option<OtlpAnyValue>,
+    #[prost(message, repeated, tag = "6")]
+    pub attributes: Vec<OtlpKeyValue>,
+    #[prost(uint32, tag = "7")]
+    pub dropped_attributes_count: u32,
+    #[prost(fixed32, tag = "8")]
+    pub flags: u32,
+    /// 16 bytes, empty without a trace context.
+    #[prost(bytes = "vec", tag = "9")]
+    pub trace_id: Vec<u8>,
+    /// 8 bytes, empty without a trace context.
+    #[prost(bytes = "vec", tag = "10")]
+    pub span_id: Vec<u8>,
+    #[prost(fixed64, tag = "11")]
+    pub observed_time_unix_nano: u64,
+}
+
+impl From<&LogEntry> for OtlpLogRecord {
+    fn from(entry: &LogEntry) -> Self {
+        // times before 1970 can't be represented
+        let time = u64::try_from(entry.time.unix_timestamp_nanos()).unwrap_or(0);
+        Self {
+            time_unix_nano: time,
+            severity_number: severity_number(entry.level).into(),
+            severity_text: entry.level.as_str().to_owned(),
+            body: Some(OtlpValue::String(entry.message.clone()).into()),
+            attributes: open_telemetry_attributes(entry)
+                .into_iter()
+                .map(|(key, value)| OtlpKeyValue::new(key, OtlpValue::from(value)))
+                .collect(),
+            dropped_attributes_count: 0,
+            flags: 0,
+            trace_id: entry
+                .trace_context
+                .map_or_else(Vec::new, |c| c.trace_id.to_be_bytes().to_vec()),
+            span_id: entry
+                .trace_context
+                .map_or_else(Vec::new, |c| c.span_id.to_be_bytes().to_vec()),
+            observed_time_unix_nano: time,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct OtlpKeyValue {
+    #[prost(string, tag = "1")]
+    pub key: String,
+    #[prost(message, optional, tag = "2")]
+    pub value: Option<OtlpAnyValue>,
+}
+
+impl OtlpKeyValue {
+    pub fn new(key: impl Into<String>, value: OtlpValue) -> Self {
+        Self {
+            key: key.into(),
+            value: Some(value.into()),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct OtlpAnyValue {
+    #[prost(oneof = "OtlpValue", tags = "1, 2, 3, 4")]
+    pub value: Option<OtlpValue>,
+}
+
+impl From<OtlpValue> for OtlpAnyValue {
+    fn from(value: OtlpValue) -> Self {
+        Self { value: Some(value) }
+    }
+}
+
+/// The kinds of values the layer records, structured fields are typed like in
+/// `ExportProfile::OpenTelemetry`.
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum OtlpValue {
+    #[prost(string, tag = "1")]
+    String(String),
+    #[prost(bool, tag = "2")]
+    Bool(bool),
+    #[prost(int64, tag = "3")]
+    Int(i64),
+    #[prost(double, tag = "4")]
+    Double(f64),
+}
+
+impl From<Value> for OtlpValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Bool(b) => OtlpValue::Bool(b),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => OtlpValue::Int(i),
+                None => OtlpValue::Double(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            Value::String(s) => OtlpValue::String(s),
+            other => OtlpValue::String(other.to_string()),
+        }
+    }
+}
+
+impl LogHandle {
+    /// The entries matching `query` as an OTLP request with the logs of `resource`, e.g. to
+    /// send them with another client. `OtlpExporter` ships them in batches instead.
+    pub fn otlp_logs(
+        &self,
+        query: &LogQuery,
+        resource: OtlpResource,
+    ) -> rusqlite::Result<OtlpLogsRequest> {
+        Ok(OtlpLogsRequest::new(resource, &self.query(query)?))
+    }
+}
+
+/// Drains a log database to an OTLP/HTTP endpoint, e.g. an OpenTelemetry collector, with the
+/// shipping watermark of `LogHandle::unshipped`, so the database works as a durable buffer.
+///
+/// ```no_run
+/// # use tracing_subscriber_sqlite::{LogHandle, LogQuery, OtlpExporter, OtlpResource};
+/// # async fn ship(handle: &LogHandle) -> rusqlite::Result<()> {
+/// let exporter = OtlpExporter::new("http://localhost:4318/v1/logs")?
+///     .with_resource(OtlpResource::service("checkout"));
+/// exporter.drain(handle, &LogQuery::new()).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OtlpExporter {
+    client: Client<HttpConnector>,
+    endpoint: Uri,
+    name: String,
+    resource: OtlpResource,
+    batch_size: u64,
+}
+
+impl OtlpExporter {
+    /// Posts to `endpoint`, the full URL including `/v1/logs`. Only `http` is supported,
+    /// e.g. with a collector on the same host that forwards over TLS.
+    pub fn new(endpoint: &str) -> rusqlite::Result<Self> {
+        let endpoint: Uri = endpoint
+            .parse()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        if endpoint.scheme_str() != Some("http") {
+            return Err(io_error(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported OTLP endpoint {endpoint}, only http is supported"),
+            )));
+        }
+        Ok(Self {
+            client: Client::new(),
+            endpoint,
+            name: "otlp".to_owned(),
+            resource: OtlpResource::default(),
+            batch_size: 512,
+        })
+    }
+
+    /// The exporter name of the shipping watermark, `otlp` by default. Each endpoint needs its
+    /// own.
+    pub fn with_name(self, name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..self
+        }
+    }
+
+    pub fn with_resource(self, resource: OtlpResource) -> Self {
+        Self { resource, ..self }
+    }
+
+    /// At most `batch_size` entries are sent per request, 512 by default.
+    pub fn with_batch_size(self, batch_size: u64) -> Self {
+        Self {
+            batch_size: batch_size.max(1),
+            ..self
+        }
+    }
+
+    /// Sends `request` and fails unless the endpoint accepts it.
+    pub async fn push(&self, request: &OtlpLogsRequest) -> rusqlite::Result<()> {
+        let request = Request::post(&self.endpoint)
+            .header(header::CONTENT_TYPE, "application/x-protobuf")
+            .body(Body::from(request.to_bytes()))
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let response = self
+            .client
+            .request(request)
+            .await
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap_or_default();
+        Err(io_error(io::Error::other(format!(
+            "OTLP endpoint answered {status}: {}",
+            String::from_utf8_lossy(&body)
+        ))))
+    }
+
+    /// Ships the entries matching `query` that this exporter hasn't shipped yet, oldest first,
+    /// acknowledging each batch once the endpoint accepted it. Returns the number of entries
+    /// shipped. Entries are sent at least once, a batch is sent again if the process stops
+    /// before its acknowledgement.
+    ///
+    /// Reading and acknowledging batches blocks the task for as long as the queries take.
+    pub async fn drain(&self, handle: &LogHandle, query: &LogQuery) -> rusqlite::Result<u64> {
+        let mut shipped = 0;
+        loop {
+            let batch = handle.unshipped(&self.name, query, self.batch_size)?;
+            if batch.entries.is_empty() {
+                return Ok(shipped);
+            }
+            self.push(&OtlpLogsRequest::new(self.resource.clone(), &batch.entries))
+                .await?;
+            handle.ack_shipped(&self.name, batch.end)?;
+            shipped += batch.entries.len() as u64;
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{db::entry_from_row, Column, LogEntry, LogHandle, LogQuery, Order};
+
+/// One page of entries from `LogHandle::query_page`.
+#[derive(Debug, Clone)]
+pub struct Page<T = LogEntry> {
+    pub entries: Vec<T>,
+    /// Whether more entries match after this page.
+    pub has_more: bool,
+    /// Where the next page starts, pass it to `LogQuery::after`. `None` on the last page.
+    pub next: Option<Cursor>,
+    /// About how many entries match the query overall, extrapolated from the share of rows
+    /// that matched while reading this page. Exact if all of them fit on one page.
+    pub total_estimate: u64,
+}
+
+/// A position in the log, between the pages of a query. Formats as, and parses from, an opaque
+/// string, e.g. for a query parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cursor {
+    pub(crate) rowid: i64,
+}
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "r{}", self.rowid)
+    }
+}
+
+impl std::str::FromStr for Cursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_prefix('r')
+            .and_then(|rowid| rowid.parse().ok())
+            .map(|rowid| Cursor { rowid })
+            .ok_or_else(|| format!("invalid cursor {s:?}"))
+    }
+}
+
+impl LogHandle {
+    /// Up to `size` entries matching `query`, starting after its cursor (see `LogQuery::after`),
+    /// with what a UI needs to page through the rest. `limit` and `offset` of the query are
+    /// ignored.
+    ///
+    /// Reads one more row than the page holds, the estimate doesn't count the table.
+    pub fn query_page(&self, query: &LogQuery, size: u64) -> rusqlite::Result<Page> {
+        let query = self.scoped(query);
+        let (where_clause, params) = query.where_clause();
+        let table = query.table_name();
+        let sql = format!(
+            "SELECT {}, rowid FROM {table}{where_clause} ORDER BY rowid{} LIMIT {}",
+            query.select_list(),
+            match query.ordering() {
+                Order::OldestFirst => "",
+                Order::NewestFirst => " DESC",
+            },
+            size.saturating_add(1),
+        );
+
+        self.with_reader(|conn| {
+            let mut rows = conn
+                .prepare(&sql)?
+                .query_map(rusqlite::params_from_iter(params), |row| {
+                    Ok((
+                        row.get::<_, i64>(Column::ALL.len())?,
+                        query.present(entry_from_row(row)?),
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            let (min, max): (Option<i64>, Option<i64>) = conn.query_row(
+                &format!("SELECT min(rowid), max(rowid) FROM {table}"),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            let (min, max) = match (min, max) {
+                (Some(min), Some(max)) => (min, max.min(query.max_rowid_bound().unwrap_or(max))),
+                _ => (0, -1),
+            };
+
+            let has_more = rows.len() as u64 > size;
+            // the rows read for this page, from the cursor to the last match, or the end
+            let matched = rows.len() as u64;
+            let (start, end) = match query.ordering() {
+                Order::OldestFirst => (
+                    query.after_rowid().map_or(min, |rowid| rowid + 1),
+                    match rows.last() {
+                        Some((rowid, _)) if has_more => *rowid,
+                        _ => max,
+                    },
+                ),
+                Order::NewestFirst => (
+                    match rows.last() {
+                        Some((rowid, _)) if has_more => *rowid,
+                        _ => min,
+                    },
+                    query.after_rowid().map_or(max, |rowid| rowid - 1),
+                ),
+            };
+            let scanned = (end - start + 1).max(0) as u64;
+            let total_rows = (max - min + 1).max(0) as u64;
+            let total_estimate = if scanned == 0 || scanned >= total_rows {
+                matched
+            } else {
+                (matched as f64 / scanned as f64 * total_rows as f64).round() as u64
+            };
+
+            rows.truncate(size as usize);
+            Ok(Page {
+                next: rows
+                    .last()
+                    .filter(|_| has_more)
+                    .map(|&(rowid, _)| Cursor { rowid }),
+                entries: rows.into_iter().map(|(_, entry)| entry).collect(),
+                has_more,
+                total_estimate,
+            })
+        })
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use rusqlite::Connection;
+
+use crate::{prepare_database, Connect, LogEntry};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// This thread's connections, by the id of the `PerThreadConnect` that opened them.
+    static CONNECTIONS: RefCell<HashMap<u64, Connection>> = RefCell::new(HashMap::new());
+}
+
+/// A `Connect` opening one connection to the database at `path` per writer thread, so threads
+/// don't queue up on one `Mutex<Connection>`.
+///
+/// The database is put in WAL mode and prepared once by `open`. SQLite still allows one writer
+/// at a time, concurrent writes wait for each other with the busy timeout (5 seconds).
+/// A thread's connection is closed when the thread exits.
+#[derive(Debug)]
+pub struct PerThreadConnect {
+    id: u64,
+    path: PathBuf,
+}
+
+impl PerThreadConnect {
+    /// Opens the database at `path`, creating and preparing it if needed.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let conn = Connection::open(&path)?;
+        // the journal mode is stored in the database, connections opened later use it too
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        prepare_database(&conn)?;
+
+        let this = Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            path,
+        };
+        CONNECTIONS.with(|conns| conns.borrow_mut().insert(this.id, conn));
+        Ok(this)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Runs `f` with this thread's connection, opening it first if needed.
+    fn with_conn<T>(
+        &self,
+        f: impl FnOnce(&Connection) -> rusqlite::Result<T>,
+    ) -> rusqlite::Result<T> {
+        CONNECTIONS.with(|conns| {
+            let mut conns = conns.borrow_mut();
+            let conn = match conns.entry(self.id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(Connection::open(&self.path)?),
+            };
+            f(conn)
+        })
+    }
+}
+
+impl Connect for PerThreadConnect {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self.with_conn(|conn| conn.log(entry))
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        self.with_conn(prepare_database)
+    }
+}
+
+impl Drop for PerThreadConnect {
+    fn drop(&mut self) {
+        // connections of other threads are closed when those exit
+        let _ = CONNECTIONS.try_with(|conns| conns.borrow_mut().remove(&self.id));
+    }
+}
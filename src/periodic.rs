@@ -0,0 +1,83 @@
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use rusqlite::Connection;
+
+/// Runs a maintenance job on the connection every `interval` on its own thread, until dropped.
+pub(crate) struct Periodic {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    name: &'static str,
+    stopped: Mutex<bool>,
+    cond: Condvar,
+    conn: Arc<Mutex<Connection>>,
+    interval: Duration,
+    job: Box<dyn Fn(&Connection) + Send + Sync>,
+}
+
+impl Periodic {
+    /// `name` is the name of the thread, e.g. `tracing-sqlite-checkpoint`.
+    pub(crate) fn new(
+        name: &'static str,
+        conn: Arc<Mutex<Connection>>,
+        interval: Duration,
+        job: impl Fn(&Connection) + Send + Sync + 'static,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            name,
+            stopped: Mutex::new(false),
+            cond: Condvar::new(),
+            conn,
+            interval,
+            job: Box::new(job),
+        });
+
+        let running = shared.clone();
+        std::thread::Builder::new()
+            .name(name.into())
+            .spawn(move || running.run())
+            .expect("failed to spawn maintenance thread");
+
+        Self { shared }
+    }
+}
+
+impl Shared {
+    fn run(&self) {
+        let mut stopped = self.stopped.lock().unwrap();
+        let mut next = Instant::now() + self.interval;
+        loop {
+            if *stopped {
+                return;
+            }
+
+            let now = Instant::now();
+            if now < next {
+                stopped = self.cond.wait_timeout(stopped, next - now).unwrap().0;
+                continue;
+            }
+            (self.job)(&self.conn.lock().unwrap());
+            next = Instant::now() + self.interval;
+        }
+    }
+}
+
+impl Drop for Periodic {
+    fn drop(&mut self) {
+        *self.shared.stopped.lock().unwrap() = true;
+        self.shared.cond.notify_one();
+    }
+}
+
+impl std::fmt::Debug for Periodic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Periodic")
+            .field("name", &self.shared.name)
+            .field("interval", &self.shared.interval)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,27 @@
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+
+use crate::{prepare_database, Connect, LogEntry};
+
+/// Writes through a connection checked out of the pool, so writer threads don't queue up on
+/// one `Mutex<Connection>`. Enabled by the `r2d2` feature.
+///
+/// SQLite still allows only one writer at a time, use WAL mode (e.g. with
+/// `SqliteConnectionManager::with_init`) so pooled readers don't block it.
+impl Connect for Pool<SqliteConnectionManager> {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        Connect::log(&*pooled(self)?, entry)
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        prepare_database(&*pooled(self)?)
+    }
+}
+
+fn pooled(
+    pool: &Pool<SqliteConnectionManager>,
+) -> rusqlite::Result<r2d2::PooledConnection<SqliteConnectionManager>> {
+    // `Connect` reports rusqlite errors, this is the variant that carries arbitrary ones
+    pool.get()
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
@@ -0,0 +1,166 @@
+use serde_json::{json, Map, Value};
+use time::format_description::well_known::Rfc3339;
+
+use crate::{jsonl::entry_to_json, FieldValue, LogEntry};
+
+/// The field names exported entries use, see `LogHandle::export_jsonl_as`.
+///
+/// `Ecs` and `OpenTelemetry` write structured field values as JSON numbers, booleans and
+/// strings instead of the text the layer stores.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ExportProfile {
+    /// The column names of this crate, the format of [`JsonlConnect`](crate::JsonlConnect)
+    /// that `import_jsonl` reads.
+    #[default]
+    Native,
+    /// Elastic Common Schema: `@timestamp`, `log.level`, `log.logger`, `message`, ... with the
+    /// structured fields at the top level, unless they clash with those.
+    Ecs,
+    /// The OpenTelemetry log data model: `Timestamp` (nanoseconds since the Unix epoch),
+    /// `SeverityText`, `SeverityNumber`, `Body`, the structured fields in `Attributes`, and
+    /// `TraceId` and `SpanId` if the entry has a trace context.
+    OpenTelemetry,
+}
+
+impl ExportProfile {
+    /// `entry` as one line of JSON, without a line break.
+    pub fn format(&self, entry: &LogEntry) -> String {
+        self.json(entry).to_string()
+    }
+
+    pub(crate) fn json(&self, entry: &LogEntry) -> Value {
+        match self {
+            ExportProfile::Native => entry_to_json(&entry.to_borrowed()),
+            ExportProfile::Ecs => ecs(entry),
+            ExportProfile::OpenTelemetry => open_telemetry(entry),
+        }
+    }
+}
+
+fn ecs(entry: &LogEntry) -> Value {
+    let mut labels = Map::new();
+    if let Some(category) = entry.category {
+        labels.insert("error_category".into(), category.as_str().into());
+    }
+    if entry.repeat_count > 1 {
+        labels.insert("repeat_count".into(), entry.repeat_count.into());
+    }
+    if let Some(origin) = entry.origin {
+        labels.insert("origin".into(), origin.as_str().into());
+    }
+
+    let mut object = Map::new();
+    object.insert("@timestamp".into(), rfc3339(entry.time));
+    object.insert(
+        "log.level".into(),
+        entry.level.as_str().to_ascii_lowercase().into(),
+    );
+    object.insert("message".into(), entry.message.as_str().into());
+    object.insert("ecs.version".into(), "8.11.0".into());
+    let optional = [
+        ("log.logger", entry.module.as_deref().map(Value::from)),
+        (
+            "log.origin.file.name",
+            entry.file.as_deref().map(Value::from),
+        ),
+        ("log.origin.file.line", entry.line.map(Value::from)),
+        (
+            "event.timezone",
+            entry.utc_offset.map(|o| Value::from(o.to_string())),
+        ),
+        (
+            "labels",
+            (!labels.is_empty()).then_some(Value::Object(labels)),
+        ),
+    ];
+    for (key, value) in optional {
+        if let Some(value) = value {
+            object.insert(key.into(), value);
+        }
+    }
+    for (name, value) in entry.structured.fields() {
+        if !object.contains_key(name) {
+            object.insert(name.clone(), typed(value));
+        }
+    }
+    Value::Object(object)
+}
+
+fn open_telemetry(entry: &LogEntry) -> Value {
+    let mut record = json!({
+        // a string, JSON numbers can't hold every 64-bit value exactly
+        "Timestamp": entry.time.unix_timestamp_nanos().to_string(),
+        "SeverityText": entry.level.as_str(),
+        "SeverityNumber": severity_number(entry.level),
+        "Body": entry.message,
+        "Attributes": open_telemetry_attributes(entry),
+    });
+    if let Some(context) = entry.trace_context {
+        record["TraceId"] = context.trace_id_hex().into();
+        record["SpanId"] = context.span_id_hex().into();
+    }
+    record
+}
+
+/// The structured fields and the columns without their own field in the OpenTelemetry log
+/// data model, with the names of its semantic conventions.
+pub(crate) fn open_telemetry_attributes(entry: &LogEntry) -> Map<String, Value> {
+    let mut attributes: Map<String, Value> = entry
+        .structured
+        .fields()
+        .iter()
+        .map(|(name, value)| (name.clone(), typed(value)))
+        .collect();
+    let optional = [
+        ("code.namespace", entry.module.as_deref().map(Value::from)),
+        ("code.filepath", entry.file.as_deref().map(Value::from)),
+        ("code.lineno", entry.line.map(Value::from)),
+        (
+            "error.category",
+            entry.category.map(|c| Value::from(c.as_str())),
+        ),
+        (
+            "log.repeat_count",
+            (entry.repeat_count > 1).then(|| Value::from(entry.repeat_count)),
+        ),
+        (
+            "log.record.uid",
+            entry.event_id.map(|id| Value::from(id.to_string())),
+        ),
+    ];
+    for (key, value) in optional {
+        if let Some(value) = value {
+            attributes.insert(key.into(), value);
+        }
+    }
+    attributes
+}
+
+/// The first severity number of the range of each level.
+pub(crate) fn severity_number(level: tracing::Level) -> u8 {
+    match level {
+        tracing::Level::TRACE => 1,
+        tracing::Level::DEBUG => 5,
+        tracing::Level::INFO => 9,
+        tracing::Level::WARN => 13,
+        tracing::Level::ERROR => 17,
+    }
+}
+
+/// A stored field value as a typed JSON value.
+fn typed(value: &str) -> Value {
+    // `Debug` escapes strings mostly like JSON does
+    if let Ok(text) = serde_json::from_str::<String>(value) {
+        return text.into();
+    }
+    match FieldValue::parse(value) {
+        FieldValue::Bool(b) => b.into(),
+        FieldValue::Int(i) => i.into(),
+        FieldValue::Float(f) => f.into(),
+        FieldValue::Str(s) => s.into(),
+    }
+}
+
+fn rfc3339(time: time::OffsetDateTime) -> Value {
+    time.format(&Rfc3339).ok().into()
+}
@@ -0,0 +1,53 @@
+use rusqlite::Connection;
+
+use crate::{query::field_expression, LOGS_TABLE};
+
+/// Adds a column per structured field in `fields` to `logs_v0`, named like the field and
+/// indexed, e.g. to look up `request_id` or `user_id` quickly or to use them in SQL tools.
+/// Fields already promoted are skipped.
+///
+/// The columns are generated by SQLite from `structured`, they take no space besides their
+/// index and need no changes to sinks. Strings are unquoted, numbers and booleans are stored
+/// as such, other values as recorded. Called by `SubscriberBuilder::build_prepared` with the
+/// fields of `with_promoted_fields`, call it after `prepare_database` for other sinks.
+pub fn promote_fields(conn: &Connection, fields: &[&str]) -> rusqlite::Result<()> {
+    let existing = conn
+        .prepare("SELECT name, hidden FROM pragma_table_xinfo(?1)")?
+        .query_map([LOGS_TABLE], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? != 0))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for field in fields {
+        match existing.iter().find(|(name, _)| name == field) {
+            Some((_, true)) => continue,
+            Some((_, false)) => {
+                return Err(rusqlite::Error::InvalidColumnName(format!(
+                    "`{field}` can't be promoted, `{LOGS_TABLE}` has a column with that name"
+                )))
+            }
+            None => {}
+        }
+
+        let value = field_expression(field);
+        let column = format!("\"{}\"", field.replace('"', "\"\""));
+        let index = format!("\"{LOGS_TABLE}_{}\"", field.replace('"', "\"\""));
+        conn.execute_batch(&format!(
+            "ALTER TABLE {LOGS_TABLE} ADD COLUMN {column} GENERATED ALWAYS AS (
+                CASE WHEN json_valid(structured, 5) THEN
+                    CASE WHEN json_valid({value}) THEN {value} ->> '$' ELSE {value} END
+                END
+            ) VIRTUAL;
+            CREATE INDEX IF NOT EXISTS {index} ON {LOGS_TABLE} ({column});"
+        ))?;
+    }
+    Ok(())
+}
+
+/// The promoted fields of `logs_v0`, see [`promote_fields`].
+pub fn promoted_fields(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    // generated columns are the hidden ones of `table_xinfo`
+    conn.prepare("SELECT name FROM pragma_table_xinfo(?1) WHERE hidden != 0 ORDER BY cid")?
+        .query_map([LOGS_TABLE], |row| row.get(0))?
+        .collect()
+}
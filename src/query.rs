@@ -0,0 +1,678 @@
+use rusqlite::types::Value;
+use time::OffsetDateTime;
+use tracing::Level;
+
+use crate::{
+    db::interned,
+    format::STRUCTURED_JSON,
+    timestamp::{time_condition, time_params},
+    LogEntry, Structured, Timezone, Ulid,
+};
+
+/// A column of the `logs_v0` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Column {
+    Time,
+    Level,
+    Module,
+    File,
+    Line,
+    Message,
+    Structured,
+    Category,
+    UtcOffset,
+    RepeatCount,
+    Origin,
+    ExpiresAt,
+    Elapsed,
+    EventId,
+    TraceId,
+    SpanId,
+}
+
+impl Column {
+    /// Every column, in the order `LogEntry` is read from a row.
+    pub const ALL: [Column; 16] = [
+        Column::Time,
+        Column::Level,
+        Column::Module,
+        Column::File,
+        Column::Line,
+        Column::Message,
+        Column::Structured,
+        Column::Category,
+        Column::UtcOffset,
+        Column::RepeatCount,
+        Column::Origin,
+        Column::ExpiresAt,
+        Column::Elapsed,
+        Column::EventId,
+        Column::TraceId,
+        Column::SpanId,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Time => "time",
+            Column::Level => "level",
+            Column::Module => "module",
+            Column::File => "file",
+            Column::Line => "line",
+            Column::Message => "message",
+            Column::Structured => "structured",
+            Column::Category => "category",
+            Column::UtcOffset => "utc_offset",
+            Column::RepeatCount => "repeat_count",
+            Column::Origin => "origin",
+            Column::ExpiresAt => "expires_at",
+            Column::Elapsed => "elapsed_us",
+            Column::EventId => "event_id",
+            Column::TraceId => "trace_id",
+            Column::SpanId => "span_id",
+        }
+    }
+}
+
+/// Filters for reading entries with `LogHandle::query`.
+///
+/// ```
+/// # use tracing_subscriber_sqlite::{Column, LogQuery};
+/// let query = LogQuery::new()
+///     .max_level(tracing::Level::WARN)
+///     .module("my_app::db")
+///     .select(&[Column::Time, Column::Level, Column::Message])
+///     .limit(200);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LogQuery {
+    table: Option<&'static str>,
+    columns: Option<Vec<Column>>,
+    max_level: Option<Level>,
+    module: Option<String>,
+    since: Option<OffsetDateTime>,
+    until: Option<OffsetDateTime>,
+    message_contains: Option<String>,
+    origin: Option<crate::Origin>,
+    fields: Vec<FieldMatch>,
+    max_rowid: Option<i64>,
+    after: Option<i64>,
+    event_id: Option<Ulid>,
+    trace_id: Option<u128>,
+    order: Order,
+    limit: Option<u64>,
+    offset: Option<u64>,
+    timezone: Timezone,
+}
+
+impl LogQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read from another table with the `logs_v0` schema, e.g. [`ERRORS_TABLE`](crate::ERRORS_TABLE).
+    pub fn table(self, table: &'static str) -> Self {
+        Self {
+            table: Some(table),
+            ..self
+        }
+    }
+
+    pub(crate) fn table_name(&self) -> &'static str {
+        self.table.unwrap_or(crate::LOGS_TABLE)
+    }
+
+    /// Only read these columns, the other fields of the returned entries are left empty
+    /// (`None`, an empty message or empty structured data). `time` and `level` are always read.
+    ///
+    /// Leaving out `Column::Structured` saves parsing its JSON for every row.
+    pub fn select(self, columns: &[Column]) -> Self {
+        Self {
+            columns: Some(columns.to_vec()),
+            ..self
+        }
+    }
+
+    /// Entries at `level` or more severe, e.g. `Level::WARN` for warnings and errors.
+    pub fn max_level(self, level: Level) -> Self {
+        Self {
+            max_level: Some(level),
+            ..self
+        }
+    }
+
+    /// Entries whose module path starts with `module`.
+    pub fn module(self, module: impl Into<String>) -> Self {
+        Self {
+            module: Some(module.into()),
+            ..self
+        }
+    }
+
+    /// Entries logged at or after `time`.
+    pub fn since(self, time: OffsetDateTime) -> Self {
+        Self {
+            since: Some(time),
+            ..self
+        }
+    }
+
+    /// Entries logged before `time`.
+    pub fn until(self, time: OffsetDateTime) -> Self {
+        Self {
+            until: Some(time),
+            ..self
+        }
+    }
+
+    /// Entries whose message contains `text`.
+    pub fn message_contains(self, text: impl Into<String>) -> Self {
+        Self {
+            message_contains: Some(text.into()),
+            ..self
+        }
+    }
+
+    /// Entries from `origin`, e.g. only `log` records in a database shared with `tracing`.
+    pub fn origin(self, origin: crate::Origin) -> Self {
+        Self {
+            origin: Some(origin),
+            ..self
+        }
+    }
+
+    /// The entry with this id, e.g. one referenced from another database.
+    pub fn event_id(self, id: Ulid) -> Self {
+        Self {
+            event_id: Some(id),
+            ..self
+        }
+    }
+
+    /// Entries recorded in this OpenTelemetry trace, see `LogEntry::trace_context`.
+    pub fn trace_id(self, trace_id: u128) -> Self {
+        Self {
+            trace_id: Some(trace_id),
+            ..self
+        }
+    }
+
+    /// Entries that recorded the structured field `name` with `value`, e.g.
+    /// `.field("user_id", 42).field("ok", false)`. Can be repeated, all of them have to match.
+    ///
+    /// Fields that are searched often can be indexed with `LogHandle::index_field`.
+    pub fn field(self, name: impl Into<String>, value: impl Into<FieldValue>) -> Self {
+        self.field_match(FieldMatch {
+            name: name.into(),
+            value: value.into(),
+        })
+    }
+
+    /// Same as `field`, e.g. with a filter parsed from a command line argument.
+    pub fn field_match(mut self, filter: FieldMatch) -> Self {
+        self.fields.push(filter);
+        self
+    }
+
+    /// Only rows up to `rowid`, see `LogHandle::as_of`.
+    pub(crate) fn max_rowid(self, rowid: i64) -> Self {
+        Self {
+            max_rowid: Some(rowid),
+            ..self
+        }
+    }
+
+    /// Entries after `cursor` in the order of the query, see `LogHandle::query_page`.
+    pub fn after(self, cursor: crate::Cursor) -> Self {
+        Self {
+            after: Some(cursor.rowid),
+            ..self
+        }
+    }
+
+    /// Entries up to and including `cursor`, e.g. the backlog of a tail from `Tail::position`.
+    pub fn up_to(self, cursor: crate::Cursor) -> Self {
+        self.max_rowid(cursor.rowid)
+    }
+
+    /// The rowid bound of `after`, exclusive.
+    pub(crate) fn after_rowid(&self) -> Option<i64> {
+        self.after
+    }
+
+    /// The rowid bound of `LogHandle::as_of`, inclusive.
+    pub(crate) fn max_rowid_bound(&self) -> Option<i64> {
+        self.max_rowid
+    }
+
+    pub(crate) fn ordering(&self) -> Order {
+        self.order
+    }
+
+    /// With `Order::NewestFirst` and a limit, only the newest rows are read, e.g. for the last
+    /// 200 lines.
+    pub fn order(self, order: Order) -> Self {
+        Self { order, ..self }
+    }
+
+    pub fn limit(self, limit: u64) -> Self {
+        Self {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    pub fn offset(self, offset: u64) -> Self {
+        Self {
+            offset: Some(offset),
+            ..self
+        }
+    }
+
+    /// Return `time` and `expires_at` in `timezone` instead of UTC, e.g. `Timezone::local()`
+    /// for a desktop viewer. Filters like `since` compare instants and are not affected.
+    pub fn timezone(self, timezone: Timezone) -> Self {
+        Self { timezone, ..self }
+    }
+
+    /// `entry` with its times in the timezone of the query.
+    pub(crate) fn present(&self, entry: LogEntry) -> LogEntry {
+        if self.timezone == Timezone::Utc {
+            return entry;
+        }
+        let offset = self.timezone.offset(entry.utc_offset);
+        LogEntry {
+            time: entry.time.to_offset(offset),
+            expires_at: entry.expires_at.map(|t| t.to_offset(offset)),
+            ..entry
+        }
+    }
+
+    fn is_selected(&self, column: Column) -> bool {
+        matches!(column, Column::Time | Column::Level)
+            || self.columns.as_ref().is_none_or(|c| c.contains(&column))
+    }
+
+    /// Whether `entry` passes the filters, for backends that don't use SQL.
+    pub(crate) fn matches(&self, entry: &LogEntry) -> bool {
+        self.max_level.is_none_or(|level| entry.level <= level)
+            && self.module.as_ref().is_none_or(|module| {
+                entry
+                    .module
+                    .as_ref()
+                    .is_some_and(|m| m.starts_with(module.as_str()))
+            })
+            && self.since.is_none_or(|since| entry.time >= since)
+            && self.until.is_none_or(|until| entry.time < until)
+            && self
+                .message_contains
+                .as_ref()
+                .is_none_or(|text| entry.message.contains(text.as_str()))
+            && self
+                .origin
+                .is_none_or(|origin| entry.origin == Some(origin))
+            && self.event_id.is_none_or(|id| entry.event_id == Some(id))
+            && self
+                .trace_id
+                .is_none_or(|id| entry.trace_context.is_some_and(|c| c.trace_id == id))
+            && self.fields.iter().all(|filter| {
+                entry.structured.get(&filter.name) == Some(filter.value.stored().as_str())
+            })
+    }
+
+    /// `entry` with the columns that are not selected left empty, as if read from the database.
+    pub(crate) fn project(&self, entry: LogEntry) -> LogEntry {
+        let keep = |column| self.is_selected(column);
+        let entry = self.present(entry);
+        LogEntry {
+            module: entry.module.filter(|_| keep(Column::Module)),
+            file: entry.file.filter(|_| keep(Column::File)),
+            line: entry.line.filter(|_| keep(Column::Line)),
+            message: if keep(Column::Message) {
+                entry.message
+            } else {
+                String::new()
+            },
+            structured: if keep(Column::Structured) {
+                entry.structured
+            } else {
+                Structured::default()
+            },
+            category: entry.category.filter(|_| keep(Column::Category)),
+            utc_offset: entry.utc_offset.filter(|_| keep(Column::UtcOffset)),
+            repeat_count: if keep(Column::RepeatCount) {
+                entry.repeat_count
+            } else {
+                1
+            },
+            origin: entry.origin.filter(|_| keep(Column::Origin)),
+            expires_at: entry.expires_at.filter(|_| keep(Column::ExpiresAt)),
+            elapsed: entry.elapsed.filter(|_| keep(Column::Elapsed)),
+            event_id: entry.event_id.filter(|_| keep(Column::EventId)),
+            trace_context: entry
+                .trace_context
+                .filter(|_| keep(Column::TraceId) && keep(Column::SpanId)),
+            ..entry
+        }
+    }
+
+    /// The matching `entries` (oldest first) with the order, `limit` and `offset` applied, for
+    /// backends that don't use SQL.
+    pub(crate) fn apply<'a>(
+        &self,
+        entries: impl DoubleEndedIterator<Item = &'a LogEntry>,
+    ) -> Vec<LogEntry> {
+        let entries: Box<dyn Iterator<Item = _>> = match self.order {
+            Order::OldestFirst => Box::new(entries),
+            Order::NewestFirst => Box::new(entries.rev()),
+        };
+        entries
+            .filter(|entry| self.matches(entry))
+            .skip(self.offset.unwrap_or(0) as usize)
+            .take(self.limit.map_or(usize::MAX, |limit| limit as usize))
+            .map(|entry| self.project(entry.clone()))
+            .collect()
+    }
+
+    /// The `SELECT` list, with `NULL` for columns that are not selected.
+    pub(crate) fn select_list(&self) -> String {
+        Column::ALL
+            .iter()
+            .map(|&c| match c {
+                Column::Module | Column::File if self.is_selected(c) => {
+                    interned(c.name(), "main")
+                }
+                // JSONB rows are read as JSON text, like the ones written before
+                Column::Structured if self.is_selected(c) => {
+                    "CASE WHEN typeof(structured) = 'blob' AND hex(substr(structured, 1, 1)) LIKE '_C' \
+                     THEN json(structured) ELSE structured END"
+                        .into()
+                }
+                _ if self.is_selected(c) => c.name().into(),
+                _ => "NULL".into(),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// The `WHERE` clause (empty if there are no filters) and its parameters.
+    pub(crate) fn where_clause(&self) -> (String, Vec<Value>) {
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+
+        if let Some(max_level) = self.max_level {
+            let levels = [
+                Level::ERROR,
+                Level::WARN,
+                Level::INFO,
+                Level::DEBUG,
+                Level::TRACE,
+            ]
+            .into_iter()
+            .filter(|l| *l <= max_level)
+            .map(|l| format!("'{}'", l.as_str()))
+            .collect::<Vec<_>>()
+            .join(", ");
+            conditions.push(format!("level IN ({levels})"));
+        }
+        if let Some(module) = &self.module {
+            conditions.push(format!(
+                "substr({}, 1, length(?)) = ?",
+                interned("module", "main")
+            ));
+            params.push(Value::Text(module.clone()));
+            params.push(Value::Text(module.clone()));
+        }
+        if let Some(since) = self.since {
+            conditions.push(time_condition(">="));
+            params.extend(time_params(since));
+        }
+        if let Some(until) = self.until {
+            conditions.push(time_condition("<"));
+            params.extend(time_params(until));
+        }
+        if let Some(text) = &self.message_contains {
+            conditions.push("instr(message, ?) > 0".to_owned());
+            params.push(Value::Text(text.clone()));
+        }
+        if let Some(origin) = self.origin {
+            conditions.push("origin = ?".to_owned());
+            params.push(Value::Text(origin.as_str().to_owned()));
+        }
+        for filter in &self.fields {
+            // the path is inlined so indexes from `LogHandle::index_field` apply
+            let value = field_expression(&filter.name);
+            let (typed, json_type) = match filter.value {
+                FieldValue::Bool(b) => (Value::Integer(b.into()), if b { "true" } else { "false" }),
+                FieldValue::Int(i) => (Value::Integer(i), "integer"),
+                FieldValue::Float(f) => (Value::Real(f), "real"),
+                FieldValue::Str(_) => {
+                    conditions.push(format!("{value} = ?"));
+                    params.push(Value::Text(filter.value.stored()));
+                    continue;
+                }
+            };
+            // `json_extract` returns `true` as 1, and older versions stored numbers and
+            // booleans as strings
+            conditions.push(format!(
+                "({value} = ? OR ({value} = ? AND {} = ?))",
+                field_expression_of("json_type", &filter.name)
+            ));
+            params.push(Value::Text(filter.value.stored()));
+            params.push(typed);
+            params.push(Value::Text(json_type.to_owned()));
+        }
+        if let Some(id) = self.event_id {
+            conditions.push("event_id = ?".to_owned());
+            params.push(Value::Text(id.to_string()));
+        }
+        if let Some(id) = self.trace_id {
+            conditions.push("trace_id = ?".to_owned());
+            params.push(Value::Text(format!("{id:032x}")));
+        }
+        if let Some(rowid) = self.max_rowid {
+            conditions.push("rowid <= ?".to_owned());
+            params.push(Value::Integer(rowid));
+        }
+        if let Some(rowid) = self.after {
+            conditions.push(match self.order {
+                Order::OldestFirst => "rowid > ?".to_owned(),
+                Order::NewestFirst => "rowid < ?".to_owned(),
+            });
+            params.push(Value::Integer(rowid));
+        }
+
+        if conditions.is_empty() {
+            (String::new(), params)
+        } else {
+            (format!(" WHERE {}", conditions.join(" AND ")), params)
+        }
+    }
+
+    /// The whole statement with its parameters.
+    pub(crate) fn to_sql(&self) -> (String, Vec<Value>) {
+        let (where_clause, params) = self.where_clause();
+        let mut sql = format!(
+            "SELECT {} FROM {}{where_clause} ORDER BY rowid{}",
+            self.select_list(),
+            self.table_name(),
+            match self.order {
+                Order::OldestFirst => "",
+                Order::NewestFirst => " DESC",
+            },
+        );
+        match (self.limit, self.offset) {
+            (Some(limit), Some(offset)) => sql += &format!(" LIMIT {limit} OFFSET {offset}"),
+            (Some(limit), None) => sql += &format!(" LIMIT {limit}"),
+            (None, Some(offset)) => sql += &format!(" LIMIT -1 OFFSET {offset}"),
+            (None, None) => {}
+        }
+        (sql, params)
+    }
+}
+
+/// The filters a `LogServer` request can narrow.
+#[cfg(feature = "http")]
+impl LogQuery {
+    pub(crate) fn level_filter(&self) -> Option<Level> {
+        self.max_level
+    }
+
+    pub(crate) fn module_filter(&self) -> Option<&str> {
+        self.module.as_deref()
+    }
+
+    pub(crate) fn time_filter(&self) -> (Option<OffsetDateTime>, Option<OffsetDateTime>) {
+        (self.since, self.until)
+    }
+
+    pub(crate) fn message_filter(&self) -> Option<&str> {
+        self.message_contains.as_deref()
+    }
+
+    pub(crate) fn trace_id_filter(&self) -> Option<u128> {
+        self.trace_id
+    }
+}
+
+/// The SQL expression extracting the structured field `name`, from JSON text and JSONB alike.
+pub(crate) fn field_expression(name: &str) -> String {
+    format!("({STRUCTURED_JSON}) ->> '{}'", field_path(name))
+}
+
+/// `function` (e.g. `json_type`) applied to the structured field `name`.
+fn field_expression_of(function: &str, name: &str) -> String {
+    format!("{function}({STRUCTURED_JSON}, '{}')", field_path(name))
+}
+
+/// The JSON path of the structured field `name`, quoted for an SQL string literal.
+pub(crate) fn field_path(name: &str) -> String {
+    format!("$.\"{}\"", name.replace('"', "\\\"")).replace('\'', "''")
+}
+
+/// The order `LogQuery` returns entries in, by when they were written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Order {
+    #[default]
+    OldestFirst,
+    NewestFirst,
+}
+
+/// A structured field condition, see `LogQuery::field`.
+///
+/// Parses from `name=value` as given on command lines and in query strings, see
+/// [`FieldValue::parse`] for the value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMatch {
+    pub name: String,
+    pub value: FieldValue,
+}
+
+impl std::str::FromStr for FieldMatch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, value)) if !name.is_empty() => Ok(FieldMatch {
+                name: name.to_owned(),
+                value: FieldValue::parse(value),
+            }),
+            _ => Err(format!("expected `name=value`, got {s:?}")),
+        }
+    }
+}
+
+/// The typed value of a [`FieldMatch`].
+///
+/// Recorded integers, floats and booleans are stored as JSON numbers and booleans, other
+/// fields as the `Debug` representation of the value. So `Int(42)` matches `user_id = 42` but
+/// not `user_id = "42"`, which matches `Str("42")`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl FieldValue {
+    /// `true` and `false` are booleans, then integers and floats, anything else is a string.
+    /// Double quotes make a string of anything, e.g. `"42"`.
+    pub fn parse(text: &str) -> Self {
+        if let Some(quoted) = text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+            return FieldValue::Str(quoted.to_owned());
+        }
+        match text {
+            "true" => FieldValue::Bool(true),
+            "false" => FieldValue::Bool(false),
+            _ => text
+                .parse()
+                .map(FieldValue::Int)
+                .or_else(|_| text.parse().map(FieldValue::Float))
+                .unwrap_or_else(|_| FieldValue::Str(text.to_owned())),
+        }
+    }
+
+    /// The value as `Structured::get` returns it.
+    pub fn stored(&self) -> String {
+        match self {
+            FieldValue::Bool(b) => b.to_string(),
+            FieldValue::Int(i) => i.to_string(),
+            FieldValue::Float(f) => format!("{f:?}"),
+            FieldValue::Str(s) => format!("{s:?}"),
+        }
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(value: bool) -> Self {
+        FieldValue::Bool(value)
+    }
+}
+
+impl From<i64> for FieldValue {
+    fn from(value: i64) -> Self {
+        FieldValue::Int(value)
+    }
+}
+
+impl From<i32> for FieldValue {
+    fn from(value: i32) -> Self {
+        FieldValue::Int(value.into())
+    }
+}
+
+impl From<u32> for FieldValue {
+    fn from(value: u32) -> Self {
+        FieldValue::Int(value.into())
+    }
+}
+
+impl From<f64> for FieldValue {
+    fn from(value: f64) -> Self {
+        FieldValue::Float(value)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(value: &str) -> Self {
+        FieldValue::Str(value.to_owned())
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(value: String) -> Self {
+        FieldValue::Str(value)
+    }
+}
+
+/// `time` as text (UTC), which sorts chronologically: how `TimePrecision::Text` stores it and
+/// how the other sinks and older versions do.
+pub(crate) fn time_value(time: OffsetDateTime) -> Value {
+    use rusqlite::types::{ToSql, ToSqlOutput};
+
+    match time.to_offset(time::UtcOffset::UTC).to_sql() {
+        Ok(ToSqlOutput::Owned(value)) => value,
+        Ok(ToSqlOutput::Borrowed(value)) => value.into(),
+        _ => unreachable!("OffsetDateTime is always converted to text"),
+    }
+}
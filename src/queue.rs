@@ -0,0 +1,296 @@
+use std::{
+    future::poll_fn,
+    task::{Poll, Waker},
+    time::Duration,
+};
+
+#[cfg(shuttle)]
+use shuttle::sync::{Arc, Condvar, Mutex};
+#[cfg(not(shuttle))]
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::LogEntry;
+
+#[cfg(all(test, shuttle))]
+mod tests;
+
+/// Entries a queue holds by default before it drops new ones, see `Queue::set_capacity`.
+pub(crate) const DEFAULT_CAPACITY: usize = 10_000;
+
+/// The sending side of the async sinks: entries go to a writer task, its errors come back
+/// through the next `log` call.
+///
+/// Built on `Mutex` and `Condvar` only, so the concurrency tests can swap in shuttle's
+/// (`RUSTFLAGS="--cfg shuttle" cargo test --features tokio --lib`).
+#[derive(Debug)]
+pub(crate) struct Queue {
+    shared: Arc<Shared>,
+}
+
+/// The writer task's side of a [`Queue`].
+#[derive(Debug)]
+pub(crate) struct Receiver {
+    shared: Arc<Shared>,
+}
+
+/// The entries that queued up while the task was busy, to be written together.
+pub(crate) struct Batch {
+    pub(crate) entries: Vec<LogEntry>,
+    /// Whether the writer should prepare the database first, see `Queue::log_or_prepare`.
+    pub(crate) prepare: bool,
+    /// Number of entries queued up to and including this batch.
+    end: u64,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    /// Signalled when entries are queued or the last `Queue` is dropped.
+    changed: Condvar,
+    /// Signalled when a batch is done or the receiver is dropped, for `wait_flushed`.
+    written: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    entries: Vec<LogEntry>,
+    /// Entries queued and entries written (or failed) since the start.
+    queued: u64,
+    done: u64,
+    error: Option<rusqlite::Error>,
+    /// Set by `log_or_prepare` until a batch is written.
+    prepare: bool,
+    /// Most entries waiting for the writer, and the entries dropped because there were as
+    /// many since `take_dropped` was called last.
+    capacity: usize,
+    dropped: u64,
+    /// `flushed` calls waiting for `done` to reach the number of entries queued before them.
+    flushing: Vec<(u64, Waker)>,
+    /// The async receiver waiting for entries.
+    receiving: Option<Waker>,
+    senders: usize,
+    stopped: bool,
+}
+
+impl Queue {
+    pub(crate) fn new() -> (Self, Receiver) {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                senders: 1,
+                capacity: DEFAULT_CAPACITY,
+                ..State::default()
+            }),
+            changed: Condvar::new(),
+            written: Condvar::new(),
+        });
+        let receiver = Receiver {
+            shared: Arc::clone(&shared),
+        };
+        (Self { shared }, receiver)
+    }
+
+    pub(crate) fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self.push(entry, false)
+    }
+
+    /// `log`, with the writer preparing the database before each batch until one is written.
+    /// The queue sinks can't reach their connection from here, and waiting for the writer
+    /// from inside the runtime could block it.
+    pub(crate) fn log_or_prepare(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self.push(entry, true)
+    }
+
+    fn push(&self, entry: LogEntry<&str>, prepare: bool) -> rusqlite::Result<()> {
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some(e) = state.error.take() {
+            return Err(e);
+        }
+        if state.stopped {
+            return Err(stopped());
+        }
+        // a writer that can't keep up must not take all the memory, the newest entries go
+        if state.entries.len() >= state.capacity {
+            state.dropped += 1;
+            return Ok(());
+        }
+
+        state.entries.push(entry.into_owned());
+        state.queued += 1;
+        state.prepare |= prepare;
+        state.wake_receiver();
+        self.shared.changed.notify_one();
+        Ok(())
+    }
+
+    /// Hold at most `capacity` entries for the writer, further ones are dropped until it
+    /// took a batch.
+    pub(crate) fn set_capacity(&self, capacity: usize) {
+        self.shared.state.lock().unwrap().capacity = capacity.max(1);
+    }
+
+    /// The entries dropped because the queue was full since the last call.
+    pub(crate) fn take_dropped(&self) -> u64 {
+        std::mem::take(&mut self.shared.state.lock().unwrap().dropped)
+    }
+
+    /// Completes once every entry logged before the call has been written.
+    pub(crate) async fn flushed(&self) {
+        let target = self.shared.state.lock().unwrap().queued;
+        poll_fn(|cx| {
+            let mut state = self.shared.state.lock().unwrap();
+            if state.done >= target || state.stopped {
+                Poll::Ready(())
+            } else {
+                state.flushing.push((target, cx.waker().clone()));
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Blocks until every entry logged before the call has been written, reporting the error
+    /// of a failed batch. Fails with `SQLITE_BUSY` after `timeout`.
+    pub(crate) fn wait_flushed(&self, timeout: Duration) -> rusqlite::Result<()> {
+        let state = self.shared.state.lock().unwrap();
+        let target = state.queued;
+        let (mut state, _) = self
+            .shared
+            .written
+            .wait_timeout_while(state, timeout, |state| {
+                state.done < target && !state.stopped
+            })
+            .unwrap();
+
+        if let Some(e) = state.error.take() {
+            Err(e)
+        } else if state.done >= target {
+            Ok(())
+        } else if state.stopped {
+            Err(stopped())
+        } else {
+            Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+                Some(format!(
+                    "the writer task did not catch up within {timeout:?}"
+                )),
+            ))
+        }
+    }
+}
+
+fn stopped() -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ABORT),
+        Some("the writer task has stopped".into()),
+    )
+}
+
+impl Clone for Queue {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().senders += 1;
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Drop for Queue {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.senders -= 1;
+        if state.senders == 0 {
+            state.wake_receiver();
+            self.shared.changed.notify_one();
+        }
+    }
+}
+
+impl Receiver {
+    /// The next batch, `None` once every `Queue` is dropped and the last entries were taken.
+    pub(crate) async fn next(&mut self) -> Option<Batch> {
+        poll_fn(|cx| {
+            let mut state = self.shared.state.lock().unwrap();
+            match state.take_batch() {
+                Some(batch) => Poll::Ready(batch),
+                None => {
+                    state.receiving = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    /// `next` for writers on a thread of their own, like the ones of the concurrency tests.
+    #[cfg(all(test, shuttle))]
+    pub(crate) fn blocking_next(&mut self) -> Option<Batch> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(batch) = state.take_batch() {
+                return batch;
+            }
+            state = self.shared.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Reports the result of writing `batch` and wakes up `flushed` and `wait_flushed` calls
+    /// waiting for it.
+    pub(crate) fn done(&self, batch: Batch, result: rusqlite::Result<()>) {
+        let mut state = self.shared.state.lock().unwrap();
+        match result {
+            Ok(()) => state.prepare = false,
+            Err(e) => state.error = Some(e),
+        }
+        state.done = batch.end;
+        let done = state.done;
+        state.flushing.retain(|(target, waker)| {
+            if *target <= done {
+                waker.wake_by_ref();
+            }
+            *target > done
+        });
+        self.shared.written.notify_all();
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.stopped = true;
+        state.entries.clear();
+        for (_, waker) in state.flushing.drain(..) {
+            waker.wake();
+        }
+        self.shared.written.notify_all();
+    }
+}
+
+impl std::fmt::Debug for Shared {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shared")
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl State {
+    /// `Some(None)` if there will be no more entries, `None` if the receiver has to wait.
+    fn take_batch(&mut self) -> Option<Option<Batch>> {
+        if !self.entries.is_empty() {
+            Some(Some(Batch {
+                entries: std::mem::take(&mut self.entries),
+                prepare: self.prepare,
+                end: self.queued,
+            }))
+        } else if self.senders == 0 {
+            Some(None)
+        } else {
+            None
+        }
+    }
+
+    fn wake_receiver(&mut self) {
+        if let Some(waker) = self.receiving.take() {
+            waker.wake();
+        }
+    }
+}
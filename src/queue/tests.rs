@@ -0,0 +1,269 @@
+//! Interleavings of loggers, `flushed` and `wait_flushed` callers and the writer, explored by shuttle.
+
+use std::time::Duration;
+
+use shuttle::{
+    future,
+    sync::{Arc, Mutex},
+    thread,
+};
+use time::OffsetDateTime;
+use tracing::Level;
+
+use super::{Queue, Receiver};
+use crate::{LogEntry, TimePrecision};
+
+const ITERATIONS: usize = 1000;
+
+fn entry(message: String) -> LogEntry<&'static str> {
+    LogEntry {
+        time: OffsetDateTime::UNIX_EPOCH,
+        time_precision: TimePrecision::Text,
+        level: Level::INFO,
+        module: None,
+        file: None,
+        line: None,
+        message,
+        structured: Default::default(),
+        category: None,
+        utc_offset: None,
+        repeat_count: 1,
+        origin: None,
+        expires_at: None,
+        elapsed: None,
+        event_id: None,
+        trace_context: None,
+    }
+}
+
+fn failure() -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_FULL), None)
+}
+
+/// Writes batches on a thread, collecting the messages.
+fn blocking_writer(
+    mut receiver: Receiver,
+    written: Arc<Mutex<Vec<String>>>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        while let Some(batch) = receiver.blocking_next() {
+            written
+                .lock()
+                .unwrap()
+                .extend(batch.entries.iter().map(|e| e.message.clone()));
+            receiver.done(batch, Ok(()));
+        }
+    })
+}
+
+#[test]
+fn every_entry_is_written_in_order_and_the_writer_stops() {
+    shuttle::check_random(
+        || {
+            let (queue, receiver) = Queue::new();
+            let written = Arc::new(Mutex::new(Vec::new()));
+            let writer = blocking_writer(receiver, written.clone());
+
+            let loggers: Vec<_> = (0..2)
+                .map(|thread| {
+                    let queue = queue.clone();
+                    thread::spawn(move || {
+                        for i in 0..3 {
+                            queue.log(entry(format!("{thread}-{i}"))).unwrap();
+                        }
+                    })
+                })
+                .collect();
+            drop(queue);
+            for logger in loggers {
+                logger.join().unwrap();
+            }
+            // the writer only stops once every sender is gone and nothing is left
+            writer.join().unwrap();
+
+            let written = written.lock().unwrap();
+            assert_eq!(written.len(), 6);
+            for thread in 0..2 {
+                let own: Vec<_> = written
+                    .iter()
+                    .filter(|m| m.starts_with(&format!("{thread}-")))
+                    .cloned()
+                    .collect();
+                assert_eq!(
+                    own,
+                    (0..3).map(|i| format!("{thread}-{i}")).collect::<Vec<_>>()
+                );
+            }
+        },
+        ITERATIONS,
+    );
+}
+
+#[test]
+fn a_full_queue_counts_every_entry_it_drops() {
+    shuttle::check_random(
+        || {
+            let (queue, receiver) = Queue::new();
+            queue.set_capacity(2);
+            let written = Arc::new(Mutex::new(Vec::new()));
+            let writer = blocking_writer(receiver, written.clone());
+
+            let loggers: Vec<_> = (0..2)
+                .map(|thread| {
+                    let queue = queue.clone();
+                    thread::spawn(move || {
+                        for i in 0..3 {
+                            queue.log(entry(format!("{thread}-{i}"))).unwrap();
+                        }
+                    })
+                })
+                .collect();
+            for logger in loggers {
+                logger.join().unwrap();
+            }
+            let dropped = queue.take_dropped();
+            drop(queue);
+            writer.join().unwrap();
+
+            assert_eq!(written.lock().unwrap().len() as u64 + dropped, 6);
+        },
+        ITERATIONS,
+    );
+}
+
+#[test]
+fn flushed_waits_for_entries_logged_before_it() {
+    shuttle::check_random(
+        || {
+            let (queue, receiver) = Queue::new();
+            let written = Arc::new(Mutex::new(Vec::new()));
+            let writer = blocking_writer(receiver, written.clone());
+
+            let flushers: Vec<_> = (0..2)
+                .map(|thread| {
+                    let queue = queue.clone();
+                    let written = written.clone();
+                    thread::spawn(move || {
+                        let message = format!("{thread}");
+                        queue.log(entry(message.clone())).unwrap();
+                        future::block_on(queue.flushed());
+                        assert!(written.lock().unwrap().contains(&message));
+                    })
+                })
+                .collect();
+            for flusher in flushers {
+                flusher.join().unwrap();
+            }
+            drop(queue);
+            writer.join().unwrap();
+        },
+        ITERATIONS,
+    );
+}
+
+#[test]
+fn wait_flushed_blocks_until_entries_logged_before_it_are_written() {
+    shuttle::check_random(
+        || {
+            let (queue, receiver) = Queue::new();
+            let written = Arc::new(Mutex::new(Vec::new()));
+            let writer = blocking_writer(receiver, written.clone());
+
+            let waiters: Vec<_> = (0..2)
+                .map(|thread| {
+                    let queue = queue.clone();
+                    let written = written.clone();
+                    thread::spawn(move || {
+                        let message = format!("{thread}");
+                        queue.log(entry(message.clone())).unwrap();
+                        // shuttle's condvar never times out
+                        queue.wait_flushed(Duration::from_secs(1)).unwrap();
+                        assert!(written.lock().unwrap().contains(&message));
+                    })
+                })
+                .collect();
+            for waiter in waiters {
+                waiter.join().unwrap();
+            }
+            drop(queue);
+            writer.join().unwrap();
+        },
+        ITERATIONS,
+    );
+}
+
+#[test]
+fn async_writer_is_woken_for_new_entries_and_shutdown() {
+    shuttle::check_random(
+        || {
+            let (queue, mut receiver) = Queue::new();
+            let writer = future::spawn(async move {
+                let mut count = 0;
+                while let Some(batch) = receiver.next().await {
+                    count += batch.entries.len();
+                    receiver.done(batch, Ok(()));
+                }
+                count
+            });
+
+            let logger = {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    queue.log(entry("a".to_owned())).unwrap();
+                    queue.log(entry("b".to_owned())).unwrap();
+                })
+            };
+            queue.log(entry("c".to_owned())).unwrap();
+            future::block_on(queue.flushed());
+            drop(queue);
+            logger.join().unwrap();
+
+            assert_eq!(future::block_on(writer).unwrap(), 3);
+        },
+        ITERATIONS,
+    );
+}
+
+#[test]
+fn write_errors_reach_the_next_log_call() {
+    shuttle::check_random(
+        || {
+            let (queue, mut receiver) = Queue::new();
+            let writer = thread::spawn(move || {
+                while let Some(batch) = receiver.blocking_next() {
+                    receiver.done(batch, Err(failure()));
+                }
+            });
+
+            queue.log(entry("a".to_owned())).unwrap();
+            future::block_on(queue.flushed());
+            assert!(queue.log(entry("b".to_owned())).is_err());
+            drop(queue);
+            writer.join().unwrap();
+        },
+        ITERATIONS,
+    );
+}
+
+#[test]
+fn stopped_writer_releases_flushed_and_fails_log() {
+    shuttle::check_random(
+        || {
+            let (queue, mut receiver) = Queue::new();
+            let writer = thread::spawn(move || {
+                // takes at most one batch, then the task is gone without writing it
+                let _ = receiver.blocking_next();
+            });
+
+            queue.log(entry("a".to_owned())).unwrap();
+            let flusher = {
+                let queue = queue.clone();
+                thread::spawn(move || future::block_on(queue.flushed()))
+            };
+            writer.join().unwrap();
+            flusher.join().unwrap();
+            assert!(queue.log(entry("b".to_owned())).is_err());
+        },
+        ITERATIONS,
+    );
+}
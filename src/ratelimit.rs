@@ -0,0 +1,58 @@
+use std::{sync::Mutex, time::Instant};
+
+/// A token bucket limiting events whose target starts with `prefix` to `per_second`,
+/// allowing bursts of up to one second worth of events.
+#[derive(Debug)]
+pub(crate) struct RateLimit {
+    pub(crate) prefix: &'static str,
+    per_second: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+    suppressed: u64,
+}
+
+pub(crate) enum Decision {
+    /// The event may be written, `suppressed` events were dropped since the last allowed one.
+    Allow {
+        suppressed: u64,
+    },
+    Deny,
+}
+
+impl RateLimit {
+    pub(crate) fn new(prefix: &'static str, per_second: u32) -> Self {
+        let per_second = f64::from(per_second);
+        Self {
+            prefix,
+            per_second,
+            bucket: Mutex::new(Bucket {
+                tokens: per_second,
+                refilled: Instant::now(),
+                suppressed: 0,
+            }),
+        }
+    }
+
+    pub(crate) fn check(&self) -> Decision {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.per_second);
+        bucket.refilled = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Decision::Allow {
+                suppressed: std::mem::take(&mut bucket.suppressed),
+            }
+        } else {
+            bucket.suppressed += 1;
+            Decision::Deny
+        }
+    }
+}
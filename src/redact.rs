@@ -0,0 +1,101 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// The text stored instead of a redacted value.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// What happens to the value of a field matched by `SubscriberBuilder::with_redaction`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Redaction {
+    /// Store [`REDACTED`].
+    Replace,
+    /// Store `hash:` and 16 hex digits, so entries with the same value can still be correlated.
+    /// This is not a cryptographic hash: values from a small set (e.g. ids) can be recovered by
+    /// hashing every candidate, and hashes may change between Rust versions.
+    Hash,
+}
+
+impl Redaction {
+    fn apply(self, value: &str) -> String {
+        match self {
+            Redaction::Replace => REDACTED.to_owned(),
+            Redaction::Hash => {
+                let mut hasher = DefaultHasher::new();
+                value.hash(&mut hasher);
+                format!("hash:{:016x}", hasher.finish())
+            }
+        }
+    }
+}
+
+/// Which fields are stored, see `SubscriberBuilder::with_field_allow_list`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FieldLists {
+    allow: Option<Vec<&'static str>>,
+    deny: Vec<&'static str>,
+}
+
+impl FieldLists {
+    pub(crate) fn allow(&mut self, patterns: impl IntoIterator<Item = &'static str>) {
+        self.allow.get_or_insert_with(Vec::new).extend(patterns);
+    }
+
+    pub(crate) fn deny(&mut self, patterns: impl IntoIterator<Item = &'static str>) {
+        self.deny.extend(patterns);
+    }
+
+    pub(crate) fn keeps(&self, name: &str) -> bool {
+        self.allow
+            .as_ref()
+            .is_none_or(|allow| allow.iter().any(|pattern| matches(pattern, name)))
+            && !self.deny.iter().any(|pattern| matches(pattern, name))
+    }
+}
+
+/// Field name patterns and what to do with the values of matching fields.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Redactor {
+    rules: Vec<(&'static str, Redaction)>,
+}
+
+impl Redactor {
+    pub(crate) fn push(&mut self, pattern: &'static str, redaction: Redaction) {
+        self.rules.push((pattern, redaction));
+    }
+
+    /// `value` as it should be stored for the field `name`, the first matching rule wins.
+    pub(crate) fn redact(&self, name: &str, value: String) -> String {
+        match self
+            .rules
+            .iter()
+            .find(|(pattern, _)| matches(pattern, name))
+        {
+            Some((_, redaction)) => redaction.apply(&value),
+            None => value,
+        }
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` stands for any number of characters.
+/// ASCII case is ignored.
+pub(crate) fn matches(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    // where the last `*` was and how much of `name` it has covered
+    let mut star = None;
+    while n < name.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, n));
+            p += 1;
+        } else if p < pattern.len() && pattern[p].eq_ignore_ascii_case(&name[n]) {
+            p += 1;
+            n += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&b| b == b'*')
+}
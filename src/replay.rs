@@ -0,0 +1,152 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+use time::format_description::well_known::Rfc3339;
+use tracing::{Dispatch, Level};
+use tracing_core::{
+    callsite::{self, Callsite, Identifier},
+    field::{display, Field, FieldSet, Value},
+    metadata::Kind,
+    subscriber::Interest,
+    Event, Metadata,
+};
+
+use crate::{LogEntry, LogHandle, LogQuery};
+
+/// The most values a replayed event can have, `tracing` builds value sets from arrays.
+const MAX_VALUES: usize = 32;
+
+impl LogHandle {
+    /// Emits the entries matching `query` as `tracing` events to `dispatch`, e.g. a `fmt`
+    /// subscriber to render historical logs. Returns the number of matching entries, also
+    /// those `dispatch` doesn't enable.
+    ///
+    /// Events have the level, module (as target), file and line of the entry, its message,
+    /// its structured fields as they were stored, and a `time` field with the original time
+    /// in RFC 3339 unless it recorded one itself. Layers that print timestamps show the time of
+    /// the replay. At most 30 structured fields are replayed per entry.
+    ///
+    /// `tracing` needs metadata that lives forever, so each combination of level, module,
+    /// file, line and field names is allocated once and kept for the rest of the process.
+    pub fn replay_into(
+        &self,
+        dispatch: impl Into<Dispatch>,
+        query: &LogQuery,
+    ) -> rusqlite::Result<u64> {
+        let dispatch = dispatch.into();
+        self.replay_with(query, |entry| replay(&dispatch, entry))
+    }
+
+    /// Calls `visit` with each entry matching `query`, oldest first, without collecting them,
+    /// e.g. to render entries without `tracing`. Returns the number of entries.
+    pub fn replay_with(
+        &self,
+        query: &LogQuery,
+        mut visit: impl FnMut(&LogEntry),
+    ) -> rusqlite::Result<u64> {
+        self.for_each_entry(query, |entry| {
+            visit(&entry);
+            Ok(())
+        })
+    }
+}
+
+fn replay(dispatch: &Dispatch, entry: &LogEntry) {
+    let time = entry
+        .structured
+        .get("time")
+        .is_none()
+        .then(|| entry.time.format(&Rfc3339).unwrap_or_default());
+    let fields: Vec<_> = entry
+        .structured
+        .fields()
+        .iter()
+        .filter(|(name, _)| name.as_str() != "message")
+        .take(MAX_VALUES - 2)
+        .collect();
+
+    let mut names = vec!["message".to_owned()];
+    names.extend(time.as_ref().map(|_| "time".to_owned()));
+    names.extend(fields.iter().map(|(name, _)| name.to_string()));
+    let metadata = ReplayCallsite::get(CallsiteKey {
+        level: entry.level,
+        module: entry.module.clone(),
+        file: entry.file.clone(),
+        line: entry.line,
+        names,
+    });
+    if !dispatch.enabled(metadata) {
+        return;
+    }
+
+    // stored values are already formatted, they are recorded as they are
+    let mut values = vec![display(entry.message.as_str())];
+    values.extend(time.as_deref().map(display));
+    values.extend(fields.iter().map(|(_, value)| display(value.as_str())));
+
+    let field_set = metadata.fields();
+    let keys: Vec<Field> = field_set.iter().collect();
+    // unused slots repeat the message field without a value, which visitors skip
+    let mut pairs: [(&Field, Option<&dyn Value>); MAX_VALUES] = [(&keys[0], None); MAX_VALUES];
+    for (pair, (key, value)) in pairs.iter_mut().zip(keys.iter().zip(&values)) {
+        *pair = (key, Some(value as &dyn Value));
+    }
+    dispatch.event(&Event::new(metadata, &field_set.value_set(&pairs)));
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct CallsiteKey {
+    level: Level,
+    module: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    names: Vec<String>,
+}
+
+struct ReplayCallsite {
+    metadata: OnceLock<Metadata<'static>>,
+}
+
+static CALLSITES: OnceLock<Mutex<HashMap<CallsiteKey, &'static ReplayCallsite>>> = OnceLock::new();
+
+impl ReplayCallsite {
+    /// The metadata of the callsite for `key`, registered the first time it is needed.
+    fn get(key: CallsiteKey) -> &'static Metadata<'static> {
+        let mut callsites = CALLSITES.get_or_init(Mutex::default).lock().unwrap();
+        if let Some(&callsite) = callsites.get(&key) {
+            return callsite.metadata();
+        }
+
+        let leak = |s: &str| -> &'static str { Box::leak(s.into()) };
+        let callsite: &'static ReplayCallsite = Box::leak(Box::new(ReplayCallsite {
+            metadata: OnceLock::new(),
+        }));
+        let names: Vec<&'static str> = key.names.iter().map(|name| leak(name)).collect();
+        let metadata = Metadata::new(
+            "replayed event",
+            key.module.as_deref().map_or("", leak),
+            key.level,
+            key.file.as_deref().map(leak),
+            key.line,
+            key.module.as_deref().map(leak),
+            FieldSet::new(Box::leak(names.into()), Identifier(callsite)),
+            Kind::EVENT,
+        );
+        let _ = callsite.metadata.set(metadata);
+        callsites.insert(key, callsite);
+        drop(callsites);
+
+        callsite::register(callsite);
+        callsite.metadata()
+    }
+}
+
+impl Callsite for ReplayCallsite {
+    fn set_interest(&self, _: Interest) {}
+
+    fn metadata(&self) -> &Metadata<'_> {
+        self.metadata.get().unwrap()
+    }
+}
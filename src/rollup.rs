@@ -0,0 +1,71 @@
+use rusqlite::Connection;
+
+use crate::{db::interned, query::field_expression, timestamp::TIME_TEXT, LogHandle, LOGS_TABLE};
+
+pub const DAILY_MODULE_STATS_TABLE: &str = "daily_module_stats_v0";
+
+const DAILY_MODULE_STATS_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS daily_module_stats_v0 (
+    day TEXT NOT NULL,
+    module TEXT NOT NULL,
+    events INTEGER NOT NULL,
+    errors INTEGER NOT NULL,
+    p95_span_duration_us INTEGER,
+    PRIMARY KEY (day, module)
+)";
+
+impl LogHandle {
+    /// Updates [`DAILY_MODULE_STATS_TABLE`] with the number of events and errors per module
+    /// (`''` for entries without one) and UTC day, so long-term trends survive pruning old rows.
+    /// Returns the number of rows written.
+    ///
+    /// Call it regularly, at least once per day before rows are pruned, or let
+    /// `SubscriberBuilder::with_daily_rollup` do it. Days before the last one in the table are
+    /// final and not computed again. `p95_span_duration_us` is the 95th percentile of the
+    /// `canonical.duration_us` field of the canonical lines (see
+    /// `SubscriberBuilder::with_canonical_lines`), `NULL` without any.
+    pub fn rollup_daily_module_stats(&self) -> rusqlite::Result<u64> {
+        rollup_daily_module_stats(&self.writer())
+    }
+}
+
+/// `LogHandle::rollup_daily_module_stats` on `conn`.
+pub(crate) fn rollup_daily_module_stats(conn: &Connection) -> rusqlite::Result<u64> {
+    conn.execute_batch(DAILY_MODULE_STATS_SCHEMA)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let last_day: Option<String> = tx.query_row(
+        &format!("SELECT max(day) FROM {DAILY_MODULE_STATS_TABLE}"),
+        [],
+        |row| row.get(0),
+    )?;
+    // the nearest-rank percentile: the smallest duration with at least 95% of them not larger
+    let written = tx.execute(
+        &format!(
+            "INSERT OR REPLACE INTO {DAILY_MODULE_STATS_TABLE}
+                 (day, module, events, errors, p95_span_duration_us)
+             WITH entries AS (
+                 SELECT date({TIME_TEXT}) AS day, coalesce({module}, '') AS name, level,
+                        repeat_count, CAST({duration} AS INTEGER) AS duration
+                 FROM {LOGS_TABLE} WHERE day >= coalesce(?1, '')
+             ), ranked AS (
+                 SELECT day, name, duration,
+                        row_number() OVER (PARTITION BY day, name ORDER BY duration) AS rank,
+                        count(*) OVER (PARTITION BY day, name) AS durations
+                 FROM entries WHERE duration IS NOT NULL
+             ), percentiles AS (
+                 SELECT day, name, min(duration) AS p95 FROM ranked
+                 WHERE rank >= 0.95 * durations GROUP BY day, name
+             )
+             SELECT day, name, sum(repeat_count),
+                    sum(CASE WHEN level = 'ERROR' THEN repeat_count ELSE 0 END),
+                    (SELECT p95 FROM percentiles AS p WHERE p.day = e.day AND p.name = e.name)
+             FROM entries AS e
+             GROUP BY day, name",
+            module = interned("module", "main"),
+            duration = field_expression("canonical.duration_us"),
+        ),
+        [last_day],
+    )?;
+    tx.commit()?;
+    Ok(written as u64)
+}
@@ -0,0 +1,385 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use rusqlite::Connection;
+use time::OffsetDateTime;
+
+use crate::{export::io_error, prepare_database, Connect, LogEntry, LogHandle};
+
+/// A `Connect` writing to the database at `path` that starts new files by time or when one
+/// gets too big, like rotating file appenders do.
+///
+/// With a [`Rotation`], entries go to one file per day or hour, e.g. `logs-2024-06-01.sqlite`
+/// for `logs.sqlite`, and the next file is started with the first entry after the boundary.
+/// Files are only started forward in time, entries older than the current period (from a clock
+/// that went back, or replayed ones) are written to the current file.
+/// A database that is full is closed and renamed to `<file>.<UTC time>`, e.g.
+/// `logs.db.20240601T120000Z`, and a new one is created in its place. `with_max_files` deletes
+/// the oldest files. Handles that were reading a database before a rotation keep reading the
+/// archive, open a new one to see the new file.
+#[derive(Debug)]
+pub struct RotatingConnect {
+    path: PathBuf,
+    rotation: Rotation,
+    max_size: Option<u64>,
+    max_files: Option<usize>,
+    current: Mutex<Current>,
+}
+
+#[derive(Debug)]
+struct Current {
+    /// The file `conn` is open on.
+    path: PathBuf,
+    conn: Option<Connection>,
+    /// The newest time a file was picked for, the period never moves back before it.
+    newest: Option<OffsetDateTime>,
+}
+
+/// When a [`RotatingConnect`] starts a new file, in UTC.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    /// Always write to `path`.
+    #[default]
+    Never,
+    /// `logs.sqlite` becomes `logs-2024-06-01-13.sqlite`.
+    Hourly,
+    /// `logs.sqlite` becomes `logs-2024-06-01.sqlite`.
+    Daily,
+}
+
+impl RotatingConnect {
+    /// Writes to the database at `path`, or the files named after it by the rotation. Files
+    /// are opened in WAL mode, and created and prepared if needed, on the first write.
+    /// Doesn't rotate until a rotation or limit is set.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self {
+            current: Mutex::new(Current {
+                path: path.clone(),
+                conn: None,
+                newest: None,
+            }),
+            path,
+            rotation: Rotation::Never,
+            max_size: None,
+            max_files: None,
+        }
+    }
+
+    /// Same as `new`, opening the database at `path` right away.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let this = Self::new(path.as_ref());
+        this.current.lock().unwrap().conn = Some(open_prepared(&this.path)?);
+        Ok(this)
+    }
+
+    /// Start a new file every day or hour. Use it with `new`, `open` already created `path`.
+    pub fn with_rotation(self, rotation: Rotation) -> Self {
+        Self { rotation, ..self }
+    }
+
+    /// Rotate once the database (including pages still in the WAL file) is `bytes` or bigger.
+    pub fn with_max_db_size(self, bytes: u64) -> Self {
+        Self {
+            max_size: Some(bytes),
+            ..self
+        }
+    }
+
+    /// Keep at most `count` old files, the oldest ones are deleted whenever a new file is started.
+    pub fn with_max_files(self, count: usize) -> Self {
+        Self {
+            max_files: Some(count),
+            ..self
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The file entries are written to right now.
+    pub fn current_path(&self) -> PathBuf {
+        let newest = self.current.lock().unwrap().newest;
+        let now = OffsetDateTime::now_utc();
+        self.rotation
+            .file(&self.path, newest.map_or(now, |newest| newest.max(now)))
+    }
+
+    /// The files written before the current one, oldest first.
+    pub fn rotated_files(&self) -> rusqlite::Result<Vec<PathBuf>> {
+        rotated_files(&self.path, self.rotation, &self.current_path())
+    }
+
+    /// Starts a new database now, returning where the old one was moved.
+    pub fn rotate(&self) -> rusqlite::Result<PathBuf> {
+        let mut current = self.current.lock().unwrap();
+        self.switch(&mut current, OffsetDateTime::now_utc())?;
+        self.rotate_locked(&mut current)
+    }
+
+    /// Opens the file for entries written at `now`, if it isn't open yet. Keeps the current
+    /// file if `now` is before the newest time seen.
+    fn switch(&self, current: &mut Current, now: OffsetDateTime) -> rusqlite::Result<()> {
+        let now = current.newest.map_or(now, |newest| newest.max(now));
+        current.newest = Some(now);
+        let path = self.rotation.file(&self.path, now);
+        if current.conn.is_some() && current.path == path {
+            return Ok(());
+        }
+
+        current.conn = None;
+        current.conn = Some(open_prepared(&path)?);
+        current.path = path;
+        self.prune(current)
+    }
+
+    fn rotate_locked(&self, current: &mut Current) -> rusqlite::Result<PathBuf> {
+        let archive = archive_path(&current.path, OffsetDateTime::now_utc())?;
+
+        // closing the last connection checkpoints the WAL file into the database
+        current.conn = None;
+        for suffix in ["", "-wal", "-shm"] {
+            match fs::rename(
+                with_suffix(&current.path, suffix),
+                with_suffix(&archive, suffix),
+            ) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(io_error(e)),
+                _ => {}
+            }
+        }
+        current.conn = Some(open_prepared(&current.path)?);
+        self.prune(current)?;
+        Ok(archive)
+    }
+
+    /// Deletes the oldest files beyond `max_files`.
+    fn prune(&self, current: &Current) -> rusqlite::Result<()> {
+        let Some(max) = self.max_files else {
+            return Ok(());
+        };
+        let files = rotated_files(&self.path, self.rotation, &current.path)?;
+        for file in &files[..files.len().saturating_sub(max)] {
+            for suffix in ["", "-wal", "-shm"] {
+                match fs::remove_file(with_suffix(file, suffix)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(io_error(e)),
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn size(conn: &Connection) -> rusqlite::Result<u64> {
+        conn.query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            |row| row.get(0),
+        )
+    }
+}
+
+impl Connect for RotatingConnect {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        let mut current = self.current.lock().unwrap();
+        self.switch(&mut current, entry.time)?;
+        let conn = current.conn.as_ref().unwrap();
+        conn.log(entry)?;
+        match self.max_size {
+            Some(max) if Self::size(conn)? >= max => self.rotate_locked(&mut current).map(drop),
+            _ => Ok(()),
+        }
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        let mut current = self.current.lock().unwrap();
+        self.switch(&mut current, OffsetDateTime::now_utc())?;
+        prepare_database(current.conn.as_ref().unwrap())
+    }
+}
+
+impl LogHandle {
+    /// The databases rotated away from the file of this handle by [`RotatingConnect`] because
+    /// they were full, oldest first. Empty for in-memory databases.
+    pub fn rotated_files(&self) -> rusqlite::Result<Vec<PathBuf>> {
+        match self.file_path() {
+            Some(path) => rotated_files(path, Rotation::Never, path),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+impl Rotation {
+    /// The file for entries written at `time`.
+    fn file(&self, base: &Path, time: OffsetDateTime) -> PathBuf {
+        let time = time.to_offset(time::UtcOffset::UTC);
+        let date = format!(
+            "{:04}-{:02}-{:02}",
+            time.year(),
+            time.month() as u8,
+            time.day()
+        );
+        let period = match self {
+            Rotation::Never => return base.to_owned(),
+            Rotation::Hourly => format!("{date}-{:02}", time.hour()),
+            Rotation::Daily => date,
+        };
+
+        let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match base.extension() {
+            Some(extension) => format!("{stem}-{period}.{}", extension.to_string_lossy()),
+            None => format!("{stem}-{period}"),
+        };
+        base.with_file_name(name)
+    }
+
+    /// Whether `name` is the name of a file `file` returns for `base`.
+    fn is_file(&self, base: &Path, name: &str) -> bool {
+        let pattern: &[u8] = match self {
+            Rotation::Never => return false,
+            Rotation::Hourly => b"0000-00-00-00",
+            Rotation::Daily => b"0000-00-00",
+        };
+        let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = base
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_default();
+
+        name.strip_prefix(&*stem)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .and_then(|rest| rest.strip_suffix(&extension))
+            .is_some_and(|period| {
+                period.len() == pattern.len()
+                    && period.bytes().zip(pattern).all(|(b, p)| match p {
+                        b'0' => b.is_ascii_digit(),
+                        _ => b == *p,
+                    })
+            })
+    }
+}
+
+fn open_prepared(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    prepare_database(&conn)?;
+    Ok(conn)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(suffix);
+    path.into()
+}
+
+/// `<path>.<time>`, with `-<n>` appended if a rotation in the same second already used it.
+/// `n` follows the highest one used, even if older archives of that second were deleted, so
+/// the new archive always sorts last.
+fn archive_path(path: &Path, time: OffsetDateTime) -> rusqlite::Result<PathBuf> {
+    let stamp = format!(
+        ".{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        time.year(),
+        time.month() as u8,
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    );
+    let archive = with_suffix(path, &stamp);
+    let (Some(dir), Some(name)) = (archive.parent(), archive.file_name()) else {
+        return Ok(archive);
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let name = name.to_string_lossy();
+
+    let mut last = None;
+    for entry in fs::read_dir(dir).map_err(io_error)? {
+        let entry = entry.map_err(io_error)?;
+        let n = match entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.strip_prefix(&*name))
+        {
+            Some("") => 0,
+            Some(rest) => match rest.strip_prefix('-').and_then(|n| n.parse().ok()) {
+                Some(n) => n,
+                None => continue,
+            },
+            None => continue,
+        };
+        last = last.max(Some(n));
+    }
+    Ok(match last {
+        None => archive,
+        Some(n) => with_suffix(&archive, &format!("-{}", n + 1)),
+    })
+}
+
+/// The files `rotation` started before `current` and the archives `archive_path` made of
+/// them (and of `current`), oldest first.
+fn rotated_files(
+    base: &Path,
+    rotation: Rotation,
+    current: &Path,
+) -> rusqlite::Result<Vec<PathBuf>> {
+    let (Some(dir), Some(base_name)) = (base.parent(), base.file_name()) else {
+        return Ok(Vec::new());
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let base_name = base_name.to_string_lossy();
+    let current_name = current.file_name().unwrap_or_default().to_string_lossy();
+    let is_file = |name: &str| name == base_name || rotation.is_file(base, name);
+
+    // sorted by the file they belong to, then archives before the file itself
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).map_err(io_error)? {
+        let entry = entry.map_err(io_error)?;
+        let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+            continue;
+        };
+
+        let archive = name
+            .rsplit_once('.')
+            .and_then(|(file, suffix)| Some((file.to_owned(), parse_stamp(suffix)?)));
+        match archive {
+            Some((file, (stamp, n))) if is_file(&file) => {
+                files.push((file, 0, stamp.to_owned(), n, entry.path()))
+            }
+            _ if rotation.is_file(base, &name) && name != current_name => {
+                files.push((name, 1, String::new(), 0, entry.path()))
+            }
+            _ => {}
+        }
+    }
+    files.sort();
+    Ok(files.into_iter().map(|(.., path)| path).collect())
+}
+
+/// Splits `YYYYMMDDTHHMMSSZ`, optionally followed by `-<n>`, into the time and `n`.
+fn parse_stamp(suffix: &str) -> Option<(&str, u32)> {
+    let (stamp, n) = match suffix.split_once('-') {
+        Some((stamp, n)) => (stamp, n.parse().ok()?),
+        None => (suffix, 0),
+    };
+    let bytes = stamp.as_bytes();
+    let valid = bytes.len() == 16
+        && bytes[8] == b'T'
+        && bytes[15] == b'Z'
+        && bytes[..8]
+            .iter()
+            .chain(&bytes[9..15])
+            .all(u8::is_ascii_digit);
+    valid.then_some((stamp, n))
+}
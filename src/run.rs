@@ -0,0 +1,156 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use rusqlite::Connection;
+use time::OffsetDateTime;
+use tracing::Level;
+
+use crate::{db::insert_entry, LogEntry, Origin, TimePrecision, LOGS_TABLE};
+
+pub const RUNS_TABLE: &str = "runs_v0";
+
+const RUNS_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS runs_v0 (
+    started TEXT NOT NULL,
+    ended TEXT,
+    pid INTEGER NOT NULL,
+    is_fatal INTEGER NOT NULL DEFAULT 0,
+    panic_message TEXT,
+    panic_file TEXT,
+    panic_line INTEGER,
+    panic_column INTEGER,
+    panic_thread TEXT,
+    last_log_rowid INTEGER
+)";
+
+/// A row in [`RUNS_TABLE`] for one run of the process, so post-mortem queries can tell
+/// how and where it ended.
+///
+/// `ended` stays `NULL` if the process died without `finish` or a panic being recorded,
+/// e.g. when it was killed. When the run ends, the highest rowid in `logs_v0` is recorded as
+/// `last_log_rowid`, see `LogHandle::as_of`.
+#[derive(Debug, Clone)]
+pub struct RunRecorder {
+    conn: Arc<Mutex<Connection>>,
+    run_id: i64,
+}
+
+impl RunRecorder {
+    /// Creates [`RUNS_TABLE`] if needed and inserts the row for this run.
+    pub fn start(conn: Arc<Mutex<Connection>>) -> rusqlite::Result<Self> {
+        let run_id = {
+            let conn = conn.lock().unwrap();
+            conn.execute_batch(RUNS_SCHEMA)?;
+            conn.execute(
+                "INSERT INTO runs_v0 (started, pid) VALUES (?1, ?2)",
+                (OffsetDateTime::now_utc(), std::process::id()),
+            )?;
+            conn.last_insert_rowid()
+        };
+
+        Ok(Self { conn, run_id })
+    }
+
+    /// The rowid of this run in [`RUNS_TABLE`].
+    pub fn run_id(&self) -> i64 {
+        self.run_id
+    }
+
+    /// Records the location, message and thread of panics on the run row and as an `ERROR`
+    /// entry, then calls the previous hook.
+    ///
+    /// A panic counts as fatal if it happens on the main thread or the binary is built with
+    /// `panic = "abort"`; other threads may be joined and the process goes on, then the row
+    /// describes the last panic. Nothing is recorded if the panicking thread holds the
+    /// connection itself.
+    pub fn install_panic_hook(&self) {
+        let recorder = self.clone();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let message = info
+                .payload()
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("Box<dyn Any>");
+            let thread = std::thread::current();
+            let fatal = cfg!(panic = "abort") || thread.name() == Some("main");
+
+            // errors can't be reported from here, the previous hook still prints the panic
+            let _ = recorder.record_panic(message, info.location(), thread.name(), fatal);
+            previous(info);
+        }));
+    }
+
+    fn record_panic(
+        &self,
+        message: &str,
+        location: Option<&std::panic::Location<'_>>,
+        thread: Option<&str>,
+        fatal: bool,
+    ) -> rusqlite::Result<()> {
+        let Ok(conn) = self.conn.try_lock() else {
+            return Ok(());
+        };
+
+        let now = OffsetDateTime::now_utc();
+        let mut fields = HashMap::from([
+            ("panic.fatal", fatal.to_string()),
+            ("run_id", self.run_id.to_string()),
+        ]);
+        if let Some(thread) = thread {
+            fields.insert("panic.thread", thread.to_owned());
+        }
+        if let Some(location) = location {
+            fields.insert("panic.column", location.column().to_string());
+        }
+        let rowid = insert_entry(
+            &conn,
+            LOGS_TABLE,
+            &LogEntry {
+                time: now,
+                time_precision: TimePrecision::Text,
+                level: Level::ERROR,
+                module: None,
+                file: location.map(|l| l.file()),
+                line: location.map(|l| l.line()),
+                message: format!("panicked: {message}"),
+                structured: fields.into(),
+                category: None,
+                utc_offset: None,
+                repeat_count: 1,
+                origin: Some(Origin::Tracing),
+                expires_at: None,
+                elapsed: None,
+                event_id: None,
+                trace_context: None,
+            },
+        )?;
+
+        conn.execute(
+            "UPDATE runs_v0 SET ended = ?1, is_fatal = ?2, panic_message = ?3, panic_file = ?4, panic_line = ?5, panic_column = ?6, panic_thread = ?7, last_log_rowid = ?8 WHERE rowid = ?9",
+            (
+                now,
+                fatal,
+                message,
+                location.map(|l| l.file()),
+                location.map(|l| l.line()),
+                location.map(|l| l.column()),
+                thread,
+                rowid,
+                self.run_id,
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Marks the run as ended normally.
+    pub fn finish(self) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE runs_v0 SET ended = ?1, last_log_rowid = (SELECT coalesce(max(rowid), 0) FROM logs_v0) WHERE rowid = ?2 AND ended IS NULL",
+            (OffsetDateTime::now_utc(), self.run_id),
+        )?;
+        Ok(())
+    }
+}
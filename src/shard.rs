@@ -0,0 +1,166 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use rusqlite::Connection;
+
+use crate::{prepare_database, Connect, LogEntry, LogHandle};
+
+/// A `Connect` writing each entry to the database of its tenant, `<dir>/<tenant>.db`, so the
+/// logs of different customers are physically separate.
+///
+/// The tenant is the value of the structured field `field`, e.g. `tracing::info!(tenant = "acme", ...)`.
+/// Databases are opened on demand and prepared the first time this process opens them. Only
+/// the `capacity` most recently used stay open, the least recently used one is closed when
+/// another has to be opened.
+///
+/// Tenant names may only contain ASCII letters, digits, `-`, `_` and `.` (not first),
+/// entries with other names fail with `InvalidPath`.
+#[derive(Debug)]
+pub struct ShardManager {
+    dir: PathBuf,
+    field: &'static str,
+    default_tenant: Option<String>,
+    capacity: usize,
+    shards: Mutex<Shards>,
+}
+
+#[derive(Debug, Default)]
+struct Shards {
+    /// Least recently used first.
+    open: Vec<(String, Connection)>,
+    prepared: HashSet<String>,
+}
+
+impl ShardManager {
+    /// Shards in `dir` (created if needed), by the value of `field`. Keeps 16 databases open.
+    pub fn new(dir: impl Into<PathBuf>, field: &'static str) -> Self {
+        Self {
+            dir: dir.into(),
+            field,
+            default_tenant: None,
+            capacity: 16,
+            shards: Mutex::default(),
+        }
+    }
+
+    /// Keep at most `capacity` (at least one) databases open.
+    pub fn with_capacity(self, capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ..self
+        }
+    }
+
+    /// Write entries without the tenant field to `tenant`, instead of failing them.
+    pub fn with_default_tenant(self, tenant: impl Into<String>) -> Self {
+        Self {
+            default_tenant: Some(tenant.into()),
+            ..self
+        }
+    }
+
+    /// The database file of `tenant`.
+    pub fn shard_path(&self, tenant: &str) -> rusqlite::Result<PathBuf> {
+        let valid = !tenant.is_empty()
+            && !tenant.starts_with('.')
+            && tenant
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'));
+        let path = self.dir.join(format!("{tenant}.db"));
+        if valid {
+            Ok(path)
+        } else {
+            Err(rusqlite::Error::InvalidPath(path))
+        }
+    }
+
+    /// A handle to read the logs of `tenant`, through its own connection.
+    pub fn handle(&self, tenant: &str) -> rusqlite::Result<LogHandle> {
+        Ok(LogHandle::new(Connection::open(self.shard_path(tenant)?)?))
+    }
+
+    /// The tenants whose databases are open, least recently used first.
+    pub fn open_tenants(&self) -> Vec<String> {
+        let shards = self.shards.lock().unwrap();
+        shards
+            .open
+            .iter()
+            .map(|(tenant, _)| tenant.clone())
+            .collect()
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Runs `f` with the connection to `tenant`, opening it first if needed.
+    fn with_shard(
+        &self,
+        tenant: &str,
+        f: impl FnOnce(&Connection) -> rusqlite::Result<()>,
+    ) -> rusqlite::Result<()> {
+        let mut shards = self.shards.lock().unwrap();
+        match shards.open.iter().position(|(t, _)| t == tenant) {
+            Some(i) => {
+                let shard = shards.open.remove(i);
+                shards.open.push(shard);
+            }
+            None => {
+                let path = self.shard_path(tenant)?;
+                std::fs::create_dir_all(&self.dir)
+                    // `Connect` reports rusqlite errors, this is the variant that carries arbitrary ones
+                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+                let conn = Connection::open(path)?;
+                if !shards.prepared.contains(tenant) {
+                    prepare_database(&conn)?;
+                    shards.prepared.insert(tenant.to_owned());
+                }
+                if shards.open.len() >= self.capacity {
+                    shards.open.remove(0);
+                }
+                shards.open.push((tenant.to_owned(), conn));
+            }
+        }
+
+        let (_, conn) = shards.open.last().unwrap();
+        f(conn)
+    }
+}
+
+impl Connect for ShardManager {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        // string fields are stored as their `Debug` representation, quoted
+        let tenant = entry
+            .structured
+            .get(self.field)
+            .map(|value| {
+                value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value)
+            })
+            .or(self.default_tenant.as_deref())
+            .ok_or_else(|| {
+                rusqlite::Error::InvalidParameterName(format!(
+                    "the entry has no `{}` field",
+                    self.field
+                ))
+            })?
+            .to_owned();
+
+        self.with_shard(&tenant, |conn| conn.log(entry))
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        let mut shards = self.shards.lock().unwrap();
+        for (_, conn) in &shards.open {
+            prepare_database(conn)?;
+        }
+        let tenants: Vec<_> = shards.open.iter().map(|(t, _)| t.clone()).collect();
+        shards.prepared.extend(tenants);
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use time::OffsetDateTime;
+
+use crate::{
+    store::TailSource,
+    timestamp::{time_condition, time_params},
+    LogEntry, LogHandle, LogQuery, LOGS_TABLE,
+};
+
+pub const SHIPPING_STATE_TABLE: &str = "shipping_state_v0";
+
+const SHIPPING_STATE_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS shipping_state_v0 (
+    exporter TEXT PRIMARY KEY NOT NULL,
+    shipped_rowid INTEGER NOT NULL,
+    shipped_at TEXT NOT NULL
+)";
+
+/// Entries of `logs_v0` an exporter has not shipped yet, see `LogHandle::unshipped`.
+#[derive(Debug, Clone)]
+pub struct ShippingBatch {
+    pub entries: Vec<LogEntry>,
+    /// The rowid to acknowledge with `LogHandle::ack_shipped` once `entries` are shipped,
+    /// past entries that don't match the query too.
+    pub end: i64,
+}
+
+impl LogHandle {
+    /// Up to `limit` entries matching `query` after the watermark of `exporter`, oldest first.
+    ///
+    /// An exporter ships them (e.g. to Loki or an OTLP collector) and then acknowledges the
+    /// batch, so entries are shipped at least once even if the process dies in between.
+    pub fn unshipped(
+        &self,
+        exporter: &str,
+        query: &LogQuery,
+        limit: u64,
+    ) -> rusqlite::Result<ShippingBatch> {
+        let watermark = self.shipped_watermark(exporter)?;
+        let (end, entries) =
+            self.entries_after(watermark, &query.clone().table(LOGS_TABLE), limit)?;
+        Ok(ShippingBatch { end, entries })
+    }
+
+    /// Records in [`SHIPPING_STATE_TABLE`] that `exporter` durably shipped the entries up to
+    /// rowid `end`. The watermark never moves back.
+    pub fn ack_shipped(&self, exporter: &str, end: i64) -> rusqlite::Result<()> {
+        let conn = self.writer();
+        conn.execute_batch(SHIPPING_STATE_SCHEMA)?;
+        conn.execute(
+            "INSERT INTO shipping_state_v0 (exporter, shipped_rowid, shipped_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT (exporter) DO UPDATE SET
+                 shipped_rowid = max(shipped_rowid, excluded.shipped_rowid),
+                 shipped_at = excluded.shipped_at",
+            (exporter, end, OffsetDateTime::now_utc()),
+        )?;
+        Ok(())
+    }
+
+    /// The highest rowid `exporter` acknowledged, `0` if it hasn't shipped anything yet.
+    pub fn shipped_watermark(&self, exporter: &str) -> rusqlite::Result<i64> {
+        let conn = self.writer();
+        conn.execute_batch(SHIPPING_STATE_SCHEMA)?;
+        conn.query_row(
+            "SELECT coalesce(max(shipped_rowid), 0) FROM shipping_state_v0 WHERE exporter = ?1",
+            [exporter],
+            |row| row.get(0),
+        )
+    }
+
+    /// Prune-after-ship retention: deletes the entries older than `before` that every exporter
+    /// in [`SHIPPING_STATE_TABLE`] has acknowledged, returning how many were deleted. Nothing
+    /// is deleted before an exporter acknowledged a batch. The newest entry is kept, so the
+    /// rowids of new entries stay above the watermarks.
+    pub fn prune_shipped(&self, before: OffsetDateTime) -> rusqlite::Result<u64> {
+        let conn = self.writer();
+        conn.execute_batch(SHIPPING_STATE_SCHEMA)?;
+        let deleted = conn.execute(
+            &format!(
+                "DELETE FROM {LOGS_TABLE} WHERE {}
+                 AND rowid <= (SELECT coalesce(min(shipped_rowid), 0) FROM shipping_state_v0)
+                 AND rowid < (SELECT max(rowid) FROM {LOGS_TABLE})",
+                time_condition("<"),
+            ),
+            time_params(before),
+        )?;
+        Ok(deleted as u64)
+    }
+}
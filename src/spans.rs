@@ -0,0 +1,109 @@
+use std::{cell::RefCell, collections::HashMap, sync::Mutex};
+
+use tracing::span;
+
+/// The open spans, for span sampling and canonical lines: which root span each belongs to
+/// and whether its trace is kept.
+///
+/// Spans are kept by id until they close, instead of in span extensions, so the layer doesn't
+/// need a subscriber that stores spans.
+#[derive(Debug)]
+pub(crate) struct SpanTracker {
+    sample_rate: Option<f64>,
+    spans: Mutex<HashMap<span::Id, SpanState>>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct SpanState {
+    pub(crate) root: span::Id,
+    /// The sampling decision of the root span, `None` without span sampling.
+    pub(crate) keep: Option<bool>,
+    /// Handles to the span, for subscribers that don't count them themselves.
+    refs: usize,
+}
+
+thread_local! {
+    /// Spans entered through a `Subscriber` on this thread, with the address of its layer.
+    static ENTERED: RefCell<Vec<(usize, span::Id)>> = const { RefCell::new(Vec::new()) };
+}
+
+impl SpanTracker {
+    pub(crate) fn new(sample_rate: Option<f64>) -> Self {
+        Self {
+            sample_rate: sample_rate.map(|rate| rate.clamp(0.0, 1.0)),
+            spans: Mutex::default(),
+        }
+    }
+
+    /// Starts following a span, a child of `parent` if that is known and a root otherwise.
+    /// Returns its state, roots make the sampling decision for their trace.
+    pub(crate) fn new_span(&self, id: &span::Id, parent: Option<&span::Id>) -> SpanState {
+        let mut spans = self.spans.lock().unwrap();
+        let state = match parent.and_then(|parent| spans.get(parent)) {
+            Some(parent) => SpanState {
+                refs: 1,
+                ..parent.clone()
+            },
+            None => SpanState {
+                root: id.clone(),
+                keep: self.sample_rate.map(|rate| fastrand::f64() < rate),
+                refs: 1,
+            },
+        };
+        spans.insert(id.clone(), state.clone());
+        state
+    }
+
+    pub(crate) fn clone_span(&self, id: &span::Id) {
+        if let Some(state) = self.spans.lock().unwrap().get_mut(id) {
+            state.refs += 1;
+        }
+    }
+
+    /// Drops one handle to the span, returns whether it was the last one.
+    pub(crate) fn release(&self, id: &span::Id) -> bool {
+        match self.spans.lock().unwrap().get_mut(id) {
+            Some(state) if state.refs > 1 => {
+                state.refs -= 1;
+                false
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    /// Stops following the span once it is closed.
+    pub(crate) fn remove(&self, id: &span::Id) -> Option<SpanState> {
+        self.spans.lock().unwrap().remove(id)
+    }
+
+    pub(crate) fn get(&self, span: Option<&span::Id>) -> Option<SpanState> {
+        let spans = self.spans.lock().unwrap();
+        span.and_then(|span| spans.get(span)).cloned()
+    }
+
+    pub(crate) fn enter(owner: usize, id: &span::Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push((owner, id.clone())));
+    }
+
+    pub(crate) fn exit(owner: usize, id: &span::Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(i) = entered.iter().rposition(|(o, e)| *o == owner && e == id) {
+                entered.remove(i);
+            }
+        })
+    }
+
+    /// The span last entered on this thread through the subscriber of `owner`.
+    pub(crate) fn current(owner: usize) -> Option<span::Id> {
+        ENTERED.with(|entered| {
+            let entered = entered.borrow();
+            entered
+                .iter()
+                .rev()
+                .find(|(o, _)| *o == owner)
+                .map(|(_, id)| id.clone())
+        })
+    }
+}
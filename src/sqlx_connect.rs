@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use rusqlite::types::Value;
+use sqlx::SqlitePool;
+use time::OffsetDateTime;
+
+use crate::{
+    query::time_value,
+    queue::{Queue, Receiver},
+    Connect, LogEntry, SQL_SCHEMA,
+};
+
+/// A `Connect` writing through a `sqlx::SqlitePool` from a tokio task, for applications that
+/// already use sqlx. Enabled by the `sqlx` feature.
+///
+/// Both drivers link the same `libsqlite3-sys`, so SQLite itself is only built once.
+/// Like [`TokioConnect`](crate::TokioConnect), queued entries are committed together, errors of
+/// the task are returned by the next `log` call and [`SqlxConnect::flushed`] waits for pending
+/// entries.
+#[derive(Debug, Clone)]
+pub struct SqlxConnect {
+    queue: Queue,
+}
+
+impl SqlxConnect {
+    /// Starts the writer task on the current runtime, panics outside of one.
+    /// The database must be prepared already, see [`SqlxConnect::prepared`].
+    pub fn new(pool: SqlitePool) -> Self {
+        let (queue, receiver) = Queue::new();
+        tokio::spawn(run(pool, receiver));
+        Self { queue }
+    }
+
+    /// Same as `new`, creating the log table first.
+    ///
+    /// Unlike `prepare_database` this doesn't add columns to tables created by older versions.
+    pub async fn prepared(pool: SqlitePool) -> Result<Self, sqlx::Error> {
+        sqlx::raw_sql(SQL_SCHEMA).execute(&pool).await?;
+        Ok(Self::new(pool))
+    }
+
+    /// Let up to `entries` wait for the writer task before new ones are dropped, 10 000 by
+    /// default. Dropped entries are counted in `Stats::dropped`.
+    pub fn with_capacity(self, entries: usize) -> Self {
+        self.queue.set_capacity(entries);
+        self
+    }
+
+    /// Completes once every entry logged before the call has been written.
+    pub async fn flushed(&self) {
+        self.queue.flushed().await
+    }
+}
+
+impl Connect for SqlxConnect {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self.queue.log(entry)
+    }
+
+    /// Blocks the calling thread, async code should await `flushed` instead.
+    fn flush_and_wait(&self, timeout: Duration) -> rusqlite::Result<()> {
+        self.queue.wait_flushed(timeout)
+    }
+
+    /// Queues `entry` like `log`; the writer task prepares the database before each batch
+    /// until one is written.
+    fn log_or_prepare(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self.queue.log_or_prepare(entry)
+    }
+
+    fn take_dropped(&self) -> u64 {
+        self.queue.take_dropped()
+    }
+}
+
+async fn run(pool: SqlitePool, mut receiver: Receiver) {
+    while let Some(batch) = receiver.next().await {
+        let result = write(&pool, &batch.entries, batch.prepare)
+            .await
+            // `Connect` reports rusqlite errors, this is the variant that carries arbitrary ones
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)));
+        receiver.done(batch, result);
+    }
+}
+
+/// Stores `module` and `file` as text, there are no interned strings here.
+const INSERT_SQL: &str = "INSERT INTO logs_v0 (time, level, module, file, line, message, \
+    structured, category, utc_offset, repeat_count, origin, expires_at, elapsed_us, event_id, \
+    trace_id, span_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)";
+
+async fn write(pool: &SqlitePool, entries: &[LogEntry], prepare: bool) -> Result<(), sqlx::Error> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    if prepare {
+        sqlx::raw_sql(SQL_SCHEMA).execute(pool).await?;
+    }
+
+    let mut tx = pool.begin().await?;
+    for entry in entries {
+        let query = sqlx::query(INSERT_SQL);
+        let query = match entry.time_precision.to_sql(entry.time) {
+            Value::Integer(i) => query.bind(i),
+            _ => query.bind(time_text(entry.time)),
+        };
+        query
+            .bind(entry.level.as_str())
+            .bind(entry.module.as_deref())
+            .bind(entry.file.as_deref())
+            .bind(entry.line)
+            .bind(&entry.message)
+            .bind(entry.structured.to_json())
+            .bind(entry.category.map(|c| c.as_str()))
+            .bind(entry.utc_offset.map(|o| o.whole_minutes()))
+            .bind(entry.repeat_count as i64)
+            .bind(entry.origin.map(|o| o.as_str()))
+            .bind(entry.expires_at.and_then(time_text))
+            .bind(entry.elapsed.map(|e| e.as_micros() as i64))
+            .bind(entry.event_id.map(|id| id.to_string()))
+            .bind(entry.trace_context.map(|c| c.trace_id_hex()))
+            .bind(entry.trace_context.map(|c| c.span_id_hex()))
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await
+}
+
+/// `time` in the format rusqlite stores it in, so `LogHandle` can read the rows.
+fn time_text(time: OffsetDateTime) -> Option<String> {
+    match time_value(time) {
+        Value::Text(text) => Some(text),
+        _ => None,
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Totals of a layer since it was built, see `Layer::stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Entries the sink accepted, including marker rows the layer writes itself.
+    pub written: u64,
+    /// Events rejected by field filters.
+    pub filtered: u64,
+    /// Events dropped by sampling or rate limits.
+    pub dropped: u64,
+    /// Entries the sink failed to write, whether or not a fallback took them.
+    pub write_errors: u64,
+    /// Mean time spent in the sink per entry, including flushes.
+    pub average_write_latency: Duration,
+    /// Where the time of an event goes, to tell slow sinks from slow layer settings.
+    pub stages: StageLatencies,
+}
+
+/// Mean time per event of each stage the layer runs, see [`Stats::stages`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StageLatencies {
+    /// Recording the fields of the event: formatting, redaction, truncation and context
+    /// providers.
+    pub record: Duration,
+    /// Field filters, rate limits, categorization and coalescing of the events that pass them.
+    pub filter: Duration,
+    /// `Connect::log`: serializing and inserting the entry for `Connection` sinks, handing it
+    /// to the queue for batching and async sinks.
+    pub sink: Duration,
+    /// `Connect::flush` after entries at the flush level, e.g. committing a batch.
+    pub flush: Duration,
+}
+
+/// The stages of [`StageLatencies`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Stage {
+    Record,
+    Filter,
+    Sink,
+    Flush,
+}
+
+#[derive(Debug, Default)]
+struct StageCounter {
+    count: AtomicU64,
+    nanos: AtomicU64,
+}
+
+impl StageCounter {
+    fn add(&self, elapsed: Duration) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn average(&self) -> Duration {
+        match self.count.load(Ordering::Relaxed) {
+            0 => Duration::ZERO,
+            n => Duration::from_nanos(self.nanos.load(Ordering::Relaxed) / n),
+        }
+    }
+}
+
+/// Reads the live [`Stats`] of a layer after it was moved into the dispatcher,
+/// see `Layer::stats_handle`.
+#[derive(Debug, Clone, Default)]
+pub struct StatsHandle(Arc<Counters>);
+
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    written: AtomicU64,
+    filtered: AtomicU64,
+    dropped: AtomicU64,
+    write_errors: AtomicU64,
+    write_nanos: AtomicU64,
+    stages: [StageCounter; 4],
+}
+
+impl StatsHandle {
+    pub fn get(&self) -> Stats {
+        let counters = &self.0;
+        let written = counters.written.load(Ordering::Relaxed);
+        let write_errors = counters.write_errors.load(Ordering::Relaxed);
+        let writes = written + write_errors;
+        let nanos = counters.write_nanos.load(Ordering::Relaxed);
+
+        Stats {
+            written,
+            filtered: counters.filtered.load(Ordering::Relaxed),
+            dropped: counters.dropped.load(Ordering::Relaxed),
+            write_errors,
+            average_write_latency: match writes {
+                0 => Duration::ZERO,
+                n => Duration::from_nanos(nanos / n),
+            },
+            stages: StageLatencies {
+                record: counters.stages[Stage::Record as usize].average(),
+                filter: counters.stages[Stage::Filter as usize].average(),
+                sink: counters.stages[Stage::Sink as usize].average(),
+                flush: counters.stages[Stage::Flush as usize].average(),
+            },
+        }
+    }
+
+    pub(crate) fn counters(&self) -> &Counters {
+        &self.0
+    }
+}
+
+impl Counters {
+    pub(crate) fn filtered(&self) {
+        self.filtered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dropped(&self) {
+        self.dropped_many(1);
+    }
+
+    pub(crate) fn dropped_many(&self, count: u64) {
+        self.dropped.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stage(&self, stage: Stage, elapsed: Duration) {
+        self.stages[stage as usize].add(elapsed);
+    }
+
+    pub(crate) fn write(&self, ok: bool, latency: Duration) {
+        let counter = if ok {
+            &self.written
+        } else {
+            &self.write_errors
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.write_nanos
+            .fetch_add(latency.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+/// When to write the next stats row, see `SubscriberBuilder::with_stats_interval`.
+#[derive(Debug)]
+pub(crate) struct StatsReport {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl StatsReport {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Mutex::new(Instant::now() + interval),
+        }
+    }
+
+    /// Whether a report is due, scheduling the next one if it is.
+    pub(crate) fn is_due(&self) -> bool {
+        let mut next = self.next.lock().unwrap();
+        let now = Instant::now();
+        if now < *next {
+            return false;
+        }
+
+        *next = now + self.interval;
+        true
+    }
+}
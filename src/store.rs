@@ -0,0 +1,40 @@
+use time::OffsetDateTime;
+
+use crate::{LogEntry, LogQuery, Tail, TailOptions};
+
+/// The read side of a log backend, mirroring `Connect` on the write side.
+///
+/// Viewers and exporters written against this trait work with [`LogHandle`](crate::LogHandle)
+/// and can be tested with [`MemoryConnect`](crate::MemoryConnect).
+pub trait LogStore {
+    /// Entries matching `query`, oldest first unless it orders them otherwise.
+    fn query(&self, query: &LogQuery) -> rusqlite::Result<Vec<LogEntry>>;
+
+    /// Follow entries matching `query` that are written from now on, delivered in batches.
+    fn tail(&self, query: LogQuery, options: TailOptions) -> rusqlite::Result<Tail>;
+
+    fn stats(&self) -> rusqlite::Result<StoreStats>;
+}
+
+/// Totals of a [`LogStore`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StoreStats {
+    pub rows: u64,
+    pub earliest: Option<OffsetDateTime>,
+    pub latest: Option<OffsetDateTime>,
+}
+
+/// What a [`Tail`] polls for new entries.
+pub(crate) trait TailSource: std::fmt::Debug + Send {
+    /// The id of the newest entry in the source of `query`, `0` if there are none.
+    fn last_id(&self, query: &LogQuery) -> rusqlite::Result<i64>;
+
+    /// Up to `limit` entries matching `query` with an id greater than `id`, and the highest id
+    /// looked at, so the next call skips entries that didn't match.
+    fn entries_after(
+        &self,
+        id: i64,
+        query: &LogQuery,
+        limit: u64,
+    ) -> rusqlite::Result<(i64, Vec<LogEntry>)>;
+}
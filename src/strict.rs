@@ -0,0 +1,144 @@
+use std::path::Path;
+
+use rusqlite::{Connection, OpenFlags};
+
+use crate::{prepare_database, LogHandle, LOGS_TABLE};
+
+/// A way the `logs_v0` table of a database differs from the one this version creates, see
+/// `LogHandle::schema_diff`. Definitions are the type followed by `NOT NULL` and `DEFAULT`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaDifference {
+    MissingTable,
+    /// Not migrated yet, `prepare_database` adds it.
+    MissingColumn {
+        name: String,
+        definition: String,
+    },
+    UnexpectedColumn {
+        name: String,
+        definition: String,
+    },
+    ChangedColumn {
+        name: String,
+        expected: String,
+        found: String,
+    },
+}
+
+impl std::fmt::Display for SchemaDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaDifference::MissingTable => write!(f, "table `{LOGS_TABLE}` is missing"),
+            SchemaDifference::MissingColumn { name, definition } => {
+                write!(f, "- {name} {definition}")
+            }
+            SchemaDifference::UnexpectedColumn { name, definition } => {
+                write!(f, "+ {name} {definition}")
+            }
+            SchemaDifference::ChangedColumn {
+                name,
+                expected,
+                found,
+            } => write!(f, "~ {name} {found}, expected {expected}"),
+        }
+    }
+}
+
+/// The error of `LogHandle::open_strict`, in a `rusqlite::Error::ToSqlConversionFailure`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaMismatch(pub Vec<SchemaDifference>);
+
+impl std::fmt::Display for SchemaMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the schema of `{LOGS_TABLE}` differs from this version:")?;
+        for difference in &self.0 {
+            write!(f, "\n{difference}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SchemaMismatch {}
+
+impl LogHandle {
+    /// Opens the existing database at `path` without creating or migrating anything, and
+    /// fails with a [`SchemaMismatch`] listing every difference if its `logs_v0` table isn't
+    /// exactly the one of this version, so schema drift is caught at startup.
+    pub fn open_strict(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+                | OpenFlags::SQLITE_OPEN_URI
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        let differences = schema_diff(&conn)?;
+        if !differences.is_empty() {
+            // `LogHandle` reports rusqlite errors, this is the variant that carries arbitrary ones
+            return Err(rusqlite::Error::ToSqlConversionFailure(Box::new(
+                SchemaMismatch(differences),
+            )));
+        }
+        Ok(Self::new(conn))
+    }
+
+    /// How the `logs_v0` table differs from the one this version creates, empty if it is
+    /// the same. The order of columns is not compared.
+    pub fn schema_diff(&self) -> rusqlite::Result<Vec<SchemaDifference>> {
+        schema_diff(&self.writer())
+    }
+}
+
+fn schema_diff(conn: &Connection) -> rusqlite::Result<Vec<SchemaDifference>> {
+    let expected = {
+        let reference = Connection::open_in_memory()?;
+        prepare_database(&reference)?;
+        columns(&reference)?
+    };
+    let found = columns(conn)?;
+    if found.is_empty() {
+        return Ok(vec![SchemaDifference::MissingTable]);
+    }
+
+    let mut differences = Vec::new();
+    for (name, definition) in &expected {
+        match found.iter().find(|(n, _)| n == name) {
+            None => differences.push(SchemaDifference::MissingColumn {
+                name: name.clone(),
+                definition: definition.clone(),
+            }),
+            // older versions declared `time` as text, see `TimePrecision`
+            Some((_, found)) if name == "time" && found == "TEXT NOT NULL" => {}
+            Some((_, found)) if found != definition => {
+                differences.push(SchemaDifference::ChangedColumn {
+                    name: name.clone(),
+                    expected: definition.clone(),
+                    found: found.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for (name, definition) in found {
+        if !expected.iter().any(|(n, _)| *n == name) {
+            differences.push(SchemaDifference::UnexpectedColumn { name, definition });
+        }
+    }
+    Ok(differences)
+}
+
+/// The columns of `logs_v0` with their definitions, empty if there is no such table.
+fn columns(conn: &Connection) -> rusqlite::Result<Vec<(String, String)>> {
+    conn.prepare("SELECT name, type, \"notnull\", dflt_value FROM pragma_table_info(?1)")?
+        .query_map([LOGS_TABLE], |row| {
+            let mut definition: String = row.get(1)?;
+            if row.get(2)? {
+                definition += " NOT NULL";
+            }
+            if let Some(default) = row.get::<_, Option<String>>(3)? {
+                definition += &format!(" DEFAULT {default}");
+            }
+            // `time` has no type
+            Ok((row.get(0)?, definition.trim_start().to_owned()))
+        })?
+        .collect()
+}
@@ -0,0 +1,221 @@
+use std::{borrow::Cow, collections::HashMap, sync::OnceLock};
+
+use crate::StructuredFormat;
+
+/// The structured key-value data of an entry.
+///
+/// Entries read from the database keep the raw JSON and only parse it when the fields are
+/// accessed, so consumers that only display messages don't pay for it.
+/// Entries passed to `Connect` (`Structured<&str>`) always have parsed fields.
+#[derive(Clone)]
+pub struct Structured<S = String> {
+    raw: Option<String>,
+    fields: OnceLock<HashMap<S, String>>,
+    /// How a written entry is stored.
+    format: StructuredFormat,
+}
+
+impl<S> Structured<S> {
+    pub fn from_fields(fields: HashMap<S, String>) -> Self {
+        Self {
+            raw: None,
+            fields: OnceLock::from(fields),
+            format: StructuredFormat::Json,
+        }
+    }
+
+    /// The JSON stored in the database, if this was read from one.
+    pub fn raw_json(&self) -> Option<&str> {
+        self.raw.as_deref()
+    }
+
+    /// Whether the fields have been parsed (or were never serialized).
+    pub fn is_parsed(&self) -> bool {
+        self.fields.get().is_some()
+    }
+}
+
+impl Structured<String> {
+    /// Wraps raw JSON without parsing it.
+    pub fn from_json(raw: String) -> Self {
+        Self {
+            raw: Some(raw),
+            fields: OnceLock::new(),
+            format: StructuredFormat::Json,
+        }
+    }
+
+    /// Reads the text of the `structured` column, failing unless it is a JSON object. The
+    /// fields are still only parsed on first access.
+    pub(crate) fn from_stored_json(raw: String) -> serde_json::Result<Self> {
+        serde_json::from_str::<serde::de::IgnoredAny>(&raw)?;
+        if !raw.trim_start().starts_with('{') {
+            return Err(serde::de::Error::custom("the fields are not a JSON object"));
+        }
+        Ok(Self::from_json(raw))
+    }
+
+    /// The fields, parsed on first access. Values other than strings are kept as their JSON
+    /// text. Text given to `from_json` that is not a JSON object is treated as no fields,
+    /// the original text is still available from `raw_json`.
+    pub fn fields(&self) -> &HashMap<String, String> {
+        self.fields.get_or_init(|| {
+            let Some(object) = self
+                .raw
+                .as_deref()
+                .and_then(|raw| serde_json::from_str::<serde_json::Map<_, _>>(raw).ok())
+            else {
+                return HashMap::new();
+            };
+            object
+                .into_iter()
+                .map(|(name, value)| match value {
+                    serde_json::Value::String(value) => (name, value),
+                    value => (name, value.to_string()),
+                })
+                .collect()
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields().get(key).map(String::as_str)
+    }
+
+    pub fn into_fields(mut self) -> HashMap<String, String> {
+        self.fields();
+        self.fields.take().unwrap_or_default()
+    }
+
+    /// The fields as JSON, without serializing them again if they were read from a database.
+    /// Values that are JSON numbers or booleans, e.g. recorded integers, are written as such.
+    pub fn to_json(&self) -> Cow<'_, str> {
+        match &self.raw {
+            Some(raw) => Cow::Borrowed(raw),
+            None => Cow::Owned(serde_json::to_string(&JsonFields(self.fields())).unwrap()),
+        }
+    }
+
+    /// Parses the fields if needed, the raw JSON is kept so writing it doesn't serialize again.
+    pub fn to_borrowed(&self) -> Structured<&str> {
+        Structured {
+            raw: self.raw.clone(),
+            format: self.format,
+            fields: OnceLock::from(
+                self.fields()
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), v.clone()))
+                    .collect::<HashMap<_, _>>(),
+            ),
+        }
+    }
+}
+
+impl<'a> Structured<&'a str> {
+    /// The fields of an entry that is being written, they are always parsed.
+    pub fn fields(&self) -> &HashMap<&'a str, String> {
+        self.fields
+            .get()
+            .expect("written entries always have parsed fields")
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields().get(key).map(String::as_str)
+    }
+
+    pub fn to_json(&self) -> Cow<'_, str> {
+        match &self.raw {
+            Some(raw) => Cow::Borrowed(raw),
+            None => Cow::Owned(serde_json::to_string(&JsonFields(self.fields())).unwrap()),
+        }
+    }
+
+    pub(crate) fn with_format(self, format: StructuredFormat) -> Self {
+        Self { format, ..self }
+    }
+
+    /// Whether the JSON of `to_sql` is to be stored as JSONB, see `db::supports_jsonb`.
+    pub(crate) fn prefers_jsonb(&self) -> bool {
+        self.format == StructuredFormat::Json
+    }
+
+    /// The value of the `structured` column: JSON, or the format the layer was built with.
+    pub(crate) fn to_sql(&self) -> rusqlite::types::Value {
+        match self.format.encode(self.fields()) {
+            Some(bytes) => rusqlite::types::Value::Blob(bytes),
+            None => rusqlite::types::Value::Text(self.to_json().into_owned()),
+        }
+    }
+
+    pub fn into_owned(self) -> Structured {
+        Structured {
+            raw: self.raw,
+            format: self.format,
+            fields: match self.fields.into_inner() {
+                Some(fields) => OnceLock::from(
+                    fields
+                        .into_iter()
+                        .map(|(k, v)| (k.to_owned(), v))
+                        .collect::<HashMap<_, _>>(),
+                ),
+                None => OnceLock::new(),
+            },
+        }
+    }
+}
+
+impl<S> Default for Structured<S> {
+    fn default() -> Self {
+        Self::from_fields(HashMap::new())
+    }
+}
+
+impl<S> From<HashMap<S, String>> for Structured<S> {
+    fn from(fields: HashMap<S, String>) -> Self {
+        Self::from_fields(fields)
+    }
+}
+
+impl<S: std::fmt::Debug> std::fmt::Debug for Structured<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.fields.get(), &self.raw) {
+            (Some(fields), _) => fields.fmt(f),
+            (None, Some(raw)) => f.debug_tuple("Unparsed").field(raw).finish(),
+            (None, None) => f.write_str("{}"),
+        }
+    }
+}
+
+/// Serializes fields with the values that are JSON numbers or booleans as such, so SQLite
+/// compares them by type. Integers that don't fit 64 bits stay strings to keep their digits.
+struct JsonFields<'a, S>(&'a HashMap<S, String>);
+
+impl<S: AsRef<str>> serde::Serialize for JsonFields<'_, S> {
+    fn serialize<Ser: serde::Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.collect_map(
+            self.0
+                .iter()
+                .map(|(name, value)| (name.as_ref(), JsonValue(value))),
+        )
+    }
+}
+
+struct JsonValue<'a>(&'a str);
+
+impl serde::Serialize for JsonValue<'_> {
+    fn serialize<Ser: serde::Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        match self.0 {
+            "true" => serializer.serialize_bool(true),
+            "false" => serializer.serialize_bool(false),
+            // `serde_json` skips whitespace around the number, it would be lost
+            text if text.trim() != text => serializer.serialize_str(text),
+            text => match serde_json::from_str::<serde_json::Number>(text) {
+                Ok(number)
+                    if number.is_i64() || number.is_u64() || text.contains(['.', 'e', 'E']) =>
+                {
+                    number.serialize(serializer)
+                }
+                _ => serializer.serialize_str(text),
+            },
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tracing::callsite::Identifier;
+
+/// Suppresses a message a callsite repeats more than `max_repeats` times within `window`,
+/// for `cooldown`, see `SubscriberBuilder::with_auto_suppression`.
+#[derive(Debug)]
+pub(crate) struct Suppressor {
+    max_repeats: u32,
+    window: Duration,
+    cooldown: Duration,
+    repeats: Mutex<HashMap<(Identifier, u64), Repeats>>,
+}
+
+#[derive(Debug)]
+struct Repeats {
+    window_start: Instant,
+    count: u32,
+    suppressed_until: Option<Instant>,
+    suppressed: u64,
+}
+
+pub(crate) enum Verdict {
+    /// The event may be written, `suppressed` repeats were dropped before it.
+    Allow {
+        suppressed: u64,
+    },
+    /// The first repeat over the limit, a suppression marker should be written.
+    Start,
+    Suppress,
+}
+
+/// Messages followed at most, older ones are forgotten once there are more.
+const MAX_TRACKED: usize = 4096;
+
+impl Suppressor {
+    pub(crate) fn new(max_repeats: u32, window: Duration, cooldown: Duration) -> Self {
+        Self {
+            max_repeats,
+            window,
+            cooldown,
+            repeats: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    pub(crate) fn check(&self, callsite: Identifier, message: &str) -> Verdict {
+        let mut hasher = DefaultHasher::new();
+        message.hash(&mut hasher);
+        let key = (callsite, hasher.finish());
+
+        let now = Instant::now();
+        let mut repeats = self.repeats.lock().unwrap();
+        if repeats.len() >= MAX_TRACKED && !repeats.contains_key(&key) {
+            repeats.retain(|_, r| {
+                r.suppressed_until.is_some_and(|until| now < until)
+                    || now.duration_since(r.window_start) < self.window
+            });
+        }
+        let entry = repeats.entry(key).or_insert(Repeats {
+            window_start: now,
+            count: 0,
+            suppressed_until: None,
+            suppressed: 0,
+        });
+
+        match entry.suppressed_until {
+            Some(until) if now < until => {
+                entry.suppressed += 1;
+                return Verdict::Suppress;
+            }
+            Some(_) => {
+                entry.suppressed_until = None;
+                entry.window_start = now;
+                entry.count = 0;
+            }
+            None if now.duration_since(entry.window_start) >= self.window => {
+                entry.window_start = now;
+                entry.count = 0;
+            }
+            None => {}
+        }
+
+        entry.count += 1;
+        if entry.count > self.max_repeats {
+            entry.suppressed_until = Some(now + self.cooldown);
+            entry.suppressed = 1;
+            return Verdict::Start;
+        }
+        Verdict::Allow {
+            suppressed: std::mem::take(&mut entry.suppressed),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{Connect, LogEntry};
+
+/// A `Connect` whose sink can be replaced while the layer runs, through a [`LayerHandle`],
+/// e.g. to roll over to a new file on command or to start a fresh database for a support session.
+pub struct SwitchConnect<C> {
+    current: Arc<Mutex<C>>,
+}
+
+/// Redirects the writes of the layer built with its [`SwitchConnect`], see `SwitchConnect::handle`.
+pub struct LayerHandle<C> {
+    current: Arc<Mutex<C>>,
+}
+
+impl<C> SwitchConnect<C> {
+    pub fn new(conn: C) -> Self {
+        Self {
+            current: Arc::new(Mutex::new(conn)),
+        }
+    }
+
+    /// A handle to switch the sink after the layer took ownership of this `SwitchConnect`.
+    pub fn handle(&self) -> LayerHandle<C> {
+        LayerHandle {
+            current: self.current.clone(),
+        }
+    }
+}
+
+impl<C: Connect> LayerHandle<C> {
+    /// Writes every entry after this call to `conn` instead, and returns the previous sink.
+    ///
+    /// The tables of `conn` are created and the previous sink is flushed first, no entry is
+    /// written while switching. If either fails, writes continue to go to the previous sink.
+    pub fn switch_database(&self, conn: C) -> rusqlite::Result<C> {
+        conn.create_tables()?;
+        let mut current = self.current.lock().unwrap();
+        current.flush()?;
+        Ok(std::mem::replace(&mut *current, conn))
+    }
+
+    /// Runs `f` with the current sink, no entry is written meanwhile.
+    pub fn with_current<T>(&self, f: impl FnOnce(&C) -> T) -> T {
+        f(&self.current.lock().unwrap())
+    }
+}
+
+impl<C> Clone for LayerHandle<C> {
+    fn clone(&self) -> Self {
+        Self {
+            current: self.current.clone(),
+        }
+    }
+}
+
+impl<C: Connect> Connect for SwitchConnect<C> {
+    fn log(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self.current.lock().unwrap().log(entry)
+    }
+
+    fn flush(&self) -> rusqlite::Result<()> {
+        self.current.lock().unwrap().flush()
+    }
+
+    fn flush_and_wait(&self, timeout: Duration) -> rusqlite::Result<()> {
+        self.current.lock().unwrap().flush_and_wait(timeout)
+    }
+
+    fn create_tables(&self) -> rusqlite::Result<()> {
+        self.current.lock().unwrap().create_tables()
+    }
+
+    fn log_or_prepare(&self, entry: LogEntry<&str>) -> rusqlite::Result<()> {
+        self.current.lock().unwrap().log_or_prepare(entry)
+    }
+
+    fn take_dropped(&self) -> u64 {
+        self.current.lock().unwrap().take_dropped()
+    }
+
+    fn keeps_failed_entries(&self) -> bool {
+        self.current.lock().unwrap().keeps_failed_entries()
+    }
+}
+
+impl<C> std::fmt::Debug for SwitchConnect<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SwitchConnect")
+    }
+}
+
+impl<C> std::fmt::Debug for LayerHandle<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("LayerHandle")
+    }
+}
@@ -0,0 +1,112 @@
+use std::time::{Duration, Instant};
+
+use crate::{store::TailSource, Cursor, LogEntry, LogQuery};
+
+/// How `LogStore::tail` delivers new entries.
+#[derive(Debug, Clone, Copy)]
+pub struct TailOptions {
+    /// A batch is delivered as soon as it has this many entries.
+    pub max_batch: usize,
+    /// A batch is delivered at most this long after its first entry was seen,
+    /// even if it is not full. The database is polled four times as often.
+    pub max_latency: Duration,
+}
+
+impl Default for TailOptions {
+    fn default() -> Self {
+        Self {
+            max_batch: 100,
+            max_latency: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Entries written after the tail was started, see `LogStore::tail`.
+///
+/// Iterating blocks until the next batch is ready.
+#[derive(Debug)]
+pub struct Tail {
+    source: Box<dyn TailSource>,
+    query: LogQuery,
+    options: TailOptions,
+    last_rowid: i64,
+}
+
+impl Tail {
+    pub(crate) fn new(
+        source: impl TailSource + 'static,
+        query: LogQuery,
+        options: TailOptions,
+    ) -> rusqlite::Result<Self> {
+        let last_rowid = source.last_id(&query)?;
+        Ok(Self {
+            source: Box::new(source),
+            query,
+            options,
+            last_rowid,
+        })
+    }
+
+    /// The last entry the tail has seen: the next batch starts after it. Right after the tail was
+    /// started, pass it to `LogQuery::up_to` for the entries before, without a gap or overlap.
+    pub fn position(&self) -> Cursor {
+        Cursor {
+            rowid: self.last_rowid,
+        }
+    }
+
+    /// Blocks until at least one new entry matches, then collects more for up to `max_latency`.
+    pub fn next_batch(&mut self) -> rusqlite::Result<Vec<LogEntry>> {
+        let poll_interval = (self.options.max_latency / 4).max(Duration::from_millis(1));
+        let max_batch = self.options.max_batch.max(1);
+
+        let mut batch = Vec::new();
+        let mut first_seen = None;
+        loop {
+            let wanted = (max_batch - batch.len()) as u64;
+            let (scanned, entries) =
+                self.source
+                    .entries_after(self.last_rowid, &self.query, wanted)?;
+            self.last_rowid = scanned;
+            if !entries.is_empty() {
+                first_seen.get_or_insert_with(Instant::now);
+            }
+            batch.extend(entries);
+
+            if batch.len() >= max_batch {
+                return Ok(batch);
+            }
+
+            match first_seen {
+                Some(first_seen) => {
+                    let elapsed = first_seen.elapsed();
+                    if elapsed >= self.options.max_latency {
+                        return Ok(batch);
+                    }
+                    std::thread::sleep(poll_interval.min(self.options.max_latency - elapsed));
+                }
+                None => std::thread::sleep(poll_interval),
+            }
+        }
+    }
+
+    /// Up to `max_batch` new entries without waiting, possibly none, e.g. for an event loop
+    /// that polls between other work.
+    pub fn try_next_batch(&mut self) -> rusqlite::Result<Vec<LogEntry>> {
+        let (scanned, entries) = self.source.entries_after(
+            self.last_rowid,
+            &self.query,
+            self.options.max_batch.max(1) as u64,
+        )?;
+        self.last_rowid = scanned;
+        Ok(entries)
+    }
+}
+
+impl Iterator for Tail {
+    type Item = rusqlite::Result<Vec<LogEntry>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_batch())
+    }
+}
@@ -0,0 +1,167 @@
+use rusqlite::{
+    types::{FromSql, FromSqlError, FromSqlResult, Type, Value, ValueRef},
+    Connection, OptionalExtension,
+};
+use std::time::Duration;
+
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+
+use crate::{query::time_value, LOGS_TABLE};
+
+/// An RFC 3339 time, or an age like `30s`, `15m`, `12h` or `7d` before now, as the
+/// `tracing-sqlite` tool and [`LogServer`](crate::LogServer) accept them.
+pub fn parse_time(text: &str) -> Result<OffsetDateTime, String> {
+    if let Ok(time) = OffsetDateTime::parse(text, &Rfc3339) {
+        return Ok(time);
+    }
+    let invalid = || format!("invalid time {text:?}, expected RFC 3339 or an age like 12h");
+    let unit = match text.chars().last().ok_or_else(invalid)? {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    let count: u64 = text[..text.len() - 1].parse().map_err(|_| invalid())?;
+    Ok(OffsetDateTime::now_utc() - Duration::from_secs(count.saturating_mul(unit)))
+}
+
+/// How the `time` column stores timestamps, see `SubscriberBuilder::with_time_precision`.
+///
+/// Integers are smaller than text and compare faster. Rows of different precisions can be
+/// mixed in one table, e.g. after switching an existing database, readers and query filters
+/// handle each row by the type of its value. Integers from 10^17 on are read as nanoseconds
+/// and smaller ones as microseconds, so `Micros` covers the years up to 5138 and `Nanos`
+/// those from 1973 on.
+///
+/// Databases created by versions before integer timestamps declare `time` as `TEXT`, which
+/// turns integers into text. They can only be written with `Text`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TimePrecision {
+    /// Text in the format rusqlite stores `OffsetDateTime` in, understood by SQLite's date
+    /// and time functions.
+    #[default]
+    Text,
+    /// Integer microseconds since the Unix epoch.
+    Micros,
+    /// Integer nanoseconds since the Unix epoch.
+    Nanos,
+}
+
+/// The offset `LogQuery::timezone` presents times in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Timezone {
+    /// UTC, as times are stored.
+    #[default]
+    Utc,
+    /// The UTC offset the writer recorded with each entry, see
+    /// `SubscriberBuilder::with_utc_offset`. UTC for entries without one.
+    Writer,
+    Fixed(UtcOffset),
+}
+
+impl Timezone {
+    /// The current local offset of this system, UTC if it can't be detected, e.g. on Unix
+    /// once several threads are running.
+    pub fn local() -> Self {
+        Timezone::Fixed(UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC))
+    }
+
+    /// The offset of an entry whose writer recorded `writer`.
+    pub fn offset(self, writer: Option<UtcOffset>) -> UtcOffset {
+        match self {
+            Timezone::Utc => UtcOffset::UTC,
+            Timezone::Writer => writer.unwrap_or(UtcOffset::UTC),
+            Timezone::Fixed(offset) => offset,
+        }
+    }
+}
+
+/// The smallest integer read as nanoseconds.
+pub(crate) const NANOS_FROM: i64 = 100_000_000_000_000_000;
+
+/// `time` as text, for SQLite's date and time functions. Integers lose their fraction of a
+/// second.
+pub(crate) const TIME_TEXT: &str = "CASE WHEN typeof(time) != 'integer' THEN time \
+    WHEN abs(time) >= 100000000000000000 THEN datetime(time / 1000000000, 'unixepoch') \
+    ELSE datetime(time / 1000000, 'unixepoch') END";
+
+impl TimePrecision {
+    /// `time` in this precision.
+    pub(crate) fn to_sql(self, time: OffsetDateTime) -> Value {
+        let nanos = time.unix_timestamp_nanos();
+        let integer = |n: i128| Value::Integer(n.clamp(i64::MIN.into(), i64::MAX.into()) as i64);
+        match self {
+            TimePrecision::Text => time_value(time),
+            TimePrecision::Micros => integer(nanos / 1000),
+            TimePrecision::Nanos => integer(nanos),
+        }
+    }
+
+    /// The precision a value of the `time` column was stored in.
+    pub(crate) fn of(value: ValueRef<'_>) -> Self {
+        match value {
+            ValueRef::Integer(i) if i.unsigned_abs() >= NANOS_FROM as u64 => TimePrecision::Nanos,
+            ValueRef::Integer(_) => TimePrecision::Micros,
+            _ => TimePrecision::Text,
+        }
+    }
+}
+
+/// A value of the `time` column in any precision.
+pub(crate) struct StoredTime(pub(crate) OffsetDateTime);
+
+impl FromSql for StoredTime {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let nanos = match (TimePrecision::of(value), value) {
+            (TimePrecision::Nanos, ValueRef::Integer(i)) => i128::from(i),
+            (TimePrecision::Micros, ValueRef::Integer(i)) => i128::from(i) * 1000,
+            _ => return OffsetDateTime::column_result(value).map(StoredTime),
+        };
+        OffsetDateTime::from_unix_timestamp_nanos(nanos)
+            .map(StoredTime)
+            .map_err(|e| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+/// The condition comparing `time` to a time with `op`, e.g. `>=`, with the three parameters
+/// of `time_params`.
+pub(crate) fn time_condition(op: &str) -> String {
+    format!(
+        "time {op} CASE WHEN typeof(time) != 'integer' THEN ? \
+         WHEN abs(time) >= {NANOS_FROM} THEN ? ELSE ? END"
+    )
+}
+
+/// The parameters of `time_condition`.
+pub(crate) fn time_params(time: OffsetDateTime) -> [Value; 3] {
+    [
+        TimePrecision::Text.to_sql(time),
+        TimePrecision::Nanos.to_sql(time),
+        TimePrecision::Micros.to_sql(time),
+    ]
+}
+
+/// Fails with `InvalidColumnType` if `precision` stores integers and the `time` column of
+/// `logs_v0` is declared as `TEXT`.
+pub(crate) fn check_time_column(
+    conn: &Connection,
+    precision: TimePrecision,
+) -> rusqlite::Result<()> {
+    if precision == TimePrecision::Text {
+        return Ok(());
+    }
+    let declared: Option<String> = conn
+        .query_row(
+            "SELECT type FROM pragma_table_info(?1) WHERE name = 'time'",
+            [LOGS_TABLE],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match declared {
+        Some(declared) if declared.eq_ignore_ascii_case("text") => Err(
+            rusqlite::Error::InvalidColumnType(0, "time".to_owned(), Type::Text),
+        ),
+        _ => Ok(()),
+    }
+}
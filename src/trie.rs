@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+/// Module lists with more items than this are matched with a [`PrefixTrie`].
+pub const TRIE_THRESHOLD: usize = 64;
+
+/// A set of module prefixes matched in `O(len(module))`, for lists with thousands of items.
+///
+/// Build it once, e.g. from a config file, and share it between layers with an `Arc`.
+#[derive(Debug, Clone)]
+pub struct PrefixTrie {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    /// Sorted by byte, pointing into `PrefixTrie::nodes`.
+    children: Vec<(u8, usize)>,
+    terminal: bool,
+}
+
+impl PrefixTrie {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node::default()],
+        }
+    }
+
+    pub fn insert(&mut self, prefix: &str) {
+        let mut current = 0;
+        for byte in prefix.bytes() {
+            current = match self.nodes[current]
+                .children
+                .binary_search_by_key(&byte, |(b, _)| *b)
+            {
+                Ok(i) => self.nodes[current].children[i].1,
+                Err(i) => {
+                    let next = self.nodes.len();
+                    self.nodes.push(Node::default());
+                    self.nodes[current].children.insert(i, (byte, next));
+                    next
+                }
+            };
+        }
+        self.nodes[current].terminal = true;
+    }
+
+    /// Whether any prefix in the trie is a prefix of `module`.
+    pub fn matches(&self, module: &str) -> bool {
+        let mut current = &self.nodes[0];
+        for byte in module.bytes() {
+            if current.terminal {
+                return true;
+            }
+            match current.children.binary_search_by_key(&byte, |(b, _)| *b) {
+                Ok(i) => current = &self.nodes[current.children[i].1],
+                Err(_) => return false,
+            }
+        }
+        current.terminal
+    }
+}
+
+impl Default for PrefixTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: AsRef<str>> FromIterator<S> for PrefixTrie {
+    fn from_iter<T: IntoIterator<Item = S>>(iter: T) -> Self {
+        let mut trie = Self::new();
+        for prefix in iter {
+            trie.insert(prefix.as_ref());
+        }
+        trie
+    }
+}
+
+/// A black or white list of module prefixes.
+#[derive(Debug, Clone)]
+pub enum ModuleList {
+    /// Matched by checking every item, fine for short lists.
+    Linear(Box<[&'static str]>),
+    Trie(Arc<PrefixTrie>),
+}
+
+impl ModuleList {
+    /// Uses a trie when there are more than [`TRIE_THRESHOLD`] items.
+    pub fn new(modules: impl IntoIterator<Item = &'static str>) -> Self {
+        let modules: Box<[_]> = modules.into_iter().collect();
+        if modules.len() > TRIE_THRESHOLD {
+            ModuleList::Trie(Arc::new(modules.iter().collect()))
+        } else {
+            ModuleList::Linear(modules)
+        }
+    }
+
+    /// Whether any item of the list is a prefix of `module`.
+    pub fn matches(&self, module: &str) -> bool {
+        match self {
+            ModuleList::Linear(modules) => modules.iter().any(|m| module.starts_with(m)),
+            ModuleList::Trie(trie) => trie.matches(module),
+        }
+    }
+}
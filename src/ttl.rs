@@ -0,0 +1,31 @@
+use rusqlite::Connection;
+use time::OffsetDateTime;
+
+use crate::{LogHandle, LOGS_TABLE};
+
+impl LogHandle {
+    /// Deletes the entries whose `expires_at` has passed, returning how many were deleted.
+    ///
+    /// Events set it by recording a number of seconds in the `log_ttl_secs` field, e.g.
+    /// `tracing::debug!(log_ttl_secs = 3600, payload = ?body)`, entries without it never
+    /// expire. Call it regularly to keep verbose entries for a short time only, or let
+    /// `SubscriberBuilder::with_expiry_sweep` do it.
+    ///
+    /// The newest entry is kept even if it expired, so rowids are not reused and shipping
+    /// watermarks (see `LogHandle::unshipped`) stay valid.
+    pub fn delete_expired(&self) -> rusqlite::Result<u64> {
+        delete_expired(&self.writer())
+    }
+}
+
+/// `LogHandle::delete_expired` on `conn`.
+pub(crate) fn delete_expired(conn: &Connection) -> rusqlite::Result<u64> {
+    let deleted = conn.execute(
+        &format!(
+            "DELETE FROM {LOGS_TABLE} WHERE expires_at <= ?1
+             AND rowid < (SELECT max(rowid) FROM {LOGS_TABLE})"
+        ),
+        [OffsetDateTime::now_utc()],
+    )?;
+    Ok(deleted as u64)
+}
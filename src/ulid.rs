@@ -0,0 +1,89 @@
+use std::sync::Mutex;
+
+use time::OffsetDateTime;
+
+/// The unique id of an event, a [ULID](https://github.com/ulid/spec): the millisecond it
+/// happened and 80 random bits, stored in the `event_id` column.
+///
+/// Ids sort by time, and the ids one layer generates in the same millisecond increase, so
+/// they also sort in the order the events happened. They stay the same when rows are
+/// merged, archived or exported, see `LogHandle::merge_from`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ulid(pub u128);
+
+const RANDOM_BITS: u32 = 80;
+
+/// Crockford's base 32, without I, L, O and U.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+impl Ulid {
+    /// A new id for an event at `time`, with random bits from `fastrand`.
+    pub fn new(time: OffsetDateTime) -> Self {
+        Self(timestamp_ms(time) << RANDOM_BITS | random())
+    }
+
+    /// Milliseconds since the Unix epoch.
+    pub fn timestamp_ms(&self) -> u64 {
+        (self.0 >> RANDOM_BITS) as u64
+    }
+}
+
+impl std::fmt::Display for Ulid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut text = [0; 26];
+        for (i, c) in text.iter_mut().enumerate() {
+            let shift = 5 * (25 - i);
+            *c = ALPHABET[(self.0 >> shift) as usize & 31];
+        }
+        f.write_str(std::str::from_utf8(&text).unwrap())
+    }
+}
+
+impl std::str::FromStr for Ulid {
+    type Err = String;
+
+    /// Parses the 26 characters of `Display`, in either case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid ULID {s:?}");
+        if s.len() != 26 || s.as_bytes()[0] > b'7' {
+            return Err(invalid());
+        }
+        s.bytes()
+            .try_fold(0u128, |id, c| {
+                let c = c.to_ascii_uppercase();
+                let digit = ALPHABET.iter().position(|&a| a == c).ok_or_else(invalid)?;
+                Ok(id << 5 | digit as u128)
+            })
+            .map(Ulid)
+    }
+}
+
+/// Generates increasing ids for the events of one layer.
+#[derive(Debug, Default)]
+pub(crate) struct UlidGenerator {
+    last: Mutex<Option<Ulid>>,
+}
+
+impl UlidGenerator {
+    /// A new id, one more than the last one if that is from the same millisecond.
+    pub(crate) fn generate(&self, time: OffsetDateTime) -> Ulid {
+        let mut last = self.last.lock().unwrap();
+        let id = match *last {
+            Some(Ulid(previous)) if previous >> RANDOM_BITS == timestamp_ms(time) => {
+                Ulid(previous.wrapping_add(1))
+            }
+            _ => Ulid::new(time),
+        };
+        *last = Some(id);
+        id
+    }
+}
+
+/// The 48 bit timestamp of `time`, times before 1970 are 0.
+fn timestamp_ms(time: OffsetDateTime) -> u128 {
+    (time.unix_timestamp_nanos() / 1_000_000).clamp(0, (1 << 48) - 1) as u128
+}
+
+fn random() -> u128 {
+    fastrand::u128(..) & ((1 << RANDOM_BITS) - 1)
+}
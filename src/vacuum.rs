@@ -0,0 +1,66 @@
+use rusqlite::Connection;
+
+use crate::LogHandle;
+
+/// When the layer returns the space of deleted rows to the file system, see
+/// `SubscriberBuilder::with_vacuum_policy`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VacuumPolicy {
+    /// Switch the database to `PRAGMA auto_vacuum = INCREMENTAL` and release free pages with
+    /// `PRAGMA incremental_vacuum`. Cheap, but doesn't defragment.
+    Incremental,
+    /// `VACUUM` once at least this fraction (`0.0..=1.0`) of the pages is free. Rewrites the
+    /// whole database and needs as much free disk space as it is large.
+    FreeRatio(f64),
+}
+
+impl LogHandle {
+    /// Rebuilds the database through the connection the handle writes with, so the file only
+    /// takes the space its rows need. Writers wait until it is done.
+    pub fn vacuum(&self) -> rusqlite::Result<()> {
+        self.writer().execute_batch("VACUUM")
+    }
+
+    /// Releases the free pages left by deleted rows if they are at least `min_free_ratio` of
+    /// the database, with `PRAGMA incremental_vacuum` if the database is in incremental
+    /// auto-vacuum mode and `VACUUM` otherwise. Returns whether space was released.
+    pub fn reclaim_space(&self, min_free_ratio: f64) -> rusqlite::Result<bool> {
+        reclaim_space(&self.writer(), min_free_ratio)
+    }
+}
+
+pub(crate) fn reclaim_space(conn: &Connection, min_free_ratio: f64) -> rusqlite::Result<bool> {
+    let (free, pages, auto_vacuum): (i64, i64, i64) = conn.query_row(
+        "SELECT * FROM pragma_freelist_count(), pragma_page_count(), pragma_auto_vacuum()",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    if free == 0 || (free as f64) < min_free_ratio * pages as f64 {
+        return Ok(false);
+    }
+
+    match auto_vacuum {
+        // INCREMENTAL, it releases pages while it is stepped
+        2 => {
+            let mut stmt = conn.prepare("PRAGMA incremental_vacuum")?;
+            let mut rows = stmt.query([])?;
+            while rows.next()?.is_some() {}
+        }
+        _ => conn.execute_batch("VACUUM")?,
+    }
+    Ok(true)
+}
+
+/// Switches to incremental auto-vacuum, existing databases are rebuilt once for that.
+pub(crate) fn enable_incremental(conn: &Connection) -> rusqlite::Result<()> {
+    let auto_vacuum: i64 = conn.pragma_query_value(None, "auto_vacuum", |row| row.get(0))?;
+    if auto_vacuum == 2 {
+        return Ok(());
+    }
+    conn.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
+    let pages: i64 = conn.pragma_query_value(None, "page_count", |row| row.get(0))?;
+    if pages > 0 {
+        conn.execute_batch("VACUUM")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::{fs, io, path::Path};
+
+use crate::{export::io_error, LogHandle};
+
+const VIEWER: &str = include_str!("../viewer/viewer.html");
+
+/// The database file in a viewer bundle.
+pub const BUNDLE_DATABASE: &str = "logs.db";
+
+/// The viewer page in a viewer bundle.
+pub const BUNDLE_VIEWER: &str = "viewer.html";
+
+impl LogHandle {
+    /// Writes a snapshot of the database to `dir` as [`BUNDLE_DATABASE`], and [`BUNDLE_VIEWER`],
+    /// a page to browse and filter its `logs_v0` entries, e.g. to send them to a customer.
+    ///
+    /// The page contains a copy of the database and works opened straight from disk, it loads
+    /// sql.js from cdnjs so it needs an internet connection. `dir` is created if needed, existing
+    /// bundle files are replaced.
+    pub fn export_viewer_bundle(&self, dir: impl AsRef<Path>) -> rusqlite::Result<()> {
+        let dir = dir.as_ref();
+        let database = dir.join(BUNDLE_DATABASE);
+        fs::create_dir_all(dir).map_err(io_error)?;
+        match fs::remove_file(&database) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(io_error(e)),
+            _ => {}
+        }
+
+        let target = database
+            .to_str()
+            .ok_or(rusqlite::Error::InvalidPath(database.clone()))?;
+        self.with_reader(|conn| conn.execute("VACUUM INTO ?1", [target]))?;
+
+        let bytes = fs::read(&database).map_err(io_error)?;
+        let page = VIEWER.replace("{{DATABASE}}", &base64(&bytes));
+        fs::write(dir.join(BUNDLE_VIEWER), page).map_err(io_error)
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = u32::from(chunk[0]) << 16
+            | u32::from(chunk.get(1).copied().unwrap_or(0)) << 8
+            | u32::from(chunk.get(2).copied().unwrap_or(0));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,84 @@
+use rusqlite::Connection;
+
+use crate::{
+    db::interned, format::STRUCTURED_JSON, query::field_expression, timestamp::NANOS_FROM,
+    LOGS_TABLE,
+};
+
+/// `logs_v0` with readable columns, see [`prepare_views`].
+pub const READABLE_VIEW: &str = "logs_readable";
+
+/// The `WARN` and `ERROR` rows of [`READABLE_VIEW`], newest first.
+pub const WARNINGS_VIEW: &str = "logs_warnings";
+
+/// The rows of [`READABLE_VIEW`] from the last hour, newest first.
+pub const RECENT_VIEW: &str = "logs_recent";
+
+/// The rows of [`READABLE_VIEW`] with a column per structured field given to [`prepare_views`].
+pub const FIELDS_VIEW: &str = "logs_fields";
+
+/// Creates views of `logs_v0` as starting points for people opening the database in a
+/// SQLite browser: [`READABLE_VIEW`], [`WARNINGS_VIEW`], [`RECENT_VIEW`] and [`FIELDS_VIEW`],
+/// with a column per field in `fields`, e.g. `request_id`.
+///
+/// Readable rows have an `id` (the rowid), `time` as UTC text with milliseconds in any
+/// `TimePrecision`, `local_time` in the UTC offset the writer recorded (UTC if it recorded
+/// none), module and file resolved from the strings table, and `fields` as JSON text
+/// (`NULL` for the binary formats). Existing views are replaced, so they follow the current
+/// schema. Called by `SubscriberBuilder::build_prepared` with the fields of `with_views`,
+/// call it after `prepare_database` for other sinks.
+pub fn prepare_views(conn: &Connection, fields: &[&str]) -> rusqlite::Result<()> {
+    let time = format!(
+        "CASE WHEN typeof(time) != 'integer' THEN strftime('%Y-%m-%d %H:%M:%f', time) \
+         WHEN abs(time) >= {NANOS_FROM} THEN strftime('%Y-%m-%d %H:%M:%f', time / 1e9, 'unixepoch') \
+         ELSE strftime('%Y-%m-%d %H:%M:%f', time / 1e6, 'unixepoch') END"
+    );
+    let module = interned("module", "main");
+    let file = interned("file", "main");
+    let flattened: String = fields
+        .iter()
+        .map(|field| {
+            let value = field_expression(field);
+            format!(
+                ",\n CASE WHEN json_valid(structured, 5) THEN \
+                 CASE WHEN json_valid({value}) THEN {value} ->> '$' ELSE {value} END \
+                 END AS \"{}\"",
+                field.replace('"', "\"\"")
+            )
+        })
+        .collect();
+
+    conn.execute_batch(&format!(
+        "DROP VIEW IF EXISTS {FIELDS_VIEW};
+         DROP VIEW IF EXISTS {RECENT_VIEW};
+         DROP VIEW IF EXISTS {WARNINGS_VIEW};
+         DROP VIEW IF EXISTS {READABLE_VIEW};
+         CREATE VIEW {READABLE_VIEW} AS SELECT
+             rowid AS id,
+             {time} AS time,
+             CASE WHEN utc_offset IS NULL THEN {time}
+                 ELSE strftime('%Y-%m-%d %H:%M:%f', {time}, utc_offset || ' minutes') END AS local_time,
+             level,
+             {module} AS module,
+             {file} AS file,
+             line,
+             message,
+             CASE WHEN json_valid({STRUCTURED_JSON}, 5) THEN json({STRUCTURED_JSON}) END AS fields,
+             category,
+             repeat_count,
+             origin,
+             expires_at,
+             elapsed_us,
+             event_id,
+             trace_id,
+             span_id
+         FROM {LOGS_TABLE};
+         CREATE VIEW {WARNINGS_VIEW} AS SELECT * FROM {READABLE_VIEW}
+             WHERE level IN ('ERROR', 'WARN') ORDER BY id DESC;
+         CREATE VIEW {RECENT_VIEW} AS SELECT * FROM {READABLE_VIEW}
+             WHERE time >= strftime('%Y-%m-%d %H:%M:%f', 'now', '-1 hour') ORDER BY id DESC;
+         CREATE VIEW {FIELDS_VIEW} AS SELECT
+             rowid AS id, {time} AS time, level, {module} AS module, message{flattened}
+         FROM {LOGS_TABLE};"
+    ))
+}
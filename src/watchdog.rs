@@ -0,0 +1,119 @@
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use rusqlite::Connection;
+
+/// Interrupts a statement of a layer write that has been running on the connection for longer
+/// than `timeout`.
+///
+/// The connection's trace callback records when each statement of a write starts, and its
+/// progress handler checks the deadline. Both only run on the thread executing a statement,
+/// i.e. the one holding the connection, so the time spent waiting for the connection doesn't
+/// count, and reads, checkpoints or later writes on the same connection are never interrupted
+/// for a write that timed out.
+///
+/// The progress handler runs between VM instructions: a statement blocked in the file system,
+/// e.g. an `fsync` that doesn't return, is only interrupted once the call returns.
+pub(crate) struct Watchdog {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    epoch: Instant,
+    /// The thread running a statement of a write, see `thread_token`; `0` if there is none.
+    writer: AtomicU64,
+    /// When that statement started, in microseconds since `epoch`.
+    started: AtomicU64,
+    timeout: Duration,
+}
+
+pub(crate) struct WatchGuard<'a> {
+    shared: &'a Shared,
+}
+
+/// VM instructions between two checks of the deadline.
+const CHECK_INTERVAL: i32 = 1000;
+
+thread_local! {
+    /// The watchdog of the write on this thread, for the trace callback.
+    static ARMED: RefCell<Option<Arc<Shared>>> = const { RefCell::new(None) };
+    static TOKEN: u8 = const { 0 };
+}
+
+/// A number identifying the current thread among the running ones, never `0`.
+fn thread_token() -> u64 {
+    TOKEN.with(|token| token as *const u8 as u64)
+}
+
+impl Watchdog {
+    /// Sets the connection's trace callback and progress handler.
+    pub(crate) fn new(conn: &mut Connection, timeout: Duration) -> Self {
+        let shared = Arc::new(Shared {
+            epoch: Instant::now(),
+            writer: AtomicU64::new(0),
+            started: AtomicU64::new(0),
+            timeout,
+        });
+
+        conn.trace(Some(statement_started));
+        let watched = shared.clone();
+        conn.progress_handler(CHECK_INTERVAL, Some(move || watched.timed_out()));
+        Self { shared }
+    }
+
+    /// Starts watching a write of the current thread, which ends when the guard is dropped.
+    pub(crate) fn arm(&self) -> WatchGuard<'_> {
+        ARMED.with(|armed| *armed.borrow_mut() = Some(self.shared.clone()));
+        WatchGuard {
+            shared: &self.shared,
+        }
+    }
+}
+
+/// The trace callback: a statement starts on the current thread.
+fn statement_started(_sql: &str) {
+    ARMED.with(|armed| {
+        if let Some(shared) = &*armed.borrow() {
+            let now = shared.epoch.elapsed().as_micros() as u64;
+            shared.started.store(now, Ordering::Relaxed);
+            shared.writer.store(thread_token(), Ordering::Release);
+        }
+    });
+}
+
+impl Shared {
+    /// Whether the statement of the current thread is part of a write past its deadline.
+    fn timed_out(&self) -> bool {
+        if self.writer.load(Ordering::Acquire) != thread_token() {
+            return false;
+        }
+        let started = Duration::from_micros(self.started.load(Ordering::Relaxed));
+        self.epoch.elapsed().saturating_sub(started) >= self.timeout
+    }
+}
+
+impl Drop for WatchGuard<'_> {
+    fn drop(&mut self) {
+        ARMED.with(|armed| armed.borrow_mut().take());
+        let _ = self.shared.writer.compare_exchange(
+            thread_token(),
+            0,
+            Ordering::Release,
+            Ordering::Relaxed,
+        );
+    }
+}
+
+impl std::fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("timeout", &self.shared.timeout)
+            .finish_non_exhaustive()
+    }
+}
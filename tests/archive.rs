@@ -0,0 +1,65 @@
+use std::{path::PathBuf, thread, time::Duration};
+
+use rusqlite::Connection;
+use time::OffsetDateTime;
+use tracing_subscriber_sqlite::{LogHandle, SubscriberBuilder};
+
+fn archive() -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "tracing-sqlite-test-archive-{}.db",
+        std::process::id()
+    ))
+}
+
+fn messages(handle: &LogHandle) -> Vec<String> {
+    handle
+        .read_logs()
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.message)
+        .collect()
+}
+
+#[test]
+fn archive_moves_older_entries() {
+    let handle = LogHandle::shared_memory("archive_moves_older_entries").unwrap();
+    let subscriber = SubscriberBuilder::new().build(handle.clone());
+    let cutoff = tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(user = %"alice", "old");
+        thread::sleep(Duration::from_millis(10));
+        let cutoff = OffsetDateTime::now_utc();
+        thread::sleep(Duration::from_millis(10));
+        tracing::info!("new");
+        cutoff
+    });
+
+    let path = archive();
+    let moved = handle.archive_to(&path, cutoff);
+    let archived = LogHandle::new(Connection::open(&path).unwrap()).read_logs();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(moved.unwrap(), 1);
+    assert_eq!(messages(&handle), ["new"]);
+    let archived = archived.unwrap();
+    assert_eq!(archived.len(), 1);
+    assert_eq!(archived[0].message, "old");
+    assert_eq!(archived[0].module.as_deref(), Some("archive"));
+    assert_eq!(archived[0].structured.get("user"), Some("alice"));
+}
+
+#[test]
+fn archive_keeps_the_newest_entry() {
+    let handle = LogHandle::shared_memory("archive_keeps_the_newest_entry").unwrap();
+    let subscriber = SubscriberBuilder::new().build(handle.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("first");
+        tracing::info!("second");
+    });
+
+    let path = archive().with_extension("newest.db");
+    let moved = handle.archive_to(&path, OffsetDateTime::now_utc());
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(moved.unwrap(), 1);
+    assert_eq!(messages(&handle), ["second"]);
+}
@@ -0,0 +1,111 @@
+#![cfg(feature = "tokio")]
+
+use std::{sync::mpsc, thread, time::Duration};
+
+use rusqlite::Connection;
+use tracing_subscriber_sqlite::{Connect, LogHandle, SubscriberBuilder, TokioConnect};
+
+/// A connection for the writer task and a handle reading what it wrote.
+fn prepared(name: &str) -> (Connection, LogHandle) {
+    let handle = LogHandle::shared_memory(name).unwrap();
+    let conn = Connection::open(format!("file:{name}?mode=memory&cache=shared")).unwrap();
+    (conn, handle)
+}
+
+#[tokio::test]
+async fn flushed_waits_for_the_writer_task() {
+    let (conn, handle) = prepared("flushed_waits_for_the_writer_task");
+    let sink = TokioConnect::new(conn);
+    let subscriber = SubscriberBuilder::new().build(sink.clone());
+
+    tracing::subscriber::with_default(subscriber, || {
+        for i in 0..500 {
+            tracing::info!(i, "queued");
+        }
+    });
+    sink.flushed().await;
+
+    let entries = handle.read_logs().unwrap();
+    assert_eq!(entries.len(), 500);
+    assert_eq!(entries[499].structured.get("i"), Some("499"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn flush_and_wait_from_synchronous_code() {
+    let (conn, handle) = prepared("flush_and_wait_from_synchronous_code");
+    let sink = TokioConnect::new(conn);
+    let subscriber = SubscriberBuilder::new().build(sink.clone());
+
+    tokio::task::spawn_blocking(move || {
+        tracing::subscriber::with_default(subscriber, || tracing::warn!("from a thread"));
+        sink.flush_and_wait(Duration::from_secs(5)).unwrap();
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(handle.read_logs().unwrap()[0].message, "from a thread");
+}
+
+#[tokio::test]
+async fn the_writer_task_prepares_the_database() {
+    let uri = "file:the_writer_task_prepares_the_database?mode=memory&cache=shared";
+    let reader = Connection::open(uri).unwrap();
+    let sink = TokioConnect::new(Connection::open(uri).unwrap());
+    let subscriber = SubscriberBuilder::new().build(sink.clone());
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("first");
+        tracing::info!("second");
+    });
+    sink.flushed().await;
+
+    let rows: i64 = reader
+        .query_row("SELECT count(*) FROM logs_v0", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(rows, 2);
+}
+
+#[test]
+fn the_runtime_shuts_down_while_the_sink_is_held() {
+    let (conn, handle) = prepared("the_runtime_shuts_down_while_the_sink_is_held");
+    let (shut_down, finished) = mpsc::channel();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+        let sink = runtime.block_on(async { TokioConnect::new(conn) });
+        let subscriber = SubscriberBuilder::new().build(sink.clone());
+        runtime.block_on(async {
+            tracing::subscriber::with_default(subscriber, || tracing::info!("before shutdown"));
+            sink.flushed().await;
+        });
+        // like the sink of a global subscriber, it is never dropped
+        std::mem::forget(sink);
+        drop(runtime);
+        shut_down.send(()).unwrap();
+    });
+
+    finished
+        .recv_timeout(Duration::from_secs(10))
+        .expect("the runtime didn't shut down");
+    assert_eq!(handle.read_logs().unwrap()[0].message, "before shutdown");
+}
+
+#[tokio::test]
+async fn a_full_queue_drops_new_entries() {
+    let (conn, handle) = prepared("a_full_queue_drops_new_entries");
+    let sink = TokioConnect::new(conn).with_capacity(10);
+    let subscriber = SubscriberBuilder::new().build(sink.clone());
+    let stats = subscriber.stats_handle();
+
+    // the writer task can't run on this thread until the test awaits
+    tracing::subscriber::with_default(subscriber, || {
+        for i in 0..25 {
+            tracing::info!(i, "queued");
+        }
+    });
+    sink.flushed().await;
+
+    let entries = handle.read_logs().unwrap();
+    assert_eq!(entries.len(), 10);
+    assert_eq!(entries[9].structured.get("i"), Some("9"));
+    assert_eq!(stats.get().dropped, 15);
+}
@@ -0,0 +1,140 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rusqlite::Connection;
+use tracing_subscriber_sqlite::{prepare_database, BatchConnect, Connect, SubscriberBuilder};
+
+fn messages(conn: &Mutex<Connection>) -> Vec<String> {
+    let conn = conn.lock().unwrap();
+    let mut stmt = conn
+        .prepare("SELECT message FROM logs_v0 ORDER BY rowid")
+        .unwrap();
+    stmt.query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap()
+}
+
+#[test]
+fn failed_batches_are_written_later() {
+    let conn = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+    let batch = BatchConnect::new(conn.clone(), 2, Duration::from_secs(3600));
+    let errors = Arc::new(Mutex::new(0));
+    let subscriber = SubscriberBuilder::new()
+        .with_auto_prepare(false)
+        .with_error_handler({
+            let errors = errors.clone();
+            move |_| *errors.lock().unwrap() += 1
+        })
+        .build(batch.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("first");
+        // the missing table fails this batch
+        tracing::info!("second");
+        tracing::info!("third");
+    });
+    assert_eq!(*errors.lock().unwrap(), 1);
+    assert_eq!(batch.pending(), 3);
+
+    prepare_database(&conn.lock().unwrap()).unwrap();
+    batch.flush().unwrap();
+    assert_eq!(batch.pending(), 0);
+    assert_eq!(messages(&conn), ["first", "second", "third"]);
+}
+
+#[test]
+fn failed_batches_go_to_the_fallback() {
+    let conn = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+    let spare = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+    prepare_database(&spare.lock().unwrap()).unwrap();
+    let batch =
+        BatchConnect::new(conn.clone(), 2, Duration::from_secs(3600)).with_fallback(spare.clone());
+    let subscriber = SubscriberBuilder::new()
+        .with_auto_prepare(false)
+        .build(batch.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("first");
+        tracing::info!("second");
+    });
+
+    assert_eq!(batch.pending(), 0);
+    assert_eq!(messages(&spare), ["first", "second"]);
+}
+
+#[test]
+fn auto_prepare_writes_the_first_batch_right_away() {
+    let conn = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+    let batch = BatchConnect::new(conn.clone(), 100, Duration::from_secs(3600));
+    let subscriber = SubscriberBuilder::new()
+        .with_auto_prepare(true)
+        .build(batch.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("first");
+        tracing::info!("second");
+    });
+
+    assert_eq!(messages(&conn), ["first"]);
+    batch.flush().unwrap();
+    assert_eq!(messages(&conn), ["first", "second"]);
+}
+
+#[test]
+fn presets_build_batched_subscribers() {
+    let conn = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+    let subscriber = SubscriberBuilder::production()
+        .with_batching(2, Duration::from_secs(3600))
+        .build_batched(conn.clone())
+        .unwrap();
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::debug!("below the level");
+        tracing::info!("first");
+        assert!(messages(&conn).is_empty());
+
+        tracing::info!("second");
+        assert_eq!(messages(&conn), ["first", "second"]);
+    });
+}
+
+#[test]
+fn entries_kept_for_the_next_batch_skip_the_layer_fallback() {
+    let conn = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+    let spare = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+    prepare_database(&spare.lock().unwrap()).unwrap();
+    let batch = BatchConnect::new(conn.clone(), 2, Duration::from_secs(3600));
+    let subscriber = SubscriberBuilder::new()
+        .with_auto_prepare(false)
+        .with_fallback(spare.clone())
+        .with_error_handler(|_| {})
+        .build(batch.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("first");
+        // the missing table fails this batch
+        tracing::info!("second");
+    });
+    assert!(messages(&spare).is_empty());
+
+    prepare_database(&conn.lock().unwrap()).unwrap();
+    batch.flush().unwrap();
+    assert_eq!(messages(&conn), ["first", "second"]);
+}
+
+#[test]
+fn entries_beyond_the_retained_batches_are_counted_as_dropped() {
+    let conn = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+    let batch = BatchConnect::new(conn.clone(), 1, Duration::ZERO);
+    let subscriber = SubscriberBuilder::new()
+        .with_auto_prepare(false)
+        .with_error_handler(|_| {})
+        .build(batch.clone());
+    let stats = subscriber.stats_handle();
+    tracing::subscriber::with_default(subscriber, || {
+        for i in 0..20 {
+            tracing::info!(i, "failing");
+        }
+    });
+
+    assert_eq!(batch.pending(), 16);
+    assert_eq!(stats.get().dropped, 4);
+}
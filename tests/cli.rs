@@ -0,0 +1,57 @@
+#![cfg(feature = "cli")]
+
+use std::{
+    fs,
+    process::Command,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use rusqlite::Connection;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing_subscriber_sqlite::{LogHandle, SubscriberBuilder};
+
+fn tracing_sqlite(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_tracing-sqlite"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn prune_deletes_only_old_matching_entries() {
+    let path =
+        std::env::temp_dir().join(format!("tracing-sqlite-test-cli-{}.db", std::process::id()));
+    let subscriber = SubscriberBuilder::new()
+        .build_prepared(Arc::new(Mutex::new(Connection::open(&path).unwrap())))
+        .unwrap();
+    let cutoff = tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("old info");
+        tracing::warn!("old warning");
+        tracing::error!("old error");
+        thread::sleep(Duration::from_millis(10));
+        let cutoff = OffsetDateTime::now_utc();
+        thread::sleep(Duration::from_millis(10));
+        tracing::warn!("new warning");
+        cutoff
+    });
+    let database = path.to_str().unwrap();
+    let cutoff = cutoff.format(&Rfc3339).unwrap();
+
+    let pruned = tracing_sqlite(&[database, "prune", "--before", &cutoff, "--level", "warn"]);
+    // --expired ignores filters, so they are refused rather than dropped
+    let refused = tracing_sqlite(&[database, "prune", "--expired", "--level", "warn"]);
+    let entries = LogHandle::new(Connection::open(&path).unwrap()).read_logs();
+    let _ = fs::remove_file(&path);
+
+    assert!(pruned.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&pruned.stdout),
+        "deleted 2 entries\n"
+    );
+    assert!(!refused.status.success());
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("takes no filters"));
+    let messages: Vec<_> = entries.unwrap().into_iter().map(|e| e.message).collect();
+    assert_eq!(messages, ["old info", "new warning"]);
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use tracing_subscriber_sqlite::{LogHandle, SubscriberBuilder};
+
+fn rows(handle: &LogHandle) -> Vec<(String, u64)> {
+    handle
+        .read_logs()
+        .unwrap()
+        .into_iter()
+        .map(|entry| (entry.message, entry.repeat_count))
+        .collect()
+}
+
+#[test]
+fn repeats_are_written_as_one_row() {
+    let handle = LogHandle::shared_memory("repeats_are_written_as_one_row").unwrap();
+    let subscriber = SubscriberBuilder::new()
+        .with_coalescing(Duration::from_secs(60))
+        .build(handle.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        for _ in 0..4 {
+            tracing::warn!("retrying");
+        }
+        tracing::info!("connected");
+    });
+
+    assert_eq!(
+        rows(&handle),
+        [
+            ("retrying".to_owned(), 1),
+            ("retrying".to_owned(), 3),
+            ("connected".to_owned(), 1)
+        ]
+    );
+}
+
+#[test]
+fn repeats_are_written_when_the_layer_is_dropped() {
+    let handle = LogHandle::shared_memory("repeats_are_written_when_the_layer_is_dropped").unwrap();
+    let subscriber = SubscriberBuilder::new()
+        .with_coalescing(Duration::from_secs(60))
+        .build(handle.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        for _ in 0..3 {
+            tracing::warn!("retrying");
+        }
+        assert_eq!(rows(&handle), [("retrying".to_owned(), 1)]);
+    });
+
+    assert_eq!(
+        rows(&handle),
+        [("retrying".to_owned(), 1), ("retrying".to_owned(), 2)]
+    );
+}
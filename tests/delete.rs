@@ -0,0 +1,106 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use rusqlite::Connection;
+use time::OffsetDateTime;
+use tracing_subscriber_sqlite::{LogHandle, LogQuery, SubscriberBuilder};
+
+fn messages(handle: &LogHandle) -> Vec<String> {
+    handle
+        .read_logs()
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.message)
+        .collect()
+}
+
+#[test]
+fn delete_before_keeps_newer_entries() {
+    let handle = LogHandle::shared_memory("delete_before_keeps_newer_entries").unwrap();
+    let subscriber = SubscriberBuilder::new().build(handle.clone());
+    let cutoff = tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("old");
+        tracing::info!("older");
+        thread::sleep(Duration::from_millis(10));
+        let cutoff = OffsetDateTime::now_utc();
+        thread::sleep(Duration::from_millis(10));
+        tracing::info!("new");
+        cutoff
+    });
+
+    assert_eq!(handle.delete_before(cutoff).unwrap(), 2);
+    assert_eq!(messages(&handle), ["new"]);
+}
+
+#[test]
+fn delete_matching_deletes_only_matching_entries() {
+    let name = "delete_matching_deletes_only_matching_entries";
+    let handle = LogHandle::shared_memory(name).unwrap();
+    let conn = Connection::open(format!("file:{name}?mode=memory&cache=shared")).unwrap();
+    let subscriber = SubscriberBuilder::new().build(handle.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(user = "alice", "first");
+        tracing::info!(user = "bob", "second");
+        tracing::info!(user = "alice", "third");
+    });
+    let rowids = || -> Vec<i64> {
+        let mut stmt = conn
+            .prepare("SELECT rowid FROM logs_v0 ORDER BY rowid")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    };
+    let before = rowids();
+
+    let deleted = handle
+        .delete_matching(&LogQuery::new().field("user", "alice"))
+        .unwrap();
+    assert_eq!(deleted, 2);
+
+    // the newest row was deleted, a note takes its rowid
+    assert_eq!(messages(&handle), ["second", "2 entries deleted"]);
+    assert_eq!(rowids(), before[1..]);
+}
+
+#[test]
+fn delete_expired_keeps_entries_without_a_ttl() {
+    let handle = LogHandle::shared_memory("delete_expired_keeps_entries_without_a_ttl").unwrap();
+    let subscriber = SubscriberBuilder::new().build(handle.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::debug!(log_ttl_secs = 0, "expired");
+        tracing::debug!(log_ttl_secs = 3600, "expires later");
+        tracing::info!("kept");
+        tracing::debug!(log_ttl_secs = 0, "expired but newest");
+    });
+
+    assert_eq!(handle.delete_expired().unwrap(), 1);
+    assert_eq!(
+        messages(&handle),
+        ["expires later", "kept", "expired but newest"]
+    );
+}
+
+#[test]
+fn retention_deletes_old_entries_but_the_newest() {
+    let name = "retention_deletes_old_entries_but_the_newest";
+    let handle = LogHandle::shared_memory(name).unwrap();
+    let conn = Connection::open(format!("file:{name}?mode=memory&cache=shared")).unwrap();
+    let subscriber = SubscriberBuilder::new()
+        .with_retention(Duration::from_millis(200), Duration::from_millis(20))
+        .build_prepared(Arc::new(Mutex::new(conn)))
+        .unwrap();
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("old");
+        tracing::info!("older");
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(messages(&handle), ["older"]);
+
+        tracing::info!("new");
+        assert_eq!(messages(&handle), ["older", "new"]);
+    });
+}
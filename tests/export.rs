@@ -0,0 +1,43 @@
+use tracing_subscriber_sqlite::{LogHandle, LogQuery, SubscriberBuilder};
+
+fn logged(name: &str) -> LogHandle {
+    let handle = LogHandle::shared_memory(name).unwrap();
+    tracing::subscriber::with_default(SubscriberBuilder::new().build(handle.clone()), || {
+        tracing::info!(user_id = 42, name = "alice", "signed in");
+        tracing::warn!("quota, almost \"full\"");
+    });
+    handle
+}
+
+#[test]
+fn jsonl_export_imports_into_another_database() {
+    let handle = logged("jsonl_export_imports_into_another_database");
+    let mut jsonl = Vec::new();
+    assert_eq!(
+        handle.export_jsonl(&mut jsonl, &LogQuery::new()).unwrap(),
+        2
+    );
+
+    let copy = LogHandle::shared_memory("jsonl_export_imports_into_another_database_copy").unwrap();
+    let report = copy.import_jsonl(jsonl.as_slice()).unwrap();
+    assert_eq!(report.imported, 2);
+    assert!(report.errors.is_empty());
+
+    let entries = copy.read_logs().unwrap();
+    assert_eq!(entries[0].structured.get("user_id"), Some("42"));
+    assert_eq!(entries[0].structured.get("name"), Some("\"alice\""));
+    assert_eq!(entries[1].level, tracing::Level::WARN);
+}
+
+#[test]
+fn csv_export_quotes_fields() {
+    let handle = logged("csv_export_quotes_fields");
+    let mut csv = Vec::new();
+    let query = LogQuery::new().max_level(tracing::Level::WARN);
+    assert_eq!(handle.export_csv(&mut csv, &query).unwrap(), 1);
+
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert!(lines[0].starts_with("time,level,module,"));
+    assert!(lines[1].contains(",\"quota, almost \"\"full\"\"\","));
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "http")]
+
+use tracing::Level;
+use tracing_subscriber_sqlite::{LogHandle, LogQuery, LogServer, SubscriberBuilder};
+
+fn server(name: &str) -> LogServer {
+    let handle = LogHandle::shared_memory(name).unwrap();
+    let subscriber = SubscriberBuilder::new().build(handle.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("routine");
+        tracing::warn!("served");
+        tracing::error!("served too");
+    });
+    LogServer::new(handle).with_query(LogQuery::new().max_level(Level::WARN).module("http"))
+}
+
+#[test]
+fn parameters_narrow_the_served_query() {
+    let server = server("parameters_narrow_the_served_query");
+
+    let (status, body) = server.respond("GET", "/logs?level=trace");
+    assert_eq!(status, 200);
+    assert_eq!(body["entries"].as_array().unwrap().len(), 2);
+
+    let (status, body) = server.respond("GET", "/logs?level=error&module=http%3A%3A");
+    assert_eq!(status, 200);
+    assert_eq!(body["entries"].as_array().unwrap().len(), 0);
+    let (_, body) = server.respond("GET", "/logs?level=error");
+    assert_eq!(body["entries"][0]["message"], "served too");
+
+    let (status, _) = server.respond("GET", "/logs?module=other");
+    assert_eq!(status, 400);
+    let (status, _) = server.respond("GET", "/logs?module=");
+    assert_eq!(status, 400);
+}
+
+#[test]
+fn stats_count_only_served_entries() {
+    let server = server("stats_count_only_served_entries");
+
+    let (status, body) = server.respond("GET", "/stats");
+    assert_eq!(status, 200);
+    assert_eq!(body["rows"], 2);
+    let (_, body) = server.respond("GET", "/stats?level=error");
+    assert_eq!(body["rows"], 1);
+    let (status, _) = server.respond("GET", "/stats?module=other");
+    assert_eq!(status, 400);
+}
@@ -0,0 +1,79 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use rusqlite::Connection;
+use tracing_subscriber_sqlite::{LogHandle, SubscriberBuilder};
+
+/// An empty directory for the files of one test.
+fn directory(test: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("tracing-sqlite-test-{test}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// The names of the files in `dir`, sorted.
+fn files(dir: &PathBuf) -> Vec<String> {
+    let mut names: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn corrupt_databases_are_moved_aside() {
+    let dir = directory("corrupt_databases_are_moved_aside");
+    let path = dir.join("logs.db");
+    let garbage = vec![0x42; 4096];
+    fs::write(&path, &garbage).unwrap();
+
+    let conn = Arc::new(Mutex::new(Connection::open(&path).unwrap()));
+    let subscriber = SubscriberBuilder::new()
+        .with_corruption_recovery()
+        .build_prepared(conn)
+        .unwrap();
+    tracing::subscriber::with_default(subscriber, || tracing::info!("after the recovery"));
+
+    let names = files(&dir);
+    let aside = names
+        .iter()
+        .find(|name| name.starts_with("logs.db.corrupt-"))
+        .map(|name| fs::read(dir.join(name)).unwrap());
+    let entries = LogHandle::new(Connection::open(&path).unwrap()).read_logs();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(aside, Some(garbage));
+    let entries = entries.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].message, "after the recovery");
+}
+
+#[test]
+fn intact_databases_are_kept() {
+    let dir = directory("intact_databases_are_kept");
+    let path = dir.join("logs.db");
+    let subscriber = SubscriberBuilder::new()
+        .build_prepared(Arc::new(Mutex::new(Connection::open(&path).unwrap())))
+        .unwrap();
+    tracing::subscriber::with_default(subscriber, || tracing::info!("before"));
+
+    let subscriber = SubscriberBuilder::new()
+        .with_corruption_recovery()
+        .build_prepared(Arc::new(Mutex::new(Connection::open(&path).unwrap())))
+        .unwrap();
+    tracing::subscriber::with_default(subscriber, || tracing::info!("after"));
+
+    let names = files(&dir);
+    let entries = LogHandle::new(Connection::open(&path).unwrap()).read_logs();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(names.iter().all(|name| !name.contains("corrupt")));
+    let messages: Vec<_> = entries.unwrap().into_iter().map(|e| e.message).collect();
+    assert_eq!(messages, ["before", "after"]);
+}
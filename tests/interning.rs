@@ -0,0 +1,58 @@
+use std::sync::{Arc, Mutex};
+
+use rusqlite::Connection;
+use tracing_subscriber_sqlite::{prepare_database, LogHandle, LogQuery, SubscriberBuilder};
+
+#[test]
+fn module_and_file_are_read_from_text_and_strings() {
+    let name = "module_and_file_are_read_from_text_and_strings";
+    let handle = LogHandle::shared_memory(name).unwrap();
+    let conn = Connection::open(format!("file:{name}?mode=memory&cache=shared")).unwrap();
+    // a table of the first release, which stored module and file as text
+    conn.execute_batch(
+        "DROP TABLE logs_v0;
+         CREATE TABLE logs_v0 (
+             time NOT NULL, level TEXT NOT NULL, module TEXT, file TEXT, line INTEGER,
+             message TEXT NOT NULL, structured TEXT NOT NULL
+         );
+         INSERT INTO logs_v0 VALUES
+             ('2024-01-01T00:00:00Z', 'INFO', 'old::module', 'src/old.rs', 1, 'old', '{}');",
+    )
+    .unwrap();
+    prepare_database(&conn).unwrap();
+
+    let conn = Arc::new(Mutex::new(conn));
+    let subscriber = SubscriberBuilder::new().build(conn.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("new");
+        tracing::info!("again");
+    });
+
+    let stored: Vec<(Option<String>, Option<i64>)> = {
+        let conn = conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT module, module_id FROM logs_v0 ORDER BY rowid")
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    };
+    assert_eq!(stored[0], (Some("old::module".to_owned()), None));
+    assert_eq!(stored[1].0, None);
+    assert!(stored[1].1.is_some());
+    assert_eq!(stored[1].1, stored[2].1);
+
+    let entries = handle.read_logs().unwrap();
+    assert_eq!(entries[0].module.as_deref(), Some("old::module"));
+    assert_eq!(entries[0].file.as_deref(), Some("src/old.rs"));
+    assert_eq!(entries[1].module.as_deref(), Some(module_path!()));
+    assert_eq!(entries[1].file.as_deref(), Some(file!()));
+
+    let old = handle.query(&LogQuery::new().module("old")).unwrap();
+    assert_eq!(old.len(), 1);
+    let new = handle
+        .query(&LogQuery::new().module(module_path!()))
+        .unwrap();
+    assert_eq!(new.len(), 2);
+}
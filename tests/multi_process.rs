@@ -0,0 +1,70 @@
+use std::{
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rusqlite::Connection;
+use tracing_subscriber_sqlite::{LogHandle, SubscriberBuilder};
+
+/// Set for the processes `processes_share_one_database` starts, to the index of the worker.
+const WORKER_ENV: &str = "TRACING_SQLITE_TEST_WORKER";
+
+fn database() -> PathBuf {
+    std::env::temp_dir().join(format!("tracing-sqlite-test-{}.db", std::process::id()))
+}
+
+/// Writes the events of one worker when started by `processes_share_one_database`.
+#[test]
+fn worker() {
+    let (Ok(worker), Ok(path)) = (std::env::var(WORKER_ENV), std::env::var("DATABASE")) else {
+        return;
+    };
+    let subscriber = SubscriberBuilder::new()
+        .with_pragma("journal_mode", "WAL")
+        .with_write_timeout(Duration::from_secs(10))
+        .build_prepared(Arc::new(Mutex::new(Connection::open(path).unwrap())))
+        .unwrap();
+    tracing::subscriber::with_default(subscriber, || {
+        for i in 0..50 {
+            tracing::info!(worker = worker.as_str(), i, "working");
+        }
+    });
+}
+
+#[test]
+fn processes_share_one_database() {
+    if std::env::var(WORKER_ENV).is_ok() {
+        return;
+    }
+    let path = database();
+    let exe = std::env::current_exe().unwrap();
+    let workers: Vec<_> = (0..3)
+        .map(|worker| {
+            Command::new(&exe)
+                .args(["--exact", "worker", "--quiet"])
+                .env(WORKER_ENV, worker.to_string())
+                .env("DATABASE", &path)
+                .stdout(Stdio::null())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    let statuses: Vec<_> = workers.into_iter().map(|mut w| w.wait().unwrap()).collect();
+
+    let entries = LogHandle::new(Connection::open(&path).unwrap()).read_logs();
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+    assert!(statuses.iter().all(|s| s.success()));
+    let entries = entries.unwrap();
+    assert_eq!(entries.len(), 150);
+    for worker in 0..3 {
+        let written = entries
+            .iter()
+            .filter(|e| e.structured.get("worker") == Some(format!("\"{worker}\"").as_str()))
+            .count();
+        assert_eq!(written, 50);
+    }
+}
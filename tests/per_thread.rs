@@ -0,0 +1,47 @@
+use std::{fs, thread};
+
+use rusqlite::Connection;
+use tracing::Dispatch;
+use tracing_subscriber_sqlite::{LogHandle, PerThreadConnect, SubscriberBuilder};
+
+#[test]
+fn entries_of_all_threads_go_to_one_database() {
+    let path = std::env::temp_dir().join(format!(
+        "tracing-sqlite-test-per-thread-{}.db",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    let dispatch =
+        Dispatch::new(SubscriberBuilder::new().build(PerThreadConnect::open(&path).unwrap()));
+    let threads: Vec<_> = (0..4)
+        .map(|thread| {
+            let dispatch = dispatch.clone();
+            thread::spawn(move || {
+                tracing::dispatcher::with_default(&dispatch, || {
+                    for i in 0..25 {
+                        tracing::info!(thread, i, "entry");
+                    }
+                })
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    drop(dispatch);
+
+    let entries = LogHandle::new(Connection::open(&path).unwrap())
+        .read_logs()
+        .unwrap();
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+    assert_eq!(entries.len(), 100);
+    for thread in 0..4 {
+        let written = entries
+            .iter()
+            .filter(|entry| entry.structured.get("thread") == Some(&thread.to_string()))
+            .count();
+        assert_eq!(written, 25);
+    }
+}
@@ -0,0 +1,59 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use rusqlite::Connection;
+use tracing_subscriber_sqlite::{LogHandle, SubscriberBuilder};
+
+fn stats(conn: &Connection) -> Vec<(String, i64, i64, Option<i64>)> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT module, events, errors, p95_span_duration_us FROM daily_module_stats_v0
+             ORDER BY module",
+        )
+        .unwrap();
+    stmt.query_map([], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+    })
+    .unwrap()
+    .collect::<rusqlite::Result<_>>()
+    .unwrap()
+}
+
+#[test]
+fn rollup_counts_events_and_span_durations() {
+    let name = "rollup_counts_events_and_span_durations";
+    let handle = LogHandle::shared_memory(name).unwrap();
+    let conn = Connection::open(format!("file:{name}?mode=memory&cache=shared")).unwrap();
+    let subscriber = SubscriberBuilder::new().build(handle.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        for duration in 1..=20 {
+            tracing::info!(canonical.duration_us = duration, "request");
+        }
+        tracing::error!("failed");
+    });
+
+    assert_eq!(handle.rollup_daily_module_stats().unwrap(), 1);
+    assert_eq!(stats(&conn), [("rollup".to_owned(), 21, 1, Some(19))]);
+}
+
+#[test]
+fn daily_rollup_runs_in_the_background() {
+    let name = "daily_rollup_runs_in_the_background";
+    let conn = Connection::open(format!("file:{name}?mode=memory&cache=shared")).unwrap();
+    let subscriber = SubscriberBuilder::new()
+        .with_daily_rollup(Duration::from_millis(20))
+        .build_prepared(Arc::new(Mutex::new(
+            Connection::open(format!("file:{name}?mode=memory&cache=shared")).unwrap(),
+        )))
+        .unwrap();
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::warn!("slow");
+        tracing::info!("done");
+        thread::sleep(Duration::from_millis(200));
+    });
+
+    assert_eq!(stats(&conn), [("rollup".to_owned(), 2, 0, None)]);
+}
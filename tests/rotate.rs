@@ -0,0 +1,146 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use rusqlite::Connection;
+use time::{Duration, OffsetDateTime};
+use tracing_subscriber_sqlite::{
+    Connect, LogEntry, LogHandle, RotatingConnect, Rotation, SubscriberBuilder,
+};
+
+/// An empty directory for the files of one test.
+fn directory(test: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("tracing-sqlite-test-{test}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn messages(path: &PathBuf) -> Vec<String> {
+    LogHandle::new(Connection::open(path).unwrap())
+        .read_logs()
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.message)
+        .collect()
+}
+
+/// Logs `messages` to `rotating`, failing the test on errors.
+fn log_to(rotating: &Arc<RotatingConnect>, messages: &[&str]) {
+    let rotating = rotating.clone();
+    let subscriber =
+        SubscriberBuilder::new().build(move |entry: LogEntry<&str>| rotating.log(entry).unwrap());
+    tracing::subscriber::with_default(subscriber, || {
+        for message in messages {
+            tracing::info!("{message}");
+        }
+    });
+}
+
+/// `hour` o'clock on 2024-06-`day`, in UTC.
+fn june(day: i64, hour: i64) -> OffsetDateTime {
+    OffsetDateTime::UNIX_EPOCH + Duration::days(19_874 + day) + Duration::hours(hour)
+}
+
+/// Logs each message to `rotating` with its time as the entry time.
+fn log_at(rotating: &Arc<RotatingConnect>, messages: &[(&str, OffsetDateTime)]) {
+    let time = Arc::new(Mutex::new(messages[0].1));
+    let clock = time.clone();
+    let rotating = rotating.clone();
+    let subscriber = SubscriberBuilder::new()
+        .with_clock(move || *clock.lock().unwrap())
+        .build(move |entry: LogEntry<&str>| rotating.log(entry).unwrap());
+    tracing::subscriber::with_default(subscriber, || {
+        for (message, at) in messages {
+            *time.lock().unwrap() = *at;
+            tracing::info!("{message}");
+        }
+    });
+}
+
+#[test]
+fn entries_from_an_earlier_day_stay_in_the_current_file() {
+    let dir = directory("entries_from_an_earlier_day_stay_in_the_current_file");
+    let rotating =
+        Arc::new(RotatingConnect::new(dir.join("logs.db")).with_rotation(Rotation::Daily));
+    log_at(
+        &rotating,
+        &[
+            ("today", june(2, 10)),
+            ("clock went back", june(1, 23)),
+            ("tomorrow", june(3, 0)),
+        ],
+    );
+
+    let (today, tomorrow) = (
+        messages(&dir.join("logs-2024-06-02.db")),
+        messages(&dir.join("logs-2024-06-03.db")),
+    );
+    let earlier = dir.join("logs-2024-06-01.db").exists();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(today, ["today", "clock went back"]);
+    assert_eq!(tomorrow, ["tomorrow"]);
+    assert!(!earlier);
+}
+
+#[test]
+fn hourly_rotation_starts_a_file_per_hour() {
+    let dir = directory("hourly_rotation_starts_a_file_per_hour");
+    let rotating =
+        Arc::new(RotatingConnect::new(dir.join("logs.db")).with_rotation(Rotation::Hourly));
+    log_at(
+        &rotating,
+        &[
+            ("ten", june(2, 10)),
+            ("still ten", june(2, 10) + Duration::minutes(59)),
+            ("eleven", june(2, 11)),
+        ],
+    );
+
+    let (ten, eleven) = (
+        messages(&dir.join("logs-2024-06-02-10.db")),
+        messages(&dir.join("logs-2024-06-02-11.db")),
+    );
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(ten, ["ten", "still ten"]);
+    assert_eq!(eleven, ["eleven"]);
+}
+
+#[test]
+fn rotate_moves_the_database_aside() {
+    let dir = directory("rotate_moves_the_database_aside");
+    let rotating = Arc::new(RotatingConnect::new(dir.join("logs.db")));
+    log_to(&rotating, &["before"]);
+
+    let moved = rotating.rotate().unwrap();
+    log_to(&rotating, &["after"]);
+
+    let (archived, current) = (messages(&moved), messages(&dir.join("logs.db")));
+    let rotated = rotating.rotated_files().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(archived, ["before"]);
+    assert_eq!(current, ["after"]);
+    assert_eq!(rotated, [moved]);
+}
+
+#[test]
+fn max_files_deletes_the_oldest_databases() {
+    let dir = directory("max_files_deletes_the_oldest_databases");
+    // every entry fills the database
+    let rotating = Arc::new(
+        RotatingConnect::new(dir.join("logs.db"))
+            .with_max_db_size(1)
+            .with_max_files(2),
+    );
+    log_to(&rotating, &["first", "second", "third", "fourth"]);
+
+    let rotated = rotating.rotated_files().unwrap();
+    let kept: Vec<_> = rotated.iter().map(messages).collect();
+    let current = messages(&dir.join("logs.db"));
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(kept, [["third"], ["fourth"]]);
+    assert!(current.is_empty());
+}
@@ -0,0 +1,75 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use tracing_subscriber_sqlite::{LogHandle, ShardManager, SubscriberBuilder};
+
+/// An empty directory for the databases of one test.
+fn directory(test: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("tracing-sqlite-test-{test}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn messages(handle: &LogHandle) -> Vec<String> {
+    handle
+        .read_logs()
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.message)
+        .collect()
+}
+
+#[test]
+fn entries_go_to_the_database_of_their_tenant() {
+    let dir = directory("entries_go_to_the_database_of_their_tenant");
+    let subscriber = SubscriberBuilder::new().build(
+        ShardManager::new(&dir, "tenant")
+            .with_capacity(1)
+            .with_default_tenant("shared"),
+    );
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(tenant = "acme", "acme first");
+        tracing::info!(tenant = "globex", "globex first");
+        // reopens the database of acme, only one is kept open
+        tracing::info!(tenant = "acme", "acme second");
+        tracing::info!("without a tenant");
+    });
+
+    let shards = ShardManager::new(&dir, "tenant");
+    let acme = messages(&shards.handle("acme").unwrap());
+    let globex = messages(&shards.handle("globex").unwrap());
+    let shared = messages(&shards.handle("shared").unwrap());
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(acme, ["acme first", "acme second"]);
+    assert_eq!(globex, ["globex first"]);
+    assert_eq!(shared, ["without a tenant"]);
+}
+
+#[test]
+fn entries_without_a_valid_tenant_fail() {
+    let dir = directory("entries_without_a_valid_tenant_fail");
+    let errors = Arc::new(Mutex::new(0));
+    let counted = errors.clone();
+    let subscriber = SubscriberBuilder::new()
+        .with_error_handler(move |_| *counted.lock().unwrap() += 1)
+        .build(ShardManager::new(&dir, "tenant"));
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("without a tenant");
+        tracing::info!(tenant = "../escape", "outside the directory");
+        tracing::info!(tenant = "acme", "written");
+    });
+
+    let acme = messages(&ShardManager::new(&dir, "tenant").handle("acme").unwrap());
+    let files: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|file| file.unwrap().file_name())
+        .collect();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(*errors.lock().unwrap(), 2);
+    assert_eq!(acme, ["written"]);
+    assert_eq!(files, ["acme.db"]);
+}
@@ -0,0 +1,79 @@
+use std::thread;
+
+use tracing_subscriber_sqlite::{LogHandle, SubscriberBuilder};
+
+#[test]
+fn clones_read_what_the_writer_wrote() {
+    let handle = LogHandle::shared_memory("clones_read_what_the_writer_wrote").unwrap();
+    let subscriber = SubscriberBuilder::new().build(handle.clone());
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(answer = 42, "hello");
+    });
+
+    let reader = handle.clone();
+    let entries = thread::spawn(move || reader.read_logs().unwrap())
+        .join()
+        .unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].message, "hello");
+    assert_eq!(entries[0].structured.get("answer"), Some("42"));
+}
+
+#[test]
+fn concurrent_reads_while_writing() {
+    let handle = LogHandle::shared_memory("concurrent_reads_while_writing").unwrap();
+    let subscriber = SubscriberBuilder::new().build(handle.clone());
+
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let reader = handle.clone();
+            thread::spawn(move || {
+                let mut seen = 0;
+                while seen < 200 {
+                    let len = reader.read_logs().unwrap().len();
+                    assert!(len >= seen, "entries disappeared");
+                    seen = len;
+                }
+            })
+        })
+        .collect();
+
+    let writer = thread::spawn(move || {
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..200 {
+                tracing::info!(i, "event");
+            }
+        });
+    });
+
+    writer.join().unwrap();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    assert_eq!(handle.read_logs().unwrap().len(), 200);
+}
+
+#[test]
+fn names_are_separate_databases() {
+    let first = LogHandle::shared_memory("names_are_separate_databases_1").unwrap();
+    let second = LogHandle::shared_memory("names_are_separate_databases_2").unwrap();
+
+    tracing::subscriber::with_default(SubscriberBuilder::new().build(first.clone()), || {
+        tracing::warn!("only in the first");
+    });
+
+    assert_eq!(first.clone().read_logs().unwrap().len(), 1);
+    assert!(second.clone().read_logs().unwrap().is_empty());
+}
+
+#[test]
+fn same_name_opens_the_same_database() {
+    let writer = LogHandle::shared_memory("same_name_opens_the_same_database").unwrap();
+    tracing::subscriber::with_default(SubscriberBuilder::new().build(writer.clone()), || {
+        tracing::error!("shared");
+    });
+
+    let other = LogHandle::shared_memory("same_name_opens_the_same_database").unwrap();
+    assert_eq!(other.read_logs().unwrap()[0].message, "shared");
+}
@@ -0,0 +1,167 @@
+use rusqlite::Connection;
+use tracing_subscriber_sqlite::{
+    prepare_database, LogHandle, LogQuery, StructuredFormat, SubscriberBuilder,
+};
+
+/// A prepared database with one row whose `structured` column is `value`.
+fn with_structured(name: &str, value: rusqlite::types::Value) -> LogHandle {
+    let handle = LogHandle::shared_memory(name).unwrap();
+    let conn = Connection::open(format!("file:{name}?mode=memory&cache=shared")).unwrap();
+    prepare_database(&conn).unwrap();
+    conn.execute(
+        "INSERT INTO logs_v0 (time, level, message, structured) \
+         VALUES ('2024-06-01 12:00:00', 'INFO', 'written elsewhere', ?1)",
+        [value],
+    )
+    .unwrap();
+    handle
+}
+
+#[test]
+fn fields_that_are_not_strings_are_kept() {
+    let handle = with_structured(
+        "fields_that_are_not_strings_are_kept",
+        r#"{"user": "alice", "attempt": 3, "retry": true, "tags": ["a"]}"#
+            .to_owned()
+            .into(),
+    );
+    let entries = handle.read_logs().unwrap();
+    let fields = &entries[0].structured;
+    assert_eq!(fields.get("user"), Some("alice"));
+    assert_eq!(fields.get("attempt"), Some("3"));
+    assert_eq!(fields.get("retry"), Some("true"));
+    assert_eq!(fields.get("tags"), Some(r#"["a"]"#));
+}
+
+#[test]
+fn undecodable_fields_fail_the_read() {
+    let blob = with_structured("undecodable_blob", vec![0x42, 0x13, 0x37].into());
+    assert!(matches!(
+        blob.read_logs(),
+        Err(rusqlite::Error::FromSqlConversionFailure(6, _, _))
+    ));
+
+    let text = with_structured("undecodable_text", "{not json".to_owned().into());
+    assert!(matches!(
+        text.read_logs(),
+        Err(rusqlite::Error::FromSqlConversionFailure(6, _, _))
+    ));
+}
+
+#[test]
+fn numbers_and_booleans_are_stored_typed() {
+    let name = "numbers_and_booleans_are_stored_typed";
+    let handle = LogHandle::shared_memory(name).unwrap();
+    let conn = Connection::open(format!("file:{name}?mode=memory&cache=shared")).unwrap();
+    let subscriber = SubscriberBuilder::new().build(handle.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(
+            user_id = 42,
+            retry = true,
+            ratio = 0.5,
+            user = "42",
+            "typed"
+        );
+        tracing::info!(user_id = 7, retry = false, "other");
+    });
+
+    let types: (String, String, String, String) = conn
+        .query_row(
+            "SELECT json_type(structured, '$.user_id'), json_type(structured, '$.retry'),
+                    json_type(structured, '$.ratio'), json_type(structured, '$.user')
+             FROM logs_v0 WHERE message = 'typed'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .unwrap();
+    assert_eq!(
+        types,
+        (
+            "integer".into(),
+            "true".into(),
+            "real".into(),
+            "text".into()
+        )
+    );
+
+    let messages = |query: LogQuery| -> Vec<String> {
+        handle
+            .query(&query)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect()
+    };
+    assert_eq!(messages(LogQuery::new().field("user_id", 42)), ["typed"]);
+    assert_eq!(messages(LogQuery::new().field("retry", false)), ["other"]);
+    assert_eq!(messages(LogQuery::new().field("ratio", 0.5)), ["typed"]);
+    assert_eq!(messages(LogQuery::new().field("user", "42")), ["typed"]);
+    // the string "42" is not the number 42, and `true` is not 1
+    assert_eq!(
+        messages(LogQuery::new().field("user", 42)),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        messages(LogQuery::new().field("retry", 1)),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn fields_stored_as_strings_still_match() {
+    let handle = with_structured(
+        "fields_stored_as_strings_still_match",
+        r#"{"user_id": "42", "retry": "true"}"#.to_owned().into(),
+    );
+    let query = LogQuery::new().field("user_id", 42).field("retry", true);
+    assert_eq!(handle.query(&query).unwrap().len(), 1);
+}
+
+#[test]
+fn json_text_opts_out_of_jsonb() {
+    let name = "json_text_opts_out_of_jsonb";
+    let handle = LogHandle::shared_memory(name).unwrap();
+    let conn = Connection::open(format!("file:{name}?mode=memory&cache=shared")).unwrap();
+    let subscriber = SubscriberBuilder::new()
+        .with_structured_format(StructuredFormat::JsonText)
+        .build(handle.clone());
+    tracing::subscriber::with_default(subscriber, || tracing::info!(user_id = 42, "text"));
+
+    let stored: (String, String) = conn
+        .query_row(
+            "SELECT typeof(structured), structured FROM logs_v0",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(stored, ("text".to_owned(), r#"{"user_id":42}"#.to_owned()));
+    assert_eq!(
+        handle
+            .query(&LogQuery::new().field("user_id", 42))
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn field_indexes_of_older_versions_are_recreated() {
+    let name = "field_indexes_of_older_versions_are_recreated";
+    let handle = with_structured(name, r#"{"user_id": 42}"#.to_owned().into());
+    let conn = Connection::open(format!("file:{name}?mode=memory&cache=shared")).unwrap();
+    conn.execute_batch(
+        "CREATE INDEX logs_v0_field_user_id ON logs_v0 (json_extract(structured, '$.\"user_id\"'))",
+    )
+    .unwrap();
+
+    prepare_database(&conn).unwrap();
+    let sql: String = conn
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE name = 'logs_v0_field_user_id'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(sql.contains("->>"), "{sql}");
+    assert_eq!(handle.indexed_fields().unwrap(), ["user_id"]);
+}
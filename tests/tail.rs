@@ -0,0 +1,76 @@
+use std::{thread, time::Duration};
+
+use tracing_subscriber_sqlite::{LogHandle, LogQuery, SubscriberBuilder, TailOptions};
+
+#[test]
+fn tail_delivers_only_new_matching_entries() {
+    let handle = LogHandle::shared_memory("tail_delivers_only_new_matching_entries").unwrap();
+    let subscriber = SubscriberBuilder::new().build(handle.clone());
+    tracing::subscriber::with_default(subscriber, || tracing::error!("before the tail"));
+
+    let mut tail = handle
+        .tail(
+            LogQuery::new().max_level(tracing::Level::WARN),
+            TailOptions::default(),
+        )
+        .unwrap();
+    let subscriber = SubscriberBuilder::new().build(handle.clone());
+    let writer = thread::spawn(move || {
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("not matching");
+            tracing::warn!("matching");
+        })
+    });
+
+    let batch = tail.next_batch().unwrap();
+    writer.join().unwrap();
+    assert_eq!(batch.len(), 1);
+    assert_eq!(batch[0].message, "matching");
+}
+
+#[test]
+fn batches_are_at_most_max_batch() {
+    let handle = LogHandle::shared_memory("batches_are_at_most_max_batch").unwrap();
+    let mut tail = handle
+        .tail(
+            LogQuery::new(),
+            TailOptions {
+                max_batch: 10,
+                max_latency: Duration::from_millis(20),
+            },
+        )
+        .unwrap();
+
+    tracing::subscriber::with_default(SubscriberBuilder::new().build(handle.clone()), || {
+        for i in 0..25 {
+            tracing::info!(i, "event");
+        }
+    });
+
+    let sizes: Vec<_> = (0..3).map(|_| tail.next_batch().unwrap().len()).collect();
+    assert_eq!(sizes, [10, 10, 5]);
+}
+
+#[test]
+fn entries_that_dont_match_are_scanned_once() {
+    let handle = LogHandle::shared_memory("entries_that_dont_match_are_scanned_once").unwrap();
+    let mut tail = handle
+        .tail(
+            LogQuery::new().max_level(tracing::Level::WARN),
+            TailOptions::default(),
+        )
+        .unwrap();
+
+    tracing::subscriber::with_default(SubscriberBuilder::new().build(handle.clone()), || {
+        for i in 0..5 {
+            tracing::info!(i, "not matching");
+        }
+    });
+
+    assert!(tail.try_next_batch().unwrap().is_empty());
+    // the tail moved past them, like a tail started now
+    let fresh = handle
+        .tail(LogQuery::new(), TailOptions::default())
+        .unwrap();
+    assert_eq!(tail.position(), fresh.position());
+}
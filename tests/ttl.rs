@@ -0,0 +1,36 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use rusqlite::Connection;
+use tracing_subscriber_sqlite::{LogHandle, SubscriberBuilder};
+
+fn messages(handle: &LogHandle) -> Vec<String> {
+    handle
+        .read_logs()
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.message)
+        .collect()
+}
+
+#[test]
+fn expiry_sweep_deletes_expired_entries_in_the_background() {
+    let name = "expiry_sweep_deletes_expired_entries_in_the_background";
+    let handle = LogHandle::shared_memory(name).unwrap();
+    let conn = Connection::open(format!("file:{name}?mode=memory&cache=shared")).unwrap();
+    let subscriber = SubscriberBuilder::new()
+        .with_expiry_sweep(Duration::from_millis(20))
+        .build_prepared(Arc::new(Mutex::new(conn)))
+        .unwrap();
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::debug!(log_ttl_secs = 0, "expired");
+        tracing::debug!(log_ttl_secs = 3600, "expires later");
+        tracing::info!("kept");
+        thread::sleep(Duration::from_millis(200));
+    });
+
+    assert_eq!(messages(&handle), ["expires later", "kept"]);
+}
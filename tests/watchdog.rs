@@ -0,0 +1,56 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use rusqlite::{Connection, ErrorCode};
+use tracing_subscriber_sqlite::SubscriberBuilder;
+
+#[test]
+fn slow_writes_are_interrupted() {
+    let name = "slow_writes_are_interrupted";
+    let conn = Connection::open(format!("file:{name}?mode=memory&cache=shared")).unwrap();
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let subscriber = SubscriberBuilder::new()
+        .with_write_timeout(Duration::from_millis(50))
+        .with_error_handler({
+            let errors = errors.clone();
+            move |e| errors.lock().unwrap().push(e.sqlite_error_code())
+        })
+        .build_prepared(Arc::new(Mutex::new(
+            Connection::open(format!("file:{name}?mode=memory&cache=shared")).unwrap(),
+        )))
+        .unwrap();
+    // makes every insert run for much longer than the timeout
+    conn.execute_batch(
+        "CREATE TRIGGER slow AFTER INSERT ON logs_v0 BEGIN
+             SELECT count(*) FROM (WITH RECURSIVE c(x) AS
+                 (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 1000000000) SELECT x FROM c);
+         END",
+    )
+    .unwrap();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let started = Instant::now();
+        tracing::info!("slow");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            *errors.lock().unwrap(),
+            [Some(ErrorCode::OperationInterrupted)]
+        );
+
+        // later writes are not interrupted for the one that timed out
+        conn.execute_batch("DROP TRIGGER slow").unwrap();
+        tracing::info!("fast");
+    });
+
+    let messages: Vec<String> = conn
+        .prepare("SELECT message FROM logs_v0")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap();
+    assert_eq!(messages, ["fast"]);
+    assert_eq!(errors.lock().unwrap().len(), 1);
+}